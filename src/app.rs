@@ -1,4 +1,11 @@
+mod diff_popup;
+mod passphrase_popup;
+mod sync_popup;
 mod tabs;
+mod trash_popup;
+mod usage_popup;
+
+use std::time::Duration;
 
 use anyhow::{Result, bail};
 // use anyhow::{Result, bail}
@@ -7,13 +14,18 @@ use ratatui::{
     crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     layout::{Constraint, Flex, Layout},
     prelude::*,
-    widgets::{Block, Clear, Paragraph},
+    widgets::{Block, Clear, Paragraph, Wrap},
 };
-use steamlocate::error::LocateError;
-// use steamlocate::error::LocateError;
 
-use crate::{save_manager::GameId, save_manager::SaveManager};
+use crate::{
+    compress::Recompressor, pcgw::PCGWError, save_manager::SaveManager, sources::LocalOverrides,
+};
+use diff_popup::DiffPopup;
+use passphrase_popup::PassphrasePopup;
+use sync_popup::SyncPopup;
 use tabs::SelectedTab;
+use trash_popup::TrashPopup;
+use usage_popup::UsagePopup;
 
 /// The main application which holds the state and logic of the application.
 #[derive(Debug)]
@@ -21,8 +33,22 @@ pub struct App {
     /// Is the application running?
     running: bool,
     save_swapper: SaveManager,
+    overrides: LocalOverrides,
     selected_tab: SelectedTab,
+    /// Sync started with the sync key, shown on top of the tabs
+    sync: Option<SyncPopup>,
+    /// Slot comparison opened from the saves tab
+    diff: Option<DiffPopup>,
+    /// Deleted slots and games opened from the games tab
+    trash: Option<TrashPopup>,
+    /// Disk usage report opened from the games tab
+    usage: Option<UsagePopup>,
+    /// Unlocks encrypted saves, opened from the games tab
+    passphrase: Option<PassphrasePopup>,
     steam_err: Option<steamlocate::Error>,
+    pcgw_err: Option<PCGWError>,
+    /// Compresses idle slots while no keys are pressed
+    recompressor: Recompressor,
 }
 
 impl App {
@@ -31,8 +57,16 @@ impl App {
         Ok(Self {
             running: Default::default(),
             save_swapper: SaveManager::build()?,
+            overrides: LocalOverrides::read()?,
             selected_tab: Default::default(),
+            sync: Default::default(),
+            diff: Default::default(),
+            trash: Default::default(),
+            usage: Default::default(),
+            passphrase: Default::default(),
             steam_err: Default::default(),
+            pcgw_err: Default::default(),
+            recompressor: Recompressor::start(),
         })
     }
 
@@ -45,7 +79,10 @@ impl App {
             match err.downcast::<steamlocate::Error>() {
                 Ok(steamlocate::Error::FailedLocate(_)) => {}
                 Ok(err) => self.steam_err = Some(err),
-                Err(err) => bail!(err),
+                Err(err) => match err.downcast::<PCGWError>() {
+                    Ok(err) => self.pcgw_err = Some(err),
+                    Err(err) => bail!(err),
+                },
             }
         };
         while self.running {
@@ -72,6 +109,17 @@ impl App {
             let area = popup_area(frame.area(), 60, 20);
             frame.render_widget(Clear, area);
             frame.render_widget(steam_popup, area);
+        } else if let Some(err) = &self.pcgw_err {
+            let message = match std::error::Error::source(err) {
+                Some(source) => format!("{err}: {source}"),
+                None => err.to_string(),
+            };
+            let pcgw_popup = Paragraph::new(Span::styled(message, Style::default().red()))
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title("Failed to fetch data from PCGamingWiki"));
+            let area = popup_area(frame.area(), 60, 20);
+            frame.render_widget(Clear, area);
+            frame.render_widget(pcgw_popup, area);
         }
         match self.selected_tab.tab() {
             0 => self.selected_tab.render_tab0(
@@ -95,9 +143,27 @@ impl App {
                 //     ])
                 //     .collect::<Vec<_>>(),
             ),
-            1 => self.selected_tab.render_tab1(frame, layout[1]),
+            1 => {
+                self.selected_tab
+                    .render_tab1(frame, layout[1], &self.save_swapper, &self.overrides)
+            }
             _ => { /* TODO: log or do something here */ }
         };
+        if let Some(diff) = &mut self.diff {
+            diff.render(frame);
+        }
+        if let Some(trash) = &mut self.trash {
+            trash.render(frame);
+        }
+        if let Some(usage) = &mut self.usage {
+            usage.render(frame);
+        }
+        if let Some(passphrase) = &mut self.passphrase {
+            passphrase.render(frame);
+        }
+        if let Some(sync) = &mut self.sync {
+            sync.render(frame);
+        }
     }
 
     /// Reads the crossterm events and updates the state of [`App`].
@@ -105,6 +171,10 @@ impl App {
     /// If your application needs to perform work in between handling events, you can use the
     /// [`event::poll`] function to check if there are any events available with a timeout.
     fn handle_crossterm_events(&mut self) -> Result<()> {
+        if !event::poll(Duration::from_millis(250))? {
+            self.recompressor.tick(&self.save_swapper);
+            return Ok(());
+        }
         match event::read()? {
             // it's important to check KeyEventKind::Press to avoid handling key release events
             Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key)?,
@@ -117,17 +187,79 @@ impl App {
 
     /// Handles the key events and updates the state of [`App`].
     fn on_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some(sync) = &mut self.sync {
+            if !sync.on_key_event(key) {
+                self.sync = None;
+                self.save_swapper.adopt_synced()?;
+            }
+            return Ok(());
+        }
+        if let Some(diff) = &mut self.diff {
+            if !diff.on_key_event(key, &self.save_swapper) {
+                self.diff = None;
+            }
+            return Ok(());
+        }
+        if let Some(trash) = &mut self.trash {
+            if !trash.on_key_event(key, &mut self.save_swapper) {
+                self.trash = None;
+            }
+            return Ok(());
+        }
+        if let Some(usage) = &mut self.usage {
+            if !usage.on_key_event(key, &self.save_swapper) {
+                self.usage = None;
+            }
+            return Ok(());
+        }
+        if let Some(passphrase) = &mut self.passphrase {
+            if !passphrase.on_key_event(key) {
+                self.passphrase = None;
+            }
+            return Ok(());
+        }
+        if self.selected_tab.is_editing() {
+            self.selected_tab
+                .on_key_event(key, &mut self.save_swapper, &mut self.overrides);
+            return Ok(());
+        }
         match (key.modifiers, key.code) {
             (_, KeyCode::Esc) => {
                 self.steam_err = None;
+                self.pcgw_err = None;
                 return Ok(());
             }
             (_, KeyCode::Char('q'))
             | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit()?,
+            (_, KeyCode::Char('s')) if self.selected_tab.tab() == 0 => {
+                self.sync = Some(SyncPopup::start(&self.save_swapper));
+                return Ok(());
+            }
+            (_, KeyCode::Char('U')) if self.selected_tab.tab() == 0 => {
+                self.usage = Some(UsagePopup::open(&self.save_swapper));
+                return Ok(());
+            }
+            (_, KeyCode::Char('P')) if self.selected_tab.tab() == 0 => {
+                self.passphrase = Some(PassphrasePopup::open());
+                return Ok(());
+            }
+            (_, KeyCode::Char('t')) if self.selected_tab.tab() == 0 => {
+                self.trash = Some(TrashPopup::open(&self.save_swapper));
+                return Ok(());
+            }
+            (_, KeyCode::Char('v')) if self.selected_tab.tab() == 1 => {
+                if let Some(id) = self.selected_tab.selected_game_id(&self.save_swapper) {
+                    self.diff = Some(DiffPopup::open(&self.save_swapper, id));
+                }
+                return Ok(());
+            }
             _ => {}
         }
 
-        if self.selected_tab.on_key_event(key, &mut self.save_swapper) {
+        if self
+            .selected_tab
+            .on_key_event(key, &mut self.save_swapper, &mut self.overrides)
+        {
             self.quit()?;
         }
         Ok(())
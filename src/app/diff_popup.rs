@@ -0,0 +1,257 @@
+use std::time::SystemTime;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::{Block, Clear, List, ListState, Paragraph},
+};
+
+use super::popup_area;
+use crate::{
+    diff::{self, Change, DiffLine, FileDiff, FileInfo},
+    dir_swapper::{DiffSide, VersionContents},
+    save_manager::{GameId, SaveManager},
+    slot_name::SlotName,
+    utils,
+};
+
+/// Files that differ between two directories.
+#[derive(Debug)]
+struct FilesView {
+    title: String,
+    old_dir: VersionContents,
+    new_dir: VersionContents,
+    diffs: Vec<FileDiff>,
+    selection: ListState,
+    /// Shown when a file cannot be diffed inline
+    status: Option<String>,
+}
+
+/// Inline diff of a text file.
+#[derive(Debug)]
+struct TextView {
+    title: String,
+    lines: Vec<DiffLine>,
+    scroll: u16,
+}
+
+/// Compares the slots of a game with each other or with the live save directory. Slots are
+/// compared against the live directory unless another slot was marked as the base.
+#[derive(Debug)]
+pub struct DiffPopup {
+    id: GameId,
+    slots: Vec<SlotName>,
+    slot_selection: ListState,
+    /// Slot compared against instead of the live save directory
+    base: Option<SlotName>,
+    files: Option<FilesView>,
+    text: Option<TextView>,
+    error: Option<String>,
+}
+
+impl DiffPopup {
+    pub fn open(save_manager: &SaveManager, id: GameId) -> Self {
+        let slots = save_manager
+            .get(id)
+            .map(|game| game.slot_swapper.versions())
+            .transpose();
+        let (slots, error) = match slots {
+            Ok(slots) => (slots.unwrap_or_default(), None),
+            Err(err) => (Vec::new(), Some(format!("{err:#}"))),
+        };
+        Self {
+            id,
+            slots,
+            slot_selection: ListState::default().with_selected(Some(0)),
+            base: None,
+            files: None,
+            text: None,
+            error,
+        }
+    }
+
+    fn selected_slot(&self) -> Option<&SlotName> {
+        self.slots.get(self.slot_selection.selected()?)
+    }
+
+    fn compare(&mut self, save_manager: &SaveManager) -> anyhow::Result<()> {
+        let Some(slot) = self.selected_slot() else {
+            return Ok(());
+        };
+        let Some(swapper) = save_manager.get(self.id).map(|game| &game.slot_swapper) else {
+            return Ok(());
+        };
+        let (old, title) = match &self.base {
+            Some(base) => (DiffSide::Version(base), format!("{base} -> {slot}")),
+            None => (DiffSide::Primary, format!("live save directory -> {slot}")),
+        };
+        let new = DiffSide::Version(slot);
+        let (Some(old_dir), Some(new_dir)) = (swapper.dir_of(old)?, swapper.dir_of(new)?) else {
+            anyhow::bail!("slot does not exist anymore");
+        };
+        let diffs = diff::diff_dirs(&old_dir, &new_dir)?;
+        self.files = Some(FilesView {
+            title,
+            old_dir,
+            new_dir,
+            diffs,
+            selection: ListState::default().with_selected(Some(0)),
+            status: None,
+        });
+        Ok(())
+    }
+
+    /// Returns `false` once the popup should be closed.
+    pub fn on_key_event(&mut self, key: KeyEvent, save_manager: &SaveManager) -> bool {
+        if self.error.take().is_some() {
+            return true;
+        }
+        if let Some(text) = &mut self.text {
+            match key.code {
+                KeyCode::Esc | KeyCode::Left | KeyCode::Char('h') => self.text = None,
+                KeyCode::Down | KeyCode::Char('j') => text.scroll = text.scroll.saturating_add(1),
+                KeyCode::Up | KeyCode::Char('k') => text.scroll = text.scroll.saturating_sub(1),
+                _ => {}
+            }
+            return true;
+        }
+        if let Some(files) = &mut self.files {
+            match key.code {
+                KeyCode::Esc | KeyCode::Left | KeyCode::Char('h') => self.files = None,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    files.status = None;
+                    files.selection.select_next();
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    files.status = None;
+                    files.selection.select_previous();
+                }
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                    let Some(file) = files.selection.selected().and_then(|i| files.diffs.get(i))
+                    else {
+                        return true;
+                    };
+                    match diff::text_diff(&files.old_dir, &files.new_dir, &file.path) {
+                        Ok(Some(lines)) => {
+                            self.text = Some(TextView {
+                                title: file.path.display().to_string(),
+                                lines,
+                                scroll: 0,
+                            })
+                        }
+                        Ok(None) => {
+                            files.status = Some(format!(
+                                "{} is binary or too large to show",
+                                file.path.display()
+                            ))
+                        }
+                        Err(err) => self.error = Some(format!("{err:#}")),
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.slot_selection.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.slot_selection.select_previous(),
+            KeyCode::Char('m') => {
+                let slot = self.selected_slot().cloned();
+                self.base = if self.base == slot { None } else { slot };
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                if let Err(err) = self.compare(save_manager) {
+                    self.error = Some(format!("{err:#}"));
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.area(), 80, 70);
+        frame.render_widget(Clear, area);
+        if let Some(error) = &self.error {
+            let popup = Paragraph::new(error.as_str().red())
+                .block(Block::bordered().title("Failed to compare slots"));
+            frame.render_widget(popup, area);
+        } else if let Some(text) = &self.text {
+            let lines = text.lines.iter().map(|line| match line {
+                DiffLine::Equal(line) => Line::raw(format!("  {line}")),
+                DiffLine::Insert(line) => Line::raw(format!("+ {line}")).green(),
+                DiffLine::Delete(line) => Line::raw(format!("- {line}")).red(),
+                DiffLine::Skipped => Line::raw("  ...").dark_gray(),
+            });
+            let popup = Paragraph::new(lines.collect::<Vec<_>>())
+                .scroll((text.scroll, 0))
+                .block(Block::bordered().title(text.title.as_str()));
+            frame.render_widget(popup, area);
+        } else if let Some(files) = &mut self.files {
+            let block = Block::bordered()
+                .title(files.title.as_str())
+                .title_bottom(files.status.as_deref().unwrap_or("Enter: show text diff"));
+            if files.diffs.is_empty() {
+                frame.render_widget(Paragraph::new("No differences.").block(block), area);
+                return;
+            }
+            let items = files.diffs.iter().map(file_line);
+            frame.render_stateful_widget(
+                List::new(items).block(block).highlight_symbol(">> "),
+                area,
+                &mut files.selection,
+            );
+        } else {
+            let title = match &self.base {
+                Some(base) => format!("Compare with {base}"),
+                None => "Compare with the live save directory".to_string(),
+            };
+            let items = self.slots.iter().map(|slot| {
+                if self.base.as_ref() == Some(slot) {
+                    Line::from(vec![Span::raw(slot.as_str()), " (base)".cyan()])
+                } else {
+                    Line::raw(slot.as_str())
+                }
+            });
+            let list = List::new(items)
+                .block(
+                    Block::bordered()
+                        .title(title)
+                        .title_bottom("Enter: compare; m: mark as base"),
+                )
+                .highlight_symbol(">> ");
+            frame.render_stateful_widget(list, area, &mut self.slot_selection);
+        }
+    }
+}
+
+fn file_line(diff: &FileDiff) -> Line<'_> {
+    let path = diff.path.display().to_string();
+    match (diff.change, diff.old, diff.new) {
+        (Change::Added, _, Some(new)) => Line::from(vec![
+            format!("+ {path}").green(),
+            Span::raw(format!("  {}", describe(new))),
+        ]),
+        (Change::Removed, Some(old), _) => Line::from(vec![
+            format!("- {path}").red(),
+            Span::raw(format!("  {}", describe(old))),
+        ]),
+        (_, old, new) => Line::from(vec![
+            format!("~ {path}").yellow(),
+            Span::raw(format!(
+                "  {} -> {}",
+                old.map(describe).unwrap_or_default(),
+                new.map(describe).unwrap_or_default()
+            )),
+        ]),
+    }
+}
+
+fn describe(info: FileInfo) -> String {
+    let modified = info
+        .modified
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or_else(|| "unknown time".to_string(), utils::format_age);
+    format!("{} bytes, {modified}", info.size)
+}
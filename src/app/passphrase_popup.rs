@@ -0,0 +1,93 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+
+use super::popup_area;
+use crate::crypt;
+
+/// Asks for the passphrase of encrypted saves, or for a new one if none is set up yet.
+#[derive(Debug)]
+pub struct PassphrasePopup {
+    set_up: bool,
+    text: String,
+    /// New passphrase entered once, waiting to be repeated
+    first: Option<String>,
+    /// Shown in red
+    error: Option<String>,
+}
+
+impl PassphrasePopup {
+    pub fn open() -> Self {
+        let (set_up, error) = match crypt::is_set_up() {
+            Ok(set_up) => (set_up, None),
+            Err(err) => (true, Some(format!("{err:#}"))),
+        };
+        Self {
+            set_up,
+            text: String::new(),
+            first: None,
+            error,
+        }
+    }
+
+    /// Returns `false` once the popup should be closed, which happens once the saves are
+    /// unlocked.
+    pub fn on_key_event(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Char(c) => self.text.push(c),
+            KeyCode::Enter => {
+                let text = std::mem::take(&mut self.text);
+                let result = if self.set_up {
+                    crypt::unlock(&text)
+                } else {
+                    match self.first.take() {
+                        None if text.is_empty() => Err(anyhow::anyhow!("the passphrase is empty")),
+                        None => {
+                            self.first = Some(text);
+                            self.error = None;
+                            return true;
+                        }
+                        Some(first) if first != text => {
+                            Err(anyhow::anyhow!("the passphrases do not match"))
+                        }
+                        Some(_) => crypt::set_up(&text),
+                    }
+                };
+                match result {
+                    Ok(()) => return false,
+                    Err(err) => self.error = Some(format!("{err:#}")),
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.area(), 50, 20);
+        frame.render_widget(Clear, area);
+        let (title, prompt) = match (self.set_up, &self.first) {
+            (true, _) => ("Unlock encrypted saves", "Passphrase: "),
+            (false, None) => ("Set up encryption", "New passphrase: "),
+            (false, Some(_)) => ("Set up encryption", "Repeat the passphrase: "),
+        };
+        let bottom = match &self.error {
+            Some(error) => Line::from(error.as_str().red()),
+            None => Line::from("Enter: confirm; Esc: cancel"),
+        };
+        let input = Line::from(vec![
+            Span::raw(prompt),
+            Span::raw("*".repeat(self.text.chars().count())),
+        ]);
+        frame.render_widget(
+            Paragraph::new(input).block(Block::bordered().title(title).title_bottom(bottom)),
+            area,
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, time::SystemTime};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::{Block, Clear, List, ListState, Paragraph, Wrap},
+};
+
+use super::popup_area;
+use crate::{
+    save_manager::SaveManager,
+    sync::{Conflict, Resolution, SlotState, SyncAction, SyncPlan, SyncRemote, Syncer},
+    utils,
+};
+
+/// Progress of a sync started from the games tab.
+#[derive(Debug)]
+pub enum SyncPopup {
+    /// Conflicts are waiting for the user to pick a side
+    Resolving {
+        syncer: Syncer<Box<dyn SyncRemote>>,
+        plan: Box<SyncPlan>,
+        resolutions: BTreeMap<String, Resolution>,
+        selection: ListState,
+    },
+    /// Outcome of the sync, `Err` is shown in red
+    Done(Result<String, String>),
+}
+
+impl SyncPopup {
+    /// Syncs right away when there is nothing to resolve, otherwise asks the user.
+    pub fn start(save_manager: &SaveManager) -> Self {
+        let mut syncer = match Syncer::build() {
+            Ok(Some(syncer)) => syncer,
+            Ok(None) => {
+                return Self::Done(Err(
+                    "Sync is not configured, add a remote to the sync file in the data directory"
+                        .to_string(),
+                ));
+            }
+            Err(err) => return Self::Done(Err(format!("{err:#}"))),
+        };
+        let loaded = save_manager
+            .prepare_sync()
+            .and_then(|()| save_manager.loaded_slots());
+        match loaded {
+            Ok(loaded) => syncer.set_loaded(loaded),
+            Err(err) => return Self::Done(Err(format!("{err:#}"))),
+        }
+        Self::prepare(syncer)
+    }
+
+    fn prepare(mut syncer: Syncer<Box<dyn SyncRemote>>) -> Self {
+        match syncer.prepare() {
+            Ok(plan) if plan.conflicts.is_empty() => Self::finish(syncer, plan, &BTreeMap::new()),
+            Ok(plan) => Self::Resolving {
+                resolutions: plan
+                    .conflicts
+                    .iter()
+                    .map(|conflict| (conflict.slot.clone(), Resolution::default()))
+                    .collect(),
+                syncer,
+                plan: Box::new(plan),
+                selection: ListState::default().with_selected(Some(0)),
+            },
+            Err(err) => Self::Done(Err(format!("{err:#}"))),
+        }
+    }
+
+    fn finish(
+        mut syncer: Syncer<Box<dyn SyncRemote>>,
+        plan: SyncPlan,
+        resolutions: &BTreeMap<String, Resolution>,
+    ) -> Self {
+        Self::Done(
+            syncer
+                .finish(plan, resolutions)
+                .map(|applied| summary(&applied))
+                .map_err(|err| format!("{err:#}")),
+        )
+    }
+
+    /// Returns `false` once the popup should be closed.
+    pub fn on_key_event(&mut self, key: KeyEvent) -> bool {
+        let Self::Resolving {
+            plan,
+            resolutions,
+            selection,
+            ..
+        } = self
+        else {
+            return !matches!(key.code, KeyCode::Esc | KeyCode::Enter);
+        };
+        let selected = selection
+            .selected()
+            .and_then(|i| plan.conflicts.get(i))
+            .map(|conflict| conflict.slot.clone());
+        let mut resolve = |resolution| {
+            if let Some(slot) = &selected {
+                resolutions.insert(slot.clone(), resolution);
+            }
+        };
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => selection.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => selection.select_previous(),
+            KeyCode::Char('l') => resolve(Resolution::Local),
+            KeyCode::Char('r') => resolve(Resolution::Remote),
+            KeyCode::Char('b') => resolve(Resolution::Both),
+            KeyCode::Enter => {
+                let Self::Resolving {
+                    syncer,
+                    plan,
+                    resolutions,
+                    ..
+                } = std::mem::replace(self, Self::Done(Ok(String::new())))
+                else {
+                    unreachable!("sync popup should be resolving conflicts");
+                };
+                *self = Self::finish(syncer, *plan, &resolutions);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.area(), 70, 50);
+        frame.render_widget(Clear, area);
+        match self {
+            Self::Done(result) => {
+                let (text, style) = match result {
+                    Ok(text) => (text.as_str(), Style::default()),
+                    Err(text) => (text.as_str(), Style::default().red()),
+                };
+                let popup = Paragraph::new(Span::styled(text, style))
+                    .wrap(Wrap { trim: true })
+                    .block(Block::bordered().title("Sync"));
+                frame.render_widget(popup, area);
+            }
+            Self::Resolving {
+                plan,
+                resolutions,
+                selection,
+                ..
+            } => {
+                let items = plan.conflicts.iter().map(|conflict| {
+                    let resolution = resolutions[&conflict.slot];
+                    conflict_item(conflict, resolution)
+                });
+                let list =
+                    List::new(items)
+                        .block(Block::bordered().title("Sync conflicts").title_bottom(
+                            "l: keep local; r: keep remote; b: keep both; Enter: apply",
+                        ))
+                        .highlight_symbol("> ");
+                frame.render_stateful_widget(list, area, selection);
+            }
+        }
+    }
+}
+
+fn conflict_item(conflict: &Conflict, resolution: Resolution) -> Text<'static> {
+    let choice = match resolution {
+        Resolution::Local => "keep local",
+        Resolution::Remote => "keep remote",
+        Resolution::Both => "keep both",
+    };
+    Text::from(vec![
+        Line::from(vec![
+            Span::raw(conflict.slot.clone()).bold(),
+            Span::raw(format!(" ({choice})")).cyan(),
+        ]),
+        Line::raw(format!("  local:  {}", describe(conflict.local.as_ref()))),
+        Line::raw(format!("  remote: {}", describe(conflict.remote.as_ref()))),
+    ])
+}
+
+fn describe(state: Option<&SlotState>) -> String {
+    let Some(state) = state else {
+        return "deleted".to_string();
+    };
+    let modified = state
+        .modified
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or_else(|| "unknown time".to_string(), utils::format_age);
+    format!(
+        "modified {modified} on {}, {} bytes",
+        state.host, state.size
+    )
+}
+
+fn summary(applied: &[(String, SyncAction)]) -> String {
+    if applied.is_empty() {
+        return "Already in sync".to_string();
+    }
+    let count = |action| applied.iter().filter(|(_, own)| *own == action).count();
+    format!(
+        "Pushed {}, pulled {} and deleted {} slots",
+        count(SyncAction::Push),
+        count(SyncAction::Pull),
+        count(SyncAction::DeleteLocal) + count(SyncAction::DeleteRemote),
+    )
+}
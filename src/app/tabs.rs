@@ -4,38 +4,82 @@ use ratatui::{
     crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
     prelude::*,
     style::{Styled, palette::tailwind},
-    widgets::{List, ListState, Paragraph, Tabs},
+    widgets::{Block, List, ListState, Paragraph, Tabs},
 };
 use strum::{Display, VariantNames};
 
-use crate::{save_manager::GameId, save_manager::SaveManager};
+use crate::{
+    consts::SAVE_SLOT_PATH,
+    pcgw::LocationKind,
+    save_manager::{GameId, SaveManager},
+    sources::{GameOverride, LocalOverrides},
+};
 
 #[derive(Debug)]
 struct SelectedTabContext {
     game_selection: ListState,
+    location_selection: ListState,
+    /// Text typed by the user, `None` when not editing
+    input: Option<Input>,
+    /// Result of the last override action, `Err` is shown in red
+    status: Option<Result<String, String>>,
 }
 impl Default for SelectedTabContext {
     fn default() -> Self {
         Self {
             game_selection: ListState::default().with_selected(Some(0)),
+            location_selection: ListState::default().with_selected(Some(0)),
+            input: None,
+            status: None,
         }
     }
 }
 
 #[derive(Debug)]
-pub struct SelectedTab {
-    ctx: SelectedTabContext,
-    state: TabState,
+struct Input {
+    kind: InputKind,
+    text: String,
 }
-impl Default for SelectedTab {
-    fn default() -> Self {
-        Self {
-            ctx: Default::default(),
-            state: Default::default(),
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    /// `<system>: <template>` of a save location
+    AddSave,
+    /// `<system>: <template>` of a config location
+    AddConfig,
+    /// Path of an exported overrides file
+    Import,
+}
+impl InputKind {
+    fn prompt(self) -> &'static str {
+        match self {
+            InputKind::AddSave => "New save location (<system>: <path>): ",
+            InputKind::AddConfig => "New config location (<system>: <path>): ",
+            InputKind::Import => "Import overrides from: ",
         }
     }
 }
 
+/// Flattens the overrides of a game into rows of `(is config, system, template)`.
+fn override_rows(game: &GameOverride) -> Vec<(bool, &str, &str)> {
+    [(false, &game.saves), (true, &game.configs)]
+        .into_iter()
+        .flat_map(|(config, table)| {
+            table.iter().flat_map(move |(system, templates)| {
+                templates
+                    .iter()
+                    .map(move |template| (config, system.as_str(), template.as_str()))
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct SelectedTab {
+    ctx: SelectedTabContext,
+    state: TabState,
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
 struct ActionKeyBinding {
     pub code: KeyCode,
@@ -47,6 +91,18 @@ struct ActionKeyBinding {
 enum Action {
     AddGame,
     RemoveGame,
+    AddLocation,
+    AddConfigLocation,
+    RemoveLocation,
+    ExportOverrides,
+    ImportOverrides,
+    Sync,
+    CompareSlots,
+    Undo,
+    Redo,
+    Trash,
+    Usage,
+    Unlock,
     // #[strum(serialize = "go back")]
     // Back,
     // #[strum(serialize = "quit")]
@@ -62,6 +118,26 @@ impl Display for Action {
                 Action::RemoveGame => "delete game"
                     .add_modifier(Modifier::ITALIC)
                     .fg(Color::LightRed),
+                Action::AddLocation => "add save location"
+                    .add_modifier(Modifier::ITALIC)
+                    .fg(Color::Blue),
+                Action::AddConfigLocation => "add config location"
+                    .add_modifier(Modifier::ITALIC)
+                    .fg(Color::Blue),
+                Action::RemoveLocation => "delete location"
+                    .add_modifier(Modifier::ITALIC)
+                    .fg(Color::LightRed),
+                Action::ExportOverrides => "export".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::ImportOverrides => "import".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::Sync => "sync".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::CompareSlots => "compare slots"
+                    .add_modifier(Modifier::ITALIC)
+                    .fg(Color::Cyan),
+                Action::Undo => "undo".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::Redo => "redo".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::Trash => "trash".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::Usage => "disk usage".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
+                Action::Unlock => "unlock".add_modifier(Modifier::ITALIC).fg(Color::Cyan),
             }
         )
     }
@@ -83,14 +159,79 @@ impl Action {
                     modifiers: KeyModifiers::NONE,
                 },
             ],
+            Action::AddLocation => &[ActionKeyBinding {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::AddConfigLocation => &[ActionKeyBinding {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::RemoveLocation => &[
+                ActionKeyBinding {
+                    code: KeyCode::Delete,
+                    modifiers: KeyModifiers::NONE,
+                },
+                ActionKeyBinding {
+                    code: KeyCode::Char('d'),
+                    modifiers: KeyModifiers::NONE,
+                },
+            ],
+            Action::ExportOverrides => &[ActionKeyBinding {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::ImportOverrides => &[ActionKeyBinding {
+                code: KeyCode::Char('i'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Sync => &[ActionKeyBinding {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Usage => &[ActionKeyBinding {
+                code: KeyCode::Char('U'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Unlock => &[ActionKeyBinding {
+                code: KeyCode::Char('P'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Trash => &[ActionKeyBinding {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Undo => &[ActionKeyBinding {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::NONE,
+            }],
+            Action::Redo => &[ActionKeyBinding {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::CONTROL,
+            }],
+            Action::CompareSlots => &[ActionKeyBinding {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::NONE,
+            }],
         }
     }
     fn key_style(&self) -> Style {
         match self {
-            Action::AddGame => Style::new().add_modifier(Modifier::ITALIC).fg(Color::Blue),
-            Action::RemoveGame => Style::new()
+            Action::AddGame | Action::AddLocation | Action::AddConfigLocation => {
+                Style::new().add_modifier(Modifier::ITALIC).fg(Color::Blue)
+            }
+            Action::RemoveGame | Action::RemoveLocation => Style::new()
                 .add_modifier(Modifier::ITALIC)
                 .fg(Color::LightRed),
+            Action::ExportOverrides
+            | Action::ImportOverrides
+            | Action::Sync
+            | Action::CompareSlots
+            | Action::Undo
+            | Action::Redo
+            | Action::Trash
+            | Action::Usage
+            | Action::Unlock => Style::new().add_modifier(Modifier::ITALIC).fg(Color::Cyan),
             // Action::Back => {
             //     const {
             //         &ActionStyle {
@@ -166,8 +307,26 @@ impl SelectedTab {
     // Returns a list keybindings used in the current tab and its description
     fn keybindings(&self) -> &'static [Action] {
         match self.state {
-            TabState::Tab1 { .. } => &[Action::AddGame, Action::RemoveGame],
-            TabState::Tab2 => &[],
+            TabState::Tab1 { .. } => &[
+                Action::AddGame,
+                Action::RemoveGame,
+                Action::Sync,
+                Action::Trash,
+                Action::Usage,
+                Action::Unlock,
+                Action::Undo,
+                Action::Redo,
+            ],
+            TabState::Tab2 => &[
+                Action::AddLocation,
+                Action::AddConfigLocation,
+                Action::RemoveLocation,
+                Action::ExportOverrides,
+                Action::ImportOverrides,
+                Action::CompareSlots,
+                Action::Undo,
+                Action::Redo,
+            ],
         }
     }
 
@@ -186,10 +345,7 @@ impl SelectedTab {
                         .flat_map(|(i, binding)| {
                             let prev_comma = if i > 0 { ", " } else { "" };
                             let span = if binding.modifiers == KeyModifiers::NONE {
-                                Span::styled(
-                                    format!("{}", &binding.code.to_string()),
-                                    action.key_style(),
-                                )
+                                Span::styled(binding.code.to_string(), action.key_style())
                             } else {
                                 Span::styled(
                                     format!(
@@ -244,25 +400,175 @@ impl SelectedTab {
             Span::raw(" to add a new one."),
         ]))
         .centered();
+        let layout = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).split(area);
         // TODO: Show message/help if no games are addded, suggest Steam?
         if items.is_empty() {
-            frame.render_widget(empty_message, area);
+            frame.render_widget(empty_message, layout[0]);
         } else {
             frame.render_stateful_widget(
                 List::new(items).highlight_symbol(">> "),
-                area,
+                layout[0],
                 &mut self.ctx.game_selection,
             );
         }
+        frame.render_widget(Paragraph::new(self.status_line()), layout[1]);
+    }
+
+    /// Result of the last action, in red if it failed.
+    fn status_line(&self) -> Line<'_> {
+        match &self.ctx.status {
+            Some(Ok(message)) => Line::from(message.as_str()),
+            Some(Err(message)) => Line::from(message.as_str().red()),
+            None => Line::default(),
+        }
     }
 
-    pub fn render_tab1(&self, frame: &mut Frame, area: Rect) {
-        frame.render_widget(Paragraph::new("TODO"), area);
+    /// Renders the local save location overrides of the selected game.
+    pub fn render_tab1(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        save_manager: &SaveManager,
+        overrides: &LocalOverrides,
+    ) {
+        let Some((name, id)) = self.selected_game(save_manager) else {
+            frame.render_widget(Paragraph::new("No game selected.").centered(), area);
+            return;
+        };
+        let layout = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).split(area);
+        let rows = overrides.get(id).map(override_rows).unwrap_or_default();
+        let block = Block::bordered().title(format!("Location overrides of {name}"));
+        if rows.is_empty() {
+            frame.render_widget(
+                Paragraph::new("No overrides, locations from the PCGamingWiki are used.")
+                    .block(block),
+                layout[0],
+            );
+        } else {
+            let items = rows.iter().map(|(config, system, template)| {
+                let kind = if *config { "config" } else { "save" };
+                Line::from(vec![
+                    Span::styled(format!("{kind:<6} "), Style::new().fg(Color::DarkGray)),
+                    Span::styled(format!("{system:<8} "), Style::new().fg(Color::Cyan)),
+                    Span::raw(*template),
+                ])
+            });
+            frame.render_stateful_widget(
+                List::new(items).block(block).highlight_symbol(">> "),
+                layout[0],
+                &mut self.ctx.location_selection,
+            );
+        }
+        let bottom_line = match (&self.ctx.input, &self.ctx.status) {
+            (Some(input), _) => Line::from(vec![
+                Span::raw(input.kind.prompt()),
+                Span::raw(input.text.as_str()),
+                Span::styled("_", Style::new().add_modifier(Modifier::SLOW_BLINK)),
+            ]),
+            (None, _) => self.status_line(),
+        };
+        frame.render_widget(Paragraph::new(bottom_line), layout[1]);
+    }
+
+    /// Returns `true` while the user is typing, so that global keybindings can be ignored.
+    pub fn is_editing(&self) -> bool {
+        self.ctx.input.is_some()
+    }
+
+    #[inline]
+    pub fn selected_game_id(&self, save_manager: &SaveManager) -> Option<GameId> {
+        self.selected_game(save_manager).map(|(_, id)| id)
+    }
+
+    fn selected_game<'a>(&self, save_manager: &'a SaveManager) -> Option<(&'a str, GameId)> {
+        save_manager
+            .games()
+            .iter()
+            .nth(self.ctx.game_selection.selected()?)
+            .map(|(name, &id)| (name.as_str(), id))
+    }
+
+    /// Applies the submitted input to the overrides of `id`.
+    fn submit_input(
+        input: Input,
+        id: GameId,
+        overrides: &mut LocalOverrides,
+    ) -> anyhow::Result<String> {
+        let text = input.text.trim();
+        match input.kind {
+            InputKind::AddSave | InputKind::AddConfig => {
+                let (system, template) = text
+                    .split_once(':')
+                    .map(|(system, template)| (system.trim(), template.trim()))
+                    .filter(|(system, template)| !system.is_empty() && !template.is_empty())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "expected `<system>: <path>`, e.g. `Windows: %APPDATA%\\Game`"
+                        )
+                    })?;
+                let config = input.kind == InputKind::AddConfig;
+                if !overrides.add(id, &LocationKind::from(system), template, config) {
+                    return Ok(format!("{template} is already listed"));
+                }
+                overrides.save()?;
+                Ok(format!("Added {template}"))
+            }
+            InputKind::Import => {
+                let count = overrides.import(text)?;
+                overrides.save()?;
+                Ok(format!("Imported overrides of {count} games"))
+            }
+        }
+    }
+
+    /// Undoes or redoes the last slot operation, see [`SaveManager::undo`].
+    fn undo(&mut self, save_manager: &mut SaveManager, redo: bool) {
+        let (result, done, nothing) = if redo {
+            (save_manager.redo(), "Redid", "Nothing to redo")
+        } else {
+            (save_manager.undo(), "Undid", "Nothing to undo")
+        };
+        self.ctx.status = Some(
+            result
+                .map(|operation| {
+                    operation.map_or(nothing.to_string(), |operation| {
+                        format!("{done}: {operation}")
+                    })
+                })
+                .map_err(|err| format!("{err:#}")),
+        );
+    }
+
+    /// Handles keys of the overrides editor while the user is typing.
+    fn on_input_key_event(&mut self, key: KeyEvent, id: GameId, overrides: &mut LocalOverrides) {
+        let Some(input) = &mut self.ctx.input else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.ctx.input = None,
+            KeyCode::Backspace => {
+                input.text.pop();
+            }
+            KeyCode::Char(c) => input.text.push(c),
+            KeyCode::Enter => {
+                let input = self.ctx.input.take().expect("input should be active");
+                self.ctx.status = Some(
+                    Self::submit_input(input, id, overrides).map_err(|err| format!("{err:#}")),
+                );
+            }
+            _ => {}
+        }
     }
 
     /// Handles keyboard events for corresponding tabs. Returns `true` if user requested to quit,
     /// otherwise false.
-    pub fn on_key_event(&mut self, key: KeyEvent, save_manager: &mut SaveManager) -> bool {
+    pub fn on_key_event(
+        &mut self,
+        key: KeyEvent,
+        save_manager: &mut SaveManager,
+        overrides: &mut LocalOverrides,
+    ) -> bool {
+        let selected_game = self.selected_game(save_manager).map(|(_, id)| id);
         let tab = &mut self.state;
         match tab {
             TabState::Tab1 { g_pressed } => {
@@ -276,6 +582,8 @@ impl SelectedTab {
                     (_, KeyCode::Esc) => {
                         return true;
                     }
+                    (KeyModifiers::CONTROL, KeyCode::Char('r')) => self.undo(save_manager, true),
+                    (_, KeyCode::Char('u')) => self.undo(save_manager, false),
                     (_, KeyCode::Down) | (_, KeyCode::Char('j')) => {
                         self.ctx.game_selection.select_next();
                     }
@@ -286,28 +594,95 @@ impl SelectedTab {
                         *tab = tab.next()
                     }
                     (_, KeyCode::Char('a')) => todo!(),
-                    (_, KeyCode::Char('d')) | (_, KeyCode::Delete) => todo!(),
+                    (_, KeyCode::Char('d')) | (_, KeyCode::Delete) => {
+                        if let Some((name, id)) = self.selected_game(save_manager) {
+                            let name = name.to_string();
+                            self.ctx.status = Some(
+                                save_manager
+                                    .remove_game(id)
+                                    .map(|_| format!("Moved {name} to the trash"))
+                                    .map_err(|err| format!("{err:#}")),
+                            );
+                        }
+                    }
                     (_, KeyCode::End) | (_, KeyCode::Char('G')) => {
                         self.ctx.game_selection.select_last();
                     }
                     (_, KeyCode::Home) => {
                         self.ctx.game_selection.select_first();
                     }
-                    (_, KeyCode::Char('g')) => {
-                        if g_pressed_copy {
-                            self.ctx.game_selection.select_first();
-                        }
+                    (_, KeyCode::Char('g')) if g_pressed_copy => {
+                        self.ctx.game_selection.select_first();
                     }
                     _ => {}
                 }
             }
 
-            TabState::Tab2 => match (key.modifiers, key.code) {
-                (_, KeyCode::Left) | (_, KeyCode::Char('h')) | (_, KeyCode::Esc) => {
-                    self.state = self.state.previous()
+            TabState::Tab2 => {
+                let Some(id) = selected_game else {
+                    if let (_, KeyCode::Left) | (_, KeyCode::Char('h')) | (_, KeyCode::Esc) =
+                        (key.modifiers, key.code)
+                    {
+                        self.state = self.state.previous()
+                    }
+                    return false;
+                };
+                if self.is_editing() {
+                    self.on_input_key_event(key, id, overrides);
+                    return false;
                 }
-                _ => {}
-            },
+                let start_input = |kind| {
+                    Some(Input {
+                        kind,
+                        text: String::new(),
+                    })
+                };
+                match (key.modifiers, key.code) {
+                    (_, KeyCode::Left) | (_, KeyCode::Char('h')) | (_, KeyCode::Esc) => {
+                        self.ctx.status = None;
+                        self.state = self.state.previous()
+                    }
+                    (_, KeyCode::Down) | (_, KeyCode::Char('j')) => {
+                        self.ctx.location_selection.select_next();
+                    }
+                    (_, KeyCode::Up) | (_, KeyCode::Char('k')) => {
+                        self.ctx.location_selection.select_previous();
+                    }
+                    (KeyModifiers::CONTROL, KeyCode::Char('r')) => self.undo(save_manager, true),
+                    (_, KeyCode::Char('u')) => self.undo(save_manager, false),
+                    (_, KeyCode::Char('a')) => self.ctx.input = start_input(InputKind::AddSave),
+                    (_, KeyCode::Char('c')) => self.ctx.input = start_input(InputKind::AddConfig),
+                    (_, KeyCode::Char('i')) => self.ctx.input = start_input(InputKind::Import),
+                    (_, KeyCode::Char('d')) | (_, KeyCode::Delete) => {
+                        let row = overrides.get(id).and_then(|game| {
+                            let rows = override_rows(game);
+                            let (config, system, template) =
+                                *rows.get(self.ctx.location_selection.selected()?)?;
+                            Some((config, LocationKind::from(system), template.to_string()))
+                        });
+                        if let Some((config, kind, template)) = row {
+                            overrides.remove(id, &kind, &template, config);
+                            self.ctx.status = Some(
+                                overrides
+                                    .save()
+                                    .map(|_| format!("Deleted {template}"))
+                                    .map_err(|err| format!("{err:#}")),
+                            );
+                        }
+                    }
+                    (_, KeyCode::Char('x')) => {
+                        let path = SAVE_SLOT_PATH.join(format!("{id}-overrides.toml"));
+                        self.ctx.status = Some(
+                            std::fs::create_dir_all(SAVE_SLOT_PATH.as_path())
+                                .map_err(anyhow::Error::from)
+                                .and_then(|_| overrides.export(&[id], &path))
+                                .map(|_| format!("Exported to {}", path.display()))
+                                .map_err(|err| format!("{err:#}")),
+                        );
+                    }
+                    _ => {}
+                }
+            }
         }
         false
     }
@@ -0,0 +1,115 @@
+use std::time::SystemTime;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::{Block, Clear, List, ListState, Paragraph},
+};
+
+use super::popup_area;
+use crate::{save_manager::SaveManager, trash::TrashItem, utils};
+
+/// Deleted slots and games, which can be restored or purged for good.
+#[derive(Debug)]
+pub struct TrashPopup {
+    items: Vec<TrashItem>,
+    selection: ListState,
+    /// Result of the last action, `Err` is shown in red
+    status: Option<Result<String, String>>,
+}
+
+impl TrashPopup {
+    pub fn open(save_manager: &SaveManager) -> Self {
+        let mut popup = Self {
+            items: Vec::new(),
+            selection: ListState::default().with_selected(Some(0)),
+            status: None,
+        };
+        popup.reload(save_manager);
+        popup
+    }
+
+    fn reload(&mut self, save_manager: &SaveManager) {
+        match save_manager.trash().items() {
+            // Most recently deleted first
+            Ok(items) => self.items = items.into_iter().rev().collect(),
+            Err(err) => self.status = Some(Err(format!("{err:#}"))),
+        }
+    }
+
+    fn selected(&self) -> Option<&TrashItem> {
+        self.items.get(self.selection.selected()?)
+    }
+
+    /// Returns `false` once the popup should be closed.
+    pub fn on_key_event(&mut self, key: KeyEvent, save_manager: &mut SaveManager) -> bool {
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.selection.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.selection.select_previous(),
+            KeyCode::Enter | KeyCode::Char('r') => {
+                let Some(item) = self.selected() else {
+                    return true;
+                };
+                let description = item.trashed.to_string();
+                self.status = Some(match save_manager.restore_trashed(&item.id) {
+                    Ok(Some(())) => Ok(format!("Restored {description}")),
+                    Ok(None) => Err(format!(
+                        "Cannot restore {description}, its game is missing or was added again"
+                    )),
+                    Err(err) => Err(format!("{err:#}")),
+                });
+                self.reload(save_manager);
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                let Some(item) = self.selected() else {
+                    return true;
+                };
+                let description = item.trashed.to_string();
+                self.status = Some(
+                    save_manager
+                        .trash()
+                        .purge(&item.id)
+                        .map(|_| format!("Deleted {description} for good"))
+                        .map_err(|err| format!("{err:#}")),
+                );
+                self.reload(save_manager);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.area(), 70, 50);
+        frame.render_widget(Clear, area);
+        let bottom = match &self.status {
+            Some(Ok(message)) => Line::from(message.as_str()),
+            Some(Err(message)) => Line::from(message.as_str().red()),
+            None => Line::from("Enter: restore; d: delete for good"),
+        };
+        let block = Block::bordered().title("Trash").title_bottom(bottom);
+        if self.items.is_empty() {
+            frame.render_widget(Paragraph::new("The trash is empty.").block(block), area);
+            return;
+        }
+        let now = SystemTime::now();
+        let items = self.items.iter().map(|item| {
+            let age = now
+                .duration_since(item.deleted)
+                .map_or_else(|_| "just now".to_string(), utils::format_age);
+            Line::from(vec![
+                Span::raw(item.trashed.to_string()),
+                Span::styled(
+                    format!("  deleted {age}, {} bytes", item.size),
+                    Style::new().fg(Color::DarkGray),
+                ),
+            ])
+        });
+        frame.render_stateful_widget(
+            List::new(items).block(block).highlight_symbol(">> "),
+            area,
+            &mut self.selection,
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+
+use super::popup_area;
+use crate::{
+    save_manager::SaveManager,
+    usage::{QuotaConfig, UsageReport},
+    utils::format_size,
+};
+
+/// Disk space used by the slots, snapshots and trash of every game, with warnings about the
+/// quotas.
+#[derive(Debug)]
+pub struct UsagePopup {
+    config: QuotaConfig,
+    report: Option<UsageReport>,
+    scroll: u16,
+    /// Result of the last action, `Err` is shown in red
+    status: Option<Result<String, String>>,
+}
+
+impl UsagePopup {
+    pub fn open(save_manager: &SaveManager) -> Self {
+        let mut popup = Self {
+            config: QuotaConfig::default(),
+            report: None,
+            scroll: 0,
+            status: None,
+        };
+        if let Err(err) = popup.reload(save_manager) {
+            popup.status = Some(Err(format!("{err:#}")));
+        }
+        popup
+    }
+
+    fn reload(&mut self, save_manager: &SaveManager) -> anyhow::Result<()> {
+        self.config = QuotaConfig::read()?;
+        self.report = Some(save_manager.usage()?);
+        Ok(())
+    }
+
+    /// Returns `false` once the popup should be closed.
+    pub fn on_key_event(&mut self, key: KeyEvent, save_manager: &SaveManager) -> bool {
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll = self.scroll.saturating_add(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Char('p') => {
+                self.status = Some(
+                    save_manager
+                        .enforce_quota(&self.config)
+                        .and_then(|(pruned, _)| {
+                            self.reload(save_manager)?;
+                            Ok(format!("Pruned {pruned} snapshots"))
+                        })
+                        .map_err(|err| format!("{err:#}")),
+                );
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.area(), 70, 60);
+        frame.render_widget(Clear, area);
+        let bottom = match &self.status {
+            Some(Ok(message)) => Line::from(message.as_str()),
+            Some(Err(message)) => Line::from(message.as_str().red()),
+            None => Line::from("p: prune old snapshots to meet the quotas"),
+        };
+        let block = Block::bordered().title("Disk usage").title_bottom(bottom);
+        let Some(report) = &self.report else {
+            frame.render_widget(block, area);
+            return;
+        };
+        let mut lines = Vec::new();
+        for warning in self.config.warnings(report) {
+            lines.push(Line::from(warning.red()));
+        }
+        for game in &report.games {
+            lines.push(Line::from(vec![
+                Span::raw(game.title()).bold(),
+                Span::raw(format!("  {}", format_size(game.total()))),
+            ]));
+            for slot in &game.slots {
+                lines.push(Line::from(vec![
+                    Span::raw(format!("  {}  {}", slot.name, format_size(slot.size))),
+                    Span::styled(
+                        format!(
+                            "  {} snapshots, {}",
+                            slot.snapshots.len(),
+                            format_size(slot.history_size())
+                        ),
+                        Style::new().fg(Color::DarkGray),
+                    ),
+                ]));
+            }
+            if game.trash > 0 {
+                lines.push(Line::from(
+                    format!("  trash  {}", format_size(game.trash)).dark_gray(),
+                ));
+            }
+        }
+        lines.push(Line::default());
+        lines.push(Line::from(format!("Trash  {}", format_size(report.trash))));
+        lines.push(Line::from(format!(
+            "Total  {}",
+            format_size(report.total())
+        )));
+        if let Some(available) = report.available {
+            lines.push(Line::from(format!("Free   {}", format_size(available))));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).scroll((self.scroll, 0)).block(block),
+            area,
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    crypt::{self, DecryptReader, EncryptWriter, Key},
+    save_manager::{GameId, SlotMeta},
+    slot_name::SlotName,
+    utils::walk_dir,
+};
+
+/// Bumped when the layout of archives changes.
+const ARCHIVE_VERSION: u32 = 1;
+const MANIFEST_PATH: &str = "manifest.toml";
+const SLOTS_DIR: &str = "slots";
+
+/// First entry of every archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub game: GameId,
+    pub slots: Vec<SlotEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotEntry {
+    /// Validated when the manifest is read, so it is safe to use as a path
+    pub name: SlotName,
+    pub meta: SlotMeta,
+    /// SHA-256 of every file, keyed by its path relative to the slot with `/` separators
+    pub files: BTreeMap<String, String>,
+    /// Directories relative to the slot, so that empty ones are kept
+    #[serde(default)]
+    pub dirs: BTreeSet<String>,
+    /// SHA-256 of the exported registry keys, if the slot has any
+    #[serde(default)]
+    pub registry: Option<String>,
+}
+
+/// A slot to export.
+#[derive(Debug)]
+pub struct SlotSource<'a> {
+    pub name: &'a SlotName,
+    pub meta: &'a SlotMeta,
+    /// Contents of the slot
+    pub dir: PathBuf,
+    /// Exported registry keys, see `crate::registry::WineRegistryKeys::export`
+    pub registry: Option<String>,
+}
+
+/// What to do when an imported slot has the name of an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    /// Import as `name (2)`, `name (3)`...
+    #[default]
+    Rename,
+    /// Replace the existing slot
+    Overwrite,
+    /// Keep the existing slot and do not import
+    Skip,
+}
+
+impl OnConflict {
+    /// Name to import `name` as, `None` if it should be skipped.
+    pub fn resolve(self, name: &SlotName, existing: &[SlotName]) -> Option<SlotName> {
+        if !existing.contains(name) {
+            return Some(name.clone());
+        }
+        match self {
+            OnConflict::Rename => (2..)
+                .map_while(|i| name.with_suffix(&i.to_string()))
+                .find(|candidate| !existing.contains(candidate)),
+            OnConflict::Overwrite => Some(name.clone()),
+            OnConflict::Skip => None,
+        }
+    }
+}
+
+fn hash_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Joins the components of a relative path with `/`.
+fn archive_path(path: &Path) -> Result<String> {
+    let components = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .with_context(|| format!("{} is not valid Unicode", path.display())),
+            _ => bail!("{} is not a plain relative path", path.display()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(components.join("/"))
+}
+
+fn append_data(builder: &mut tar::Builder<impl Write>, path: &str, data: &[u8]) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)
+}
+
+/// Writes `slots` of `game` to a zstd-compressed tar archive at `path`, which is encrypted if
+/// `key` is set.
+pub fn export(
+    game: GameId,
+    slots: &[SlotSource],
+    path: impl AsRef<Path>,
+    key: Option<&Key>,
+) -> Result<()> {
+    let mut manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        game,
+        slots: Vec::new(),
+    };
+    let mut contents = Vec::new();
+    for slot in slots {
+        let (files, dirs) = walk_dir(&slot.dir)?;
+        let mut entry = SlotEntry {
+            name: slot.name.clone(),
+            meta: slot.meta.clone(),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+            registry: slot
+                .registry
+                .as_ref()
+                .map(|reg| hash_reader(reg.as_bytes()))
+                .transpose()?,
+        };
+        for dir in dirs {
+            entry.dirs.insert(archive_path(&dir)?);
+        }
+        for file in files {
+            let relative = archive_path(&file)?;
+            let source = slot.dir.join(&file);
+            entry
+                .files
+                .insert(relative.clone(), hash_reader(File::open(&source)?)?);
+            contents.push((format!("{SLOTS_DIR}/{}/{relative}", slot.name), source));
+        }
+        manifest.slots.push(entry);
+    }
+
+    let path = path.as_ref();
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let file = match key {
+        Some(key) => {
+            write_archive(EncryptWriter::new(file, key)?, slots, &manifest, contents)?.finish()?
+        }
+        None => write_archive(file, slots, &manifest, contents)?,
+    };
+    file.sync_all()?;
+    Ok(())
+}
+
+fn write_archive<W: Write>(
+    writer: W,
+    slots: &[SlotSource],
+    manifest: &ArchiveManifest,
+    contents: Vec<(String, PathBuf)>,
+) -> Result<W> {
+    let mut builder = tar::Builder::new(zstd::Encoder::new(writer, 0)?);
+    append_data(
+        &mut builder,
+        MANIFEST_PATH,
+        toml::to_string(manifest)?.as_bytes(),
+    )?;
+    for slot in slots {
+        if let Some(registry) = &slot.registry {
+            append_data(
+                &mut builder,
+                &format!("{SLOTS_DIR}/{}.reg", slot.name),
+                registry.as_bytes(),
+            )?;
+        }
+    }
+    for (name, source) in contents {
+        builder.append_path_with_name(source, name)?;
+    }
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// An archive extracted and verified in a staging directory, which is removed on drop.
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    pub manifest: ArchiveManifest,
+    staging_dir: PathBuf,
+}
+
+impl ExtractedArchive {
+    /// Contents of the slot.
+    pub fn slot_dir(&self, name: &str) -> PathBuf {
+        self.staging_dir.join(SLOTS_DIR).join(name)
+    }
+    /// Exported registry keys of the slot, if it has any.
+    pub fn registry_file(&self, name: &str) -> Option<PathBuf> {
+        let path = self.staging_dir.join(SLOTS_DIR).join(format!("{name}.reg"));
+        path.exists().then_some(path)
+    }
+}
+
+impl Drop for ExtractedArchive {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+/// Extracts the archive at `path` to `staging_dir`, checking every file against the hashes in
+/// the manifest. Encrypted archives are decrypted with the key of the session.
+pub fn extract(path: impl AsRef<Path>, staging_dir: PathBuf) -> Result<ExtractedArchive> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let reader: Box<dyn Read> = if crypt::is_encrypted(path)? {
+        let key = crypt::session_key().context("archive is encrypted")?;
+        Box::new(DecryptReader::new(file, &key)?)
+    } else {
+        Box::new(file)
+    };
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    let mut entries = archive.entries()?;
+
+    let mut manifest_entry = entries.next().context("archive is empty")??;
+    if manifest_entry.path()?.as_ref() != Path::new(MANIFEST_PATH) {
+        bail!("archive does not start with {MANIFEST_PATH}");
+    }
+    let mut manifest = String::new();
+    manifest_entry.read_to_string(&mut manifest)?;
+    let manifest: ArchiveManifest = toml::from_str(&manifest).context("invalid manifest")?;
+    if manifest.version > ARCHIVE_VERSION {
+        bail!(
+            "archive version {} is newer than the supported version {ARCHIVE_VERSION}",
+            manifest.version
+        );
+    }
+
+    fs::create_dir_all(&staging_dir)?;
+    let extracted = ExtractedArchive {
+        manifest,
+        staging_dir,
+    };
+    let mut seen = BTreeSet::new();
+    for entry in entries {
+        let mut entry = entry?;
+        let entry_path = archive_path(&entry.path()?)?;
+        let Some((slot_name, relative)) = entry_path
+            .strip_prefix(&format!("{SLOTS_DIR}/"))
+            .map(|path| path.split_once('/').unwrap_or((path, "")))
+        else {
+            bail!("unexpected file {entry_path} in archive");
+        };
+        let (slot_name, expected) = match slot_name.strip_suffix(".reg") {
+            Some(slot_name) if relative.is_empty() => (
+                slot_name,
+                extracted
+                    .manifest
+                    .slots
+                    .iter()
+                    .find(|slot| *slot.name == *slot_name)
+                    .and_then(|slot| slot.registry.as_ref()),
+            ),
+            _ => (
+                slot_name,
+                extracted
+                    .manifest
+                    .slots
+                    .iter()
+                    .find(|slot| *slot.name == *slot_name)
+                    .and_then(|slot| slot.files.get(relative)),
+            ),
+        };
+        let Some(expected) = expected else {
+            bail!("{entry_path} of slot \"{slot_name}\" is not listed in the manifest");
+        };
+        let target = extracted.staging_dir.join(&entry_path);
+        fs::create_dir_all(target.parent().expect("slot files have a parent"))?;
+        let mut hasher = Sha256::new();
+        let mut file = File::create(&target)?;
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let read = entry.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            file.write_all(&buf[..read])?;
+        }
+        if hex::encode(hasher.finalize()) != *expected {
+            bail!("{entry_path} is corrupted, its hash does not match the manifest");
+        }
+        seen.insert(entry_path);
+    }
+
+    for slot in &extracted.manifest.slots {
+        let slot_dir = extracted.slot_dir(&slot.name);
+        fs::create_dir_all(&slot_dir)?;
+        for dir in &slot.dirs {
+            // Directories come from the manifest, not from entries that were already checked
+            archive_path(Path::new(dir))
+                .with_context(|| format!("invalid directory of slot \"{}\"", slot.name))?;
+            fs::create_dir_all(slot_dir.join(dir))?;
+        }
+        let registry = slot
+            .registry
+            .as_ref()
+            .map(|_| format!("{SLOTS_DIR}/{}.reg", slot.name));
+        for file in slot
+            .files
+            .keys()
+            .map(|file| format!("{SLOTS_DIR}/{}/{file}", slot.name))
+            .chain(registry)
+        {
+            if !seen.contains(&file) {
+                bail!("{file} is listed in the manifest but missing from the archive");
+            }
+        }
+    }
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn write(path: impl AsRef<Path>, contents: &str) {
+        let path = path.as_ref();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn export_and_extract_round_trip() {
+        let dir = tempdir().unwrap();
+        let slot1 = dir.path().join("slot1");
+        write(slot1.join("file0"), "save data");
+        write(slot1.join("profiles/1/options.ini"), "volume=3");
+        fs::create_dir_all(slot1.join("empty")).unwrap();
+        let slot2 = dir.path().join("slot2");
+        fs::create_dir_all(&slot2).unwrap();
+        let meta = SlotMeta::default();
+        let archive = dir.path().join("undertale.tar.zst");
+        export(
+            GameId::Steam(391540),
+            &[
+                SlotSource {
+                    name: &"Genocide".parse().unwrap(),
+                    meta: &meta,
+                    dir: slot1,
+                    registry: Some("Windows Registry Editor Version 5.00\n".to_string()),
+                },
+                SlotSource {
+                    name: &"Pacifist".parse().unwrap(),
+                    meta: &meta,
+                    dir: slot2,
+                    registry: None,
+                },
+            ],
+            &archive,
+            None,
+        )
+        .unwrap();
+
+        let extracted = extract(&archive, dir.path().join("staging")).unwrap();
+        assert_eq!(extracted.manifest.game, GameId::Steam(391540));
+        assert_eq!(extracted.manifest.slots.len(), 2);
+        let genocide = extracted.slot_dir("Genocide");
+        assert_eq!(
+            fs::read_to_string(genocide.join("profiles/1/options.ini")).unwrap(),
+            "volume=3"
+        );
+        assert!(genocide.join("empty").is_dir());
+        assert!(extracted.registry_file("Genocide").is_some());
+        assert!(extracted.registry_file("Pacifist").is_none());
+        assert_eq!(
+            fs::read_dir(extracted.slot_dir("Pacifist"))
+                .unwrap()
+                .count(),
+            0
+        );
+
+        drop(extracted);
+        assert!(!dir.path().join("staging").exists());
+    }
+
+    #[test]
+    fn corrupted_archive_is_rejected() {
+        let dir = tempdir().unwrap();
+        let slot = dir.path().join("slot");
+        write(slot.join("file0"), "save data");
+        let meta = SlotMeta::default();
+        let archive = dir.path().join("archive.tar.zst");
+        export(
+            GameId::Steam(391540),
+            &[SlotSource {
+                name: &"Slot".parse().unwrap(),
+                meta: &meta,
+                dir: slot,
+                registry: None,
+            }],
+            &archive,
+            None,
+        )
+        .unwrap();
+
+        // Rewrite the archive with different contents but the original manifest
+        let mut tar = Vec::new();
+        zstd::Decoder::new(File::open(&archive).unwrap())
+            .unwrap()
+            .read_to_end(&mut tar)
+            .unwrap();
+        let needle = b"save data";
+        let i = tar
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap();
+        tar[i] = b'S';
+        fs::write(&archive, zstd::encode_all(tar.as_slice(), 0).unwrap()).unwrap();
+
+        let err = extract(&archive, dir.path().join("staging")).unwrap_err();
+        assert!(
+            err.to_string().contains("slots/Slot/file0 is corrupted"),
+            "{err}"
+        );
+        assert!(!dir.path().join("staging").exists());
+    }
+
+    #[test]
+    fn encrypted_archive_needs_the_session_key() {
+        let dir = tempdir().unwrap();
+        let slot = dir.path().join("slot");
+        write(slot.join("file0"), "save data");
+        let meta = SlotMeta::default();
+        let archive = dir.path().join("archive.tar.zst");
+        let (_, key) = crypt::EncryptionConfig::new("hunter2");
+        export(
+            GameId::Steam(391540),
+            &[SlotSource {
+                name: &"Slot".parse().unwrap(),
+                meta: &meta,
+                dir: slot,
+                registry: None,
+            }],
+            &archive,
+            Some(&key),
+        )
+        .unwrap();
+
+        assert!(crypt::is_encrypted(&archive).unwrap());
+        let err = crypt::with_session_key(None, || extract(&archive, dir.path().join("staging")))
+            .unwrap_err();
+        assert!(
+            err.downcast_ref::<crypt::CryptError>()
+                .is_some_and(|err| matches!(err, crypt::CryptError::Locked)),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn manifest_dirs_stay_in_staging_dir() {
+        let dir = tempdir().unwrap();
+        let manifest = ArchiveManifest {
+            version: ARCHIVE_VERSION,
+            game: GameId::Steam(391540),
+            slots: vec![SlotEntry {
+                name: "Slot".parse().unwrap(),
+                meta: SlotMeta::default(),
+                files: BTreeMap::new(),
+                dirs: BTreeSet::from(["../../../escaped".to_string()]),
+                registry: None,
+            }],
+        };
+        let archive = dir.path().join("archive.tar.zst");
+        let mut builder = tar::Builder::new(Vec::new());
+        append_data(
+            &mut builder,
+            MANIFEST_PATH,
+            toml::to_string(&manifest).unwrap().as_bytes(),
+        )
+        .unwrap();
+        let tar = builder.into_inner().unwrap();
+        fs::write(&archive, zstd::encode_all(tar.as_slice(), 0).unwrap()).unwrap();
+
+        let staging = dir.path().join("staging");
+        let err = extract(&archive, staging.clone()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("not a plain relative path"),
+            "{err:#}"
+        );
+        assert!(!dir.path().join("escaped").exists());
+        assert!(!staging.exists());
+    }
+
+    #[test]
+    fn conflicting_names() {
+        let name = |name: &str| name.parse::<SlotName>().unwrap();
+        let existing = [name("Slot"), name("Slot (2)")];
+        assert_eq!(
+            OnConflict::Rename.resolve(&name("Slot"), &existing),
+            Some(name("Slot (3)"))
+        );
+        assert_eq!(
+            OnConflict::Overwrite.resolve(&name("Slot"), &existing),
+            Some(name("Slot"))
+        );
+        assert_eq!(OnConflict::Skip.resolve(&name("Slot"), &existing), None);
+        assert_eq!(
+            OnConflict::Skip.resolve(&name("Other"), &existing),
+            Some(name("Other"))
+        );
+        // Names at the length limit are cut short for the suffix
+        let long = name(&"a".repeat(64));
+        assert_eq!(
+            OnConflict::Rename.resolve(&long, std::slice::from_ref(&long)),
+            Some(name(&format!("{} (2)", "a".repeat(60))))
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+
+use crate::{
+    app::App,
+    crypt::{self, CryptError},
+    dir_swapper::SwapMode,
+    launch::{self, Launcher},
+    registry::{RegistryKey, WineRegistryKeys},
+    save_manager::{GameId, SaveManager},
+    slot_name::SlotName,
+    usage::QuotaConfig,
+    utils,
+};
+
+/// Passphrase of encrypted saves, for scripts that cannot enter it.
+const PASSPHRASE_VAR: &str = "VITTUSAVE_PASSPHRASE";
+
+/// Save slot manager. Starts the terminal UI when run without a command.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    /// Asks for the passphrase of encrypted saves first. `VITTUSAVE_PASSPHRASE` is used instead
+    /// if it is set
+    #[arg(long, global = true)]
+    unlock: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Loads a slot, plays the game and saves the progress into the slot once the game exits
+    Launch {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Slot to load, defaults to the loaded one
+        #[arg(long)]
+        slot: Option<SlotName>,
+        /// Runs this instead of launching through Steam, remembered for later launches
+        #[arg(long)]
+        executable: Option<PathBuf>,
+        /// Records every save in the history of the slot while the game runs
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Like `launch`, but runs the given command. Meant for Steam launch options:
+    /// `vittusave wrap %command%`
+    Wrap {
+        /// Steam app ID or name of the game, set by Steam
+        #[arg(long, env = "SteamAppId")]
+        game: String,
+        /// Slot to load, defaults to the loaded one
+        #[arg(long)]
+        slot: Option<SlotName>,
+        /// Records every save in the history of the slot while the game runs
+        #[arg(long)]
+        watch: bool,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<OsString>,
+    },
+    /// Adds a slot with a copy of another slot or of the live save directory, without loading
+    /// anything
+    Duplicate {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Name of the new slot
+        name: SlotName,
+        /// Slot to copy, defaults to the live save directory
+        #[arg(long)]
+        from: Option<SlotName>,
+    },
+    /// Lists the snapshots recorded with `--watch`, oldest first
+    History {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Defaults to the loaded slot
+        #[arg(long)]
+        slot: Option<SlotName>,
+    },
+    /// Rolls a slot back to a snapshot listed by `history`
+    Restore {
+        /// Steam app ID or name of the game
+        game: String,
+        snapshot: String,
+        /// Defaults to the loaded slot
+        #[arg(long)]
+        slot: Option<SlotName>,
+    },
+    /// Shows or changes whether slots are loaded by copying them or by linking the save directory
+    /// to the slot
+    Mode {
+        /// Steam app ID or name of the game
+        game: String,
+        /// `copy` or `symlink`
+        mode: Option<SwapMode>,
+    },
+    /// Points a game to the new location of its save directory, e.g. after it was moved
+    Relocate {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Save directory of the game
+        path: PathBuf,
+    },
+    /// Swaps registry keys in the Wine prefix of a game along with its slots. The Proton prefix
+    /// and the registry locations listed on the wiki are used unless given
+    Registry {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Wine prefix of the game
+        #[arg(long)]
+        prefix: Option<PathBuf>,
+        /// Registry key to swap, e.g. `HKEY_CURRENT_USER\Software\Game`. Can be repeated
+        #[arg(long = "key")]
+        keys: Vec<RegistryKey>,
+        /// Stops swapping registry keys
+        #[arg(long, conflicts_with_all = ["prefix", "keys"])]
+        off: bool,
+    },
+    /// Stores the slots of a game that are not loaded compressed. Slots are compressed right away
+    /// and again in the background once they were not modified for an hour
+    Compress {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Decompresses all slots and stops compressing them
+        #[arg(long)]
+        off: bool,
+    },
+    /// Encrypts the slots of a game that are not loaded with the passphrase, along with their
+    /// snapshots, registry keys and trashed slots, which also compresses them
+    Encrypt {
+        /// Steam app ID or name of the game
+        game: String,
+        /// Decrypts all slots and stops encrypting them
+        #[arg(long)]
+        off: bool,
+    },
+    /// Sets up the passphrase that slots and exported archives are encrypted with
+    Passphrase,
+    /// Shows the disk space used by the slots and snapshots of every game and by the trash
+    Usage {
+        /// Prunes the oldest snapshots first until the quotas are met
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Reverts the last delete, rename, load or save directory change
+    Undo,
+    /// Does the last operation reverted by `undo` again
+    Redo,
+}
+
+impl Cli {
+    /// Runs the command, returning the exit code of the game for `launch` and `wrap`.
+    pub fn run(self) -> Result<ExitCode> {
+        if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+            crypt::unlock(&passphrase)?;
+        } else if self.unlock {
+            crypt::unlock(&rpassword::prompt_password("Passphrase: ")?)?;
+        }
+        let Some(command) = self.command else {
+            let terminal = ratatui::init();
+            let result = App::build()?.run(terminal);
+            ratatui::restore();
+            return result.map(|_| ExitCode::SUCCESS);
+        };
+        let mut save_manager = SaveManager::build()?;
+        let (game, slot, launcher, watch) = match command {
+            CliCommand::Launch {
+                game,
+                slot,
+                executable,
+                watch,
+            } => {
+                let id = find_game(&save_manager, &game)?;
+                if executable.is_some() {
+                    save_manager
+                        .set_executable(id, executable)
+                        .expect("game should exist");
+                    save_manager.save()?;
+                }
+                (id, slot, Launcher::of(&save_manager, id), watch)
+            }
+            CliCommand::Wrap {
+                game,
+                slot,
+                watch,
+                command,
+            } => (
+                find_game(&save_manager, &game)?,
+                slot,
+                Launcher::Command(command),
+                watch,
+            ),
+            CliCommand::Duplicate { game, name, from } => {
+                let id = find_game(&save_manager, &game)?;
+                let created = match &from {
+                    Some(from) => save_manager.duplicate(id, from, &name)?,
+                    None => save_manager.create_from_live(id, &name)?,
+                };
+                created.with_context(|| match &from {
+                    Some(from) => {
+                        format!("slot \"{from}\" does not exist or \"{name}\" already exists")
+                    }
+                    None => format!("slot \"{name}\" already exists"),
+                })?;
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::History { game, slot } => {
+                let id = find_game(&save_manager, &game)?;
+                let slot = slot_or_loaded(&save_manager, id, slot)?;
+                let now = SystemTime::now();
+                for snapshot in save_manager.history(id, &slot)?.unwrap_or_default() {
+                    let age = snapshot
+                        .parse()
+                        .ok()
+                        .map(|millis| SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+                        .and_then(|time| now.duration_since(time).ok())
+                        .map_or_else(String::new, utils::format_age);
+                    println!("{snapshot}  {age}");
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Restore {
+                game,
+                snapshot,
+                slot,
+            } => {
+                let id = find_game(&save_manager, &game)?;
+                let slot = slot_or_loaded(&save_manager, id, slot)?;
+                save_manager
+                    .restore(id, &slot, &snapshot)?
+                    .with_context(|| format!("slot \"{slot}\" has no snapshot {snapshot}"))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Mode { game, mode } => {
+                let id = find_game(&save_manager, &game)?;
+                if let Some(mode) = mode {
+                    save_manager.set_mode(id, mode)?.expect("game should exist");
+                    save_manager.save()?;
+                }
+                let swapper = &save_manager
+                    .get(id)
+                    .expect("game should exist")
+                    .slot_swapper;
+                match swapper.mode() {
+                    SwapMode::Copy => println!("copy"),
+                    SwapMode::Symlink => println!("symlink, {}", swapper.link_state()?),
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Relocate { game, path } => {
+                let id = find_game(&save_manager, &game)?;
+                if !path.is_dir() {
+                    bail!("{} is not a directory", path.display());
+                }
+                save_manager.set_path(id, path).expect("game should exist");
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Registry {
+                game,
+                prefix,
+                mut keys,
+                off,
+            } => {
+                let id = find_game(&save_manager, &game)?;
+                if off {
+                    save_manager
+                        .set_registry(id, None)?
+                        .expect("game should exist");
+                    save_manager.save()?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+                let prefix = match prefix {
+                    Some(prefix) => prefix,
+                    None => id.proton_prefix()?.with_context(|| {
+                        format!("no Proton prefix found for {id}, pass --prefix")
+                    })?,
+                };
+                if !prefix.join("system.reg").is_file() {
+                    bail!("{} is not a Wine prefix", prefix.display());
+                }
+                if keys.is_empty() {
+                    keys = id
+                        .fetch_locations()?
+                        .map(|locations| locations.registry_keys())
+                        .unwrap_or_default();
+                }
+                if keys.is_empty() {
+                    bail!("no registry keys are known for {id}, pass --key");
+                }
+                for key in &keys {
+                    println!("{key}");
+                }
+                save_manager
+                    .set_registry(id, Some(WineRegistryKeys::new(prefix, keys)))?
+                    .expect("game should exist");
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Compress { game, off } => {
+                let id = find_game(&save_manager, &game)?;
+                let compressed = save_manager
+                    .set_compression(id, !off)?
+                    .expect("game should exist");
+                if !off {
+                    println!("Compressed {compressed} slots");
+                }
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Encrypt { game, off } => {
+                let id = find_game(&save_manager, &game)?;
+                let encrypted = match save_manager.set_encryption(id, !off) {
+                    Err(err) if matches!(err.downcast_ref(), Some(CryptError::Locked)) => {
+                        return Err(err.context("run with --unlock to enter the passphrase"));
+                    }
+                    result => result?.expect("game should exist"),
+                };
+                if !off {
+                    println!("Encrypted {encrypted} slots");
+                }
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Passphrase => {
+                if crypt::is_set_up()? {
+                    bail!(CryptError::AlreadySetUp);
+                }
+                let passphrase = rpassword::prompt_password("New passphrase: ")?;
+                if passphrase.is_empty() {
+                    bail!("the passphrase is empty");
+                }
+                if rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+                    bail!("the passphrases do not match");
+                }
+                crypt::set_up(&passphrase)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Usage { prune } => {
+                let config = QuotaConfig::read()?;
+                if prune {
+                    let (pruned, _) = save_manager.enforce_quota(&config)?;
+                    println!("Pruned {pruned} snapshots");
+                }
+                let report = save_manager.usage()?;
+                for game in &report.games {
+                    println!("{}  {}", game.title(), utils::format_size(game.total()));
+                    for slot in &game.slots {
+                        println!(
+                            "  {}  {}, {} snapshots {}",
+                            slot.name,
+                            utils::format_size(slot.size),
+                            slot.snapshots.len(),
+                            utils::format_size(slot.history_size())
+                        );
+                    }
+                    if game.trash > 0 {
+                        println!("  trash  {}", utils::format_size(game.trash));
+                    }
+                }
+                println!("Trash  {}", utils::format_size(report.trash));
+                println!("Total  {}", utils::format_size(report.total()));
+                if let Some(available) = report.available {
+                    println!("Free   {}", utils::format_size(available));
+                }
+                for warning in config.warnings(&report) {
+                    eprintln!("warning: {warning}");
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Undo => {
+                match save_manager.undo()? {
+                    Some(operation) => println!("Undid: {operation}"),
+                    None => println!("Nothing to undo"),
+                }
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            CliCommand::Redo => {
+                match save_manager.redo()? {
+                    Some(operation) => println!("Redid: {operation}"),
+                    None => println!("Nothing to redo"),
+                }
+                save_manager.save()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+        };
+        let code = launch::launch(&mut save_manager, game, slot.as_ref(), &launcher, watch)?;
+        save_manager.save()?;
+        // Exit codes outside of `u8` are reported as a generic failure
+        Ok(code.map_or(ExitCode::SUCCESS, |code| {
+            u8::try_from(code).map_or(ExitCode::FAILURE, ExitCode::from)
+        }))
+    }
+}
+
+fn find_game(save_manager: &SaveManager, query: &str) -> Result<GameId> {
+    save_manager
+        .find(query)
+        .with_context(|| format!("no game \"{query}\" found, add it in the terminal UI first"))
+}
+
+fn slot_or_loaded(
+    save_manager: &SaveManager,
+    id: GameId,
+    slot: Option<SlotName>,
+) -> Result<SlotName> {
+    slot.or_else(|| save_manager.get(id)?.slot_swapper.active_version().cloned())
+        .with_context(|| format!("game {id} has no loaded slot, pass --slot"))
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::{
+    crypt::{self, DecryptReader, EncryptWriter, Key},
+    dir_swapper::PendingCompression,
+    save_manager::{GameId, SaveManager},
+};
+
+/// Zstd level of compressed versions, which are written once and read rarely.
+const LEVEL: i32 = 9;
+
+/// How long a version has to stay unchanged before it is compressed in the background.
+pub const MIN_IDLE: Duration = Duration::from_secs(60 * 60);
+/// How often the background job looks for versions to compress.
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Writes the contents of `dir` to a zstd-compressed tar archive at `archive`, keeping symbolic
+/// links, permissions and modification times. The archive is encrypted if `key` is set.
+pub fn compress_dir(dir: &Path, archive: &Path, key: Option<&Key>) -> io::Result<()> {
+    let file = File::create(archive)?;
+    let file = match key {
+        Some(key) => write_tar(dir, EncryptWriter::new(file, key)?)?.finish()?,
+        None => write_tar(dir, file)?,
+    };
+    file.sync_all()
+}
+
+fn write_tar<W: Write>(dir: &Path, writer: W) -> io::Result<W> {
+    let mut builder = tar::Builder::new(zstd::Encoder::new(writer, LEVEL)?);
+    builder.follow_symlinks(false);
+    builder.mode(tar::HeaderMode::Complete);
+    builder.append_dir_all(".", dir)?;
+    builder.into_inner()?.finish()
+}
+
+/// Unpacks an archive written by [`compress_dir`] into `dir`, replacing existing files. Encrypted
+/// archives need the key of the session, see [`crypt::session_key`].
+pub fn extract(archive: &Path, dir: &Path) -> io::Result<()> {
+    let file = File::open(archive)?;
+    if crypt::is_encrypted(archive)? {
+        unpack(DecryptReader::new(file, &crypt::session_key()?)?, dir)
+    } else {
+        unpack(file, dir)
+    }
+}
+
+fn unpack(reader: impl Read, dir: &Path) -> io::Result<()> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.set_overwrite(true);
+    archive.unpack(dir)
+}
+
+type Job = (GameId, PendingCompression);
+
+/// Compresses inactive versions of games with compression enabled on a background thread. Only
+/// the archives are written there, they replace the versions on the thread calling
+/// [`Recompressor::tick`] if the versions did not change in the meantime.
+#[derive(Debug)]
+pub struct Recompressor {
+    jobs: Sender<Job>,
+    done: Receiver<(Job, io::Result<()>)>,
+    /// Jobs sent to the background thread and not finished yet
+    running: usize,
+    /// Version directories that could not be compressed, which are not retried
+    failed: HashSet<PathBuf>,
+    next_scan: Instant,
+}
+
+impl Recompressor {
+    pub fn start() -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let (sender, done) = mpsc::channel();
+        thread::spawn(move || {
+            for job in receiver {
+                let result = job.1.run();
+                if sender.send((job, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs,
+            done,
+            running: 0,
+            failed: HashSet::new(),
+            next_scan: Instant::now(),
+        }
+    }
+
+    /// Finishes the versions that were compressed in the meantime and queues new ones once all
+    /// are done. Meant to be called whenever the application is idle.
+    pub fn tick(&mut self, save_manager: &SaveManager) {
+        while let Ok(((game, pending), result)) = self.done.try_recv() {
+            self.running -= 1;
+            let finished = result
+                .map_err(anyhow::Error::from)
+                .and_then(|()| save_manager.finish_compression(game, &pending));
+            match finished {
+                Ok(true) => info!("compressed slot \"{}\" of {game}", pending.name),
+                Ok(false) => {}
+                Err(err) => {
+                    warn!("failed to compress slot \"{}\": {err:#}", pending.name);
+                    pending.discard();
+                    self.failed.insert(pending.dir().to_path_buf());
+                }
+            }
+        }
+        if self.running > 0 || Instant::now() < self.next_scan {
+            return;
+        }
+        self.next_scan = Instant::now() + SCAN_INTERVAL;
+        let jobs = match save_manager.pending_compressions(MIN_IDLE) {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                warn!("failed to look for slots to compress: {err:#}");
+                return;
+            }
+        };
+        for job in jobs {
+            if self.failed.contains(job.1.dir()) {
+                continue;
+            }
+            if self.jobs.send(job).is_err() {
+                warn!("background compression stopped");
+                return;
+            }
+            self.running += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn archives_keep_contents_and_links() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("profiles")).unwrap();
+        fs::write(src.join("profiles/save.dat"), "save").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("profiles/save.dat", src.join("current")).unwrap();
+
+        let archive = dir.path().join("src.tar.zst");
+        compress_dir(&src, &archive, None).unwrap();
+        let dst = dir.path().join("dst");
+        fs::create_dir(&dst).unwrap();
+        extract(&archive, &dst).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dst.join("profiles/save.dat")).unwrap(),
+            "save"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dst.join("current")).unwrap(),
+            Path::new("profiles/save.dat")
+        );
+    }
+
+    #[test]
+    fn encrypted_archives_need_the_session_key() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("save.dat"), "save").unwrap();
+        let (_, key) = crypt::EncryptionConfig::new("hunter2");
+        let archive = dir.path().join("src.tar.zst");
+        compress_dir(&src, &archive, Some(&key)).unwrap();
+        assert!(crypt::is_encrypted(&archive).unwrap());
+
+        let err = crypt::with_session_key(None, || extract(&archive, dir.path())).unwrap_err();
+        assert_eq!(err.to_string(), crypt::CryptError::Locked.to_string());
+    }
+}
@@ -18,8 +18,29 @@ pub static CACHE_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
 
 pub const DATA_FILENAME: &str = "vittusave";
 
+/// Deleted slots and games, see `crate::trash::Trash`.
+pub static TRASH_DIR: LazyLock<PathBuf> = LazyLock::new(|| DATA_DIR.join("trash"));
+/// Expiry of the trash, see `crate::trash::TrashConfig`.
+pub const TRASH_FILENAME: &str = "trash";
+/// Disk space limits of the slot store, see `crate::usage::QuotaConfig`.
+pub const QUOTA_FILENAME: &str = "quota";
+/// Salt of the key the saves of the user are encrypted with, see
+/// `crate::crypt::EncryptionConfig`.
+pub const ENCRYPTION_FILENAME: &str = "encryption";
+
 pub const PCGW_API: &str = "https://www.pcgamingwiki.com/w/api.php";
 
+/// Optional Ludusavi manifest in `DATA_DIR` that is used alongside the PCGamingWiki.
+pub const LUDUSAVI_MANIFEST_FILENAME: &str = "manifest.yaml";
+
+/// User-maintained save locations, see `crate::sources::LocalOverrides`.
+pub const OVERRIDES_FILENAME: &str = "overrides";
+
+/// Remote the slot store is synced with, see `crate::sync::SyncConfig`.
+pub const SYNC_FILENAME: &str = "sync";
+/// Slot hashes as of the last sync.
+pub const SYNC_STATE_FILENAME: &str = "sync-state";
+
 pub static SAVE_SLOT_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     dirs::document_dir()
         .expect("no document directory found")
@@ -0,0 +1,265 @@
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+};
+
+use filetime::FileTime;
+use log::warn;
+
+/// Totals of a [`DirCopier`] run, also passed to its progress callback after every entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CopyStats {
+    pub files: u64,
+    pub dirs: u64,
+    pub symlinks: u64,
+    /// Size of the files that were copied or reflinked, hard links are free
+    pub bytes: u64,
+    /// Files sharing their data with the source through copy-on-write
+    pub reflinked: u64,
+    /// Files hard linked to an unchanged file in the link destination
+    pub hardlinked: u64,
+    /// Sockets, FIFOs and devices, which cannot be copied
+    pub skipped: u64,
+}
+
+/// Copies directory trees without recursion, so that deep trees cannot overflow the stack.
+/// Modification times and permissions are kept, symbolic links are copied as links and files are
+/// reflinked on filesystems that support it.
+#[derive(Default)]
+pub struct DirCopier<'a> {
+    link_dest: Option<&'a Path>,
+    progress: Option<&'a mut dyn FnMut(&CopyStats)>,
+}
+
+impl<'a> DirCopier<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Hard links files that have the same size and modification time in `dir` as in the source
+    /// instead of copying them. Only for copies that are never modified in place, like snapshots,
+    /// as both paths share the same data.
+    pub fn link_dest(mut self, dir: &'a Path) -> Self {
+        self.link_dest = Some(dir);
+        self
+    }
+    /// Calls `progress` after every copied entry.
+    pub fn progress(mut self, progress: &'a mut dyn FnMut(&CopyStats)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+    /// Copies the contents of `src` into `dst`, creating `dst` if needed. Existing entries in
+    /// `dst` are replaced.
+    pub fn copy(&mut self, src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<CopyStats> {
+        let (src, dst) = (src.as_ref(), dst.as_ref());
+        let mut stats = CopyStats::default();
+        fs::create_dir_all(dst)?;
+        // Metadata of directories is applied once their contents are copied, as copying into a
+        // directory changes its modification time
+        let mut dirs = Vec::new();
+        let mut stack = vec![PathBuf::new()];
+        while let Some(parent) = stack.pop() {
+            for entry in fs::read_dir(src.join(&parent))? {
+                let relative = parent.join(entry?.file_name());
+                let (from, to) = (src.join(&relative), dst.join(&relative));
+                let metadata = fs::symlink_metadata(&from)?;
+                let file_type = metadata.file_type();
+                if file_type.is_dir() {
+                    if fs::symlink_metadata(&to).is_ok_and(|existing| !existing.is_dir()) {
+                        fs::remove_file(&to)?;
+                    }
+                    fs::create_dir_all(&to)?;
+                    stats.dirs += 1;
+                    dirs.push((to, metadata));
+                    stack.push(relative);
+                } else if file_type.is_symlink() {
+                    remove_existing(&to)?;
+                    copy_symlink(&from, &to)?;
+                    let mtime = FileTime::from_last_modification_time(&metadata);
+                    filetime::set_symlink_file_times(&to, mtime, mtime)?;
+                    stats.symlinks += 1;
+                } else if file_type.is_file() {
+                    remove_existing(&to)?;
+                    self.copy_file(&relative, &from, &to, &metadata, &mut stats)?;
+                    stats.files += 1;
+                } else {
+                    warn!("skipping special file {}", from.display());
+                    stats.skipped += 1;
+                }
+                if let Some(progress) = &mut self.progress {
+                    progress(&stats);
+                }
+            }
+        }
+        // Parents were found before their children
+        for (to, metadata) in dirs.iter().rev() {
+            fs::set_permissions(to, metadata.permissions())?;
+            filetime::set_file_mtime(to, FileTime::from_last_modification_time(metadata))?;
+        }
+        filetime::set_file_mtime(
+            dst,
+            FileTime::from_last_modification_time(&fs::metadata(src)?),
+        )?;
+        Ok(stats)
+    }
+
+    fn copy_file(
+        &self,
+        relative: &Path,
+        from: &Path,
+        to: &Path,
+        metadata: &Metadata,
+        stats: &mut CopyStats,
+    ) -> io::Result<()> {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        if let Some(link_dest) = self.link_dest {
+            let unchanged = link_dest.join(relative);
+            if fs::symlink_metadata(&unchanged).is_ok_and(|other| {
+                other.is_file()
+                    && other.len() == metadata.len()
+                    && FileTime::from_last_modification_time(&other) == mtime
+            }) {
+                fs::hard_link(unchanged, to)?;
+                stats.hardlinked += 1;
+                return Ok(());
+            }
+        }
+        if reflink_copy::reflink_or_copy(from, to)?.is_none() {
+            stats.reflinked += 1;
+        }
+        stats.bytes += metadata.len();
+        fs::set_permissions(to, metadata.permissions())?;
+        filetime::set_file_times(to, FileTime::from_last_access_time(metadata), mtime)
+    }
+}
+
+/// Removes a file or link that is in the way of a copy.
+fn remove_existing(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    // Windows has separate links for directories, the target decides which one the link was
+    if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn write(path: impl AsRef<Path>, contents: &str) {
+        let path = path.as_ref();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn mtime(path: impl AsRef<Path>) -> FileTime {
+        FileTime::from_last_modification_time(&fs::symlink_metadata(path).unwrap())
+    }
+
+    #[test]
+    fn copies_metadata_and_links() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        write(src.join("profiles/1/save.dat"), "save");
+        let old = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3600));
+        filetime::set_file_mtime(src.join("profiles/1/save.dat"), old).unwrap();
+        filetime::set_file_mtime(src.join("profiles"), old).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::os::unix::fs::symlink("profiles/1", src.join("current")).unwrap();
+            fs::set_permissions(
+                src.join("profiles/1/save.dat"),
+                fs::Permissions::from_mode(0o600),
+            )
+            .unwrap();
+        }
+
+        let dst = dir.path().join("dst");
+        let mut calls = 0;
+        let mut progress = |_: &CopyStats| calls += 1;
+        let stats = DirCopier::new()
+            .progress(&mut progress)
+            .copy(&src, &dst)
+            .unwrap();
+        assert_eq!((stats.files, stats.dirs, stats.bytes), (1, 2, 4));
+        assert_eq!(
+            fs::read_to_string(dst.join("profiles/1/save.dat")).unwrap(),
+            "save"
+        );
+        assert_eq!(mtime(dst.join("profiles/1/save.dat")), old);
+        assert_eq!(mtime(dst.join("profiles")), old);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(stats.symlinks, 1);
+            assert_eq!(calls, 4);
+            assert_eq!(
+                fs::read_link(dst.join("current")).unwrap(),
+                Path::new("profiles/1")
+            );
+            let mode = fs::metadata(dst.join("profiles/1/save.dat"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn unchanged_files_are_hard_linked() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        write(src.join("same.dat"), "same");
+        write(src.join("changed.dat"), "old");
+        let previous = dir.path().join("previous");
+        DirCopier::new().copy(&src, &previous).unwrap();
+        write(src.join("changed.dat"), "new contents");
+
+        let stats = DirCopier::new()
+            .link_dest(&previous)
+            .copy(&src, dir.path().join("next"))
+            .unwrap();
+        assert_eq!((stats.files, stats.hardlinked), (2, 1));
+        assert_eq!(stats.bytes, "new contents".len() as u64);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("next/changed.dat")).unwrap(),
+            "new contents"
+        );
+    }
+
+    #[test]
+    fn deep_trees_are_copied() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        let mut deep = src.clone();
+        for _ in 0..200 {
+            deep.push("d");
+        }
+        write(deep.join("save.dat"), "deep");
+
+        let stats = DirCopier::new().copy(&src, dir.path().join("dst")).unwrap();
+        assert_eq!((stats.files, stats.dirs), (1, 200));
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Result, bail};
+use argon2::Argon2;
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{
+        Aead, OsRng,
+        generic_array::GenericArray,
+        rand_core::RngCore,
+        stream::{DecryptorBE32, EncryptorBE32},
+    },
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{consts::ENCRYPTION_FILENAME, utils};
+
+/// Start of every file written by [`EncryptWriter`].
+const MAGIC: &[u8; 8] = b"VSCRYPT1";
+const SALT_LEN: usize = 16;
+/// Random part of the nonce of every chunk, the remaining 5 bytes count the chunks.
+const NONCE_PREFIX_LEN: usize = 19;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_PREFIX_LEN;
+/// Plaintext bytes per authenticated chunk.
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+/// Encrypted into the config, so that a wrong passphrase is told apart from corrupted data.
+const CHECK: &[u8] = b"vittusave";
+
+#[derive(Debug, Error)]
+pub enum CryptError {
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+    #[error("no passphrase is set up")]
+    NotSetUp,
+    #[error("a passphrase is already set up")]
+    AlreadySetUp,
+    #[error("encrypted saves are locked, enter the passphrase first")]
+    Locked,
+    #[error("encrypted with the passphrase of another user")]
+    ForeignKey,
+    #[error("not encrypted by VittuSave")]
+    NotEncrypted,
+    #[error("encrypted data is corrupted or was modified")]
+    Corrupted,
+}
+
+impl From<CryptError> for io::Error {
+    fn from(err: CryptError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Key derived from the passphrase of the user with Argon2id.
+#[derive(Clone)]
+pub struct Key {
+    /// Salt of the user, written to encrypted files to recognize the key they need
+    salt: [u8; SALT_LEN],
+    key: chacha20poly1305::Key,
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("salt", &hex::encode(self.salt))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Key {
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Self {
+        let mut key = chacha20poly1305::Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .expect("salt and key lengths are valid");
+        Self { salt, key }
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.key)
+    }
+}
+
+/// Salt of the key of the user and a value encrypted with it, stored in
+/// `crate::consts::ENCRYPTION_FILENAME`. The passphrase itself is never stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    salt: String,
+    check: String,
+}
+
+impl EncryptionConfig {
+    /// Creates a config with a new salt for `passphrase`.
+    pub fn new(passphrase: &str) -> (Self, Key) {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = Key::derive(passphrase, salt);
+        let mut nonce = XNonce::default();
+        OsRng.fill_bytes(&mut nonce);
+        let mut check = nonce.to_vec();
+        check.extend(
+            key.cipher()
+                .encrypt(&nonce, CHECK)
+                .expect("short values can be encrypted"),
+        );
+        let config = Self {
+            salt: hex::encode(salt),
+            check: hex::encode(check),
+        };
+        (config, key)
+    }
+
+    pub fn read() -> Result<Option<Self>> {
+        utils::read_data(ENCRYPTION_FILENAME)
+    }
+
+    /// Derives the key of `passphrase`, failing if it is not the passphrase of this config.
+    pub fn key(&self, passphrase: &str) -> Result<Key, CryptError> {
+        let salt = hex::decode(&self.salt)
+            .ok()
+            .and_then(|salt| salt.try_into().ok())
+            .ok_or(CryptError::Corrupted)?;
+        let check = hex::decode(&self.check).map_err(|_| CryptError::Corrupted)?;
+        if check.len() < XNonce::default().len() {
+            return Err(CryptError::Corrupted);
+        }
+        let (nonce, check) = check.split_at(XNonce::default().len());
+        let key = Key::derive(passphrase, salt);
+        match key.cipher().decrypt(XNonce::from_slice(nonce), check) {
+            Ok(check) if check == CHECK => Ok(key),
+            _ => Err(CryptError::WrongPassphrase),
+        }
+    }
+}
+
+/// Key of the passphrase entered in this session, see [`unlock`].
+static SESSION_KEY: Mutex<Option<Key>> = Mutex::new(None);
+
+fn set_session_key(key: Option<Key>) {
+    *SESSION_KEY.lock().unwrap_or_else(PoisonError::into_inner) = key;
+}
+
+/// Runs `f` with `key` as the session key. The key is shared by all threads, so tests that depend
+/// on it run one at a time through this.
+#[cfg(test)]
+pub(crate) fn with_session_key<T>(key: Option<Key>, f: impl FnOnce() -> T) -> T {
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            set_session_key(None);
+        }
+    }
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    set_session_key(key);
+    let _reset = Reset;
+    f()
+}
+
+/// Whether the user has set up a passphrase.
+pub fn is_set_up() -> Result<bool> {
+    Ok(EncryptionConfig::read()?.is_some())
+}
+
+/// Sets up the passphrase of the user and unlocks the session with it. The passphrase cannot be
+/// changed afterwards, as everything encrypted with it would be lost.
+pub fn set_up(passphrase: &str) -> Result<()> {
+    if is_set_up()? {
+        bail!(CryptError::AlreadySetUp);
+    }
+    let (config, key) = EncryptionConfig::new(passphrase);
+    utils::write_data(ENCRYPTION_FILENAME, &config)?;
+    set_session_key(Some(key));
+    Ok(())
+}
+
+/// Derives the key of the user from `passphrase` and keeps it until the application exits.
+pub fn unlock(passphrase: &str) -> Result<()> {
+    let config = EncryptionConfig::read()?.ok_or(CryptError::NotSetUp)?;
+    set_session_key(Some(config.key(passphrase)?));
+    Ok(())
+}
+
+pub fn is_unlocked() -> bool {
+    SESSION_KEY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Key entered in this session with [`unlock`] or [`set_up`].
+pub fn session_key() -> Result<Key, CryptError> {
+    SESSION_KEY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .ok_or(CryptError::Locked)
+}
+
+/// Whether the file at `path` was written by [`EncryptWriter`].
+pub fn is_encrypted(path: impl AsRef<Path>) -> io::Result<bool> {
+    let mut magic = [0; MAGIC.len()];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes `data` to the file at `path`, encrypted with `key` if set.
+pub fn write_file(path: impl AsRef<Path>, data: &[u8], key: Option<&Key>) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    match key {
+        Some(key) => {
+            let mut writer = EncryptWriter::new(file, key)?;
+            writer.write_all(data)?;
+            writer.finish()?;
+        }
+        None => file.write_all(data)?,
+    }
+    Ok(())
+}
+
+/// Reads the file at `path`, which is decrypted with the key of the session if it was written by
+/// [`EncryptWriter`].
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+    let mut data = Vec::new();
+    if is_encrypted(path)? {
+        DecryptReader::new(file, &session_key()?)?.read_to_end(&mut data)?;
+    } else {
+        file.read_to_end(&mut data)?;
+    }
+    Ok(data)
+}
+
+/// Encrypts everything written to it with XChaCha20-Poly1305 in authenticated chunks, so that
+/// truncated or reordered data is detected. The data cannot be decrypted unless
+/// [`EncryptWriter::finish`] is called.
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    stream: EncryptorBE32<XChaCha20Poly1305>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    pub fn new(mut inner: W, key: &Key) -> io::Result<Self> {
+        let mut nonce = [0; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        inner.write_all(MAGIC)?;
+        inner.write_all(&key.salt)?;
+        inner.write_all(&nonce)?;
+        Ok(Self {
+            inner,
+            stream: EncryptorBE32::from_aead(key.cipher(), GenericArray::from_slice(&nonce)),
+            buf: Vec::with_capacity(CHUNK_LEN),
+        })
+    }
+
+    /// Writes the last chunk and returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        let Self {
+            mut inner,
+            stream,
+            buf,
+        } = self;
+        let chunk = stream
+            .encrypt_last(buf.as_slice())
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        inner.write_all(&chunk)?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // A full chunk is only written once more data follows, the last one is written by
+        // `finish`
+        if self.buf.len() == CHUNK_LEN && !data.is_empty() {
+            let chunk = self
+                .stream
+                .encrypt_next(self.buf.as_slice())
+                .map_err(|_| io::Error::other("encryption failed"))?;
+            self.inner.write_all(&chunk)?;
+            self.buf.clear();
+        }
+        let len = data.len().min(CHUNK_LEN - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts data written by [`EncryptWriter`], failing with [`CryptError::Corrupted`] as soon
+/// as a chunk does not authenticate.
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    /// `None` once the last chunk was decrypted
+    stream: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    /// Encrypted data read ahead, as only the byte after a chunk tells whether it is the last
+    pending: Vec<u8>,
+    plain: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Reads the header, failing if the data was not encrypted with `key`.
+    pub fn new(mut inner: R, key: &Key) -> io::Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        inner.read_exact(&mut header).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                CryptError::NotEncrypted.into()
+            } else {
+                err
+            }
+        })?;
+        let (magic, rest) = header.split_at(MAGIC.len());
+        let (salt, nonce) = rest.split_at(SALT_LEN);
+        if magic != MAGIC {
+            return Err(CryptError::NotEncrypted.into());
+        }
+        if salt != key.salt {
+            return Err(CryptError::ForeignKey.into());
+        }
+        Ok(Self {
+            inner,
+            stream: Some(DecryptorBE32::from_aead(
+                key.cipher(),
+                GenericArray::from_slice(nonce),
+            )),
+            pending: Vec::new(),
+            plain: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    /// Decrypts the next chunk into `plain`.
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let encrypted_len = CHUNK_LEN + TAG_LEN;
+        while self.pending.len() <= encrypted_len {
+            let start = self.pending.len();
+            self.pending.resize(encrypted_len + 1, 0);
+            let read = self.inner.read(&mut self.pending[start..]);
+            self.pending
+                .truncate(start + read.as_ref().map_or(0, |read| *read));
+            match read {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.plain = if self.pending.len() > encrypted_len {
+            let rest = self.pending.split_off(encrypted_len);
+            let chunk = std::mem::replace(&mut self.pending, rest);
+            self.stream
+                .as_mut()
+                .expect("chunks follow until the last one")
+                .decrypt_next(chunk.as_slice())
+        } else {
+            self.stream
+                .take()
+                .expect("chunks follow until the last one")
+                .decrypt_last(std::mem::take(&mut self.pending).as_slice())
+        }
+        .map_err(|_| CryptError::Corrupted)?;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plain.len() {
+            if self.stream.is_none() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let len = buf.len().min(self.plain.len() - self.pos);
+        buf[..len].copy_from_slice(&self.plain[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(data: &[u8], key: &Key) -> Vec<u8> {
+        let mut writer = EncryptWriter::new(Vec::new(), key).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(data: &[u8], key: &Key) -> io::Result<Vec<u8>> {
+        let mut plain = Vec::new();
+        DecryptReader::new(data, key)?.read_to_end(&mut plain)?;
+        Ok(plain)
+    }
+
+    fn crypt_error(err: io::Error) -> CryptError {
+        *err.into_inner().unwrap().downcast::<CryptError>().unwrap()
+    }
+
+    #[test]
+    fn passphrase_is_checked() {
+        let (config, key) = EncryptionConfig::new("hunter2");
+        assert_eq!(config.key("hunter2").unwrap().key, key.key);
+        assert!(matches!(
+            config.key("hunter3"),
+            Err(CryptError::WrongPassphrase)
+        ));
+        // Another user with the same passphrase has another key
+        let (_, other) = EncryptionConfig::new("hunter2");
+        assert_ne!(other.key, key.key);
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let (_, key) = EncryptionConfig::new("hunter2");
+        for len in [0, 1, CHUNK_LEN, CHUNK_LEN + 1, 3 * CHUNK_LEN] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encrypted = encrypt(&data, &key);
+            assert_eq!(decrypt(&encrypted, &key).unwrap(), data, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn modified_data_is_rejected() {
+        let (_, key) = EncryptionConfig::new("hunter2");
+        let data = vec![7; 2 * CHUNK_LEN];
+        let encrypted = encrypt(&data, &key);
+
+        let mut flipped = encrypted.clone();
+        flipped[HEADER_LEN + 5] ^= 1;
+        let err = crypt_error(decrypt(&flipped, &key).unwrap_err());
+        assert!(matches!(err, CryptError::Corrupted));
+        // Dropping the last chunk is detected as well
+        let truncated = &encrypted[..HEADER_LEN + CHUNK_LEN + TAG_LEN];
+        let err = crypt_error(decrypt(truncated, &key).unwrap_err());
+        assert!(matches!(err, CryptError::Corrupted));
+
+        let (_, other) = EncryptionConfig::new("hunter2");
+        let err = crypt_error(decrypt(&encrypted, &other).unwrap_err());
+        assert!(matches!(err, CryptError::ForeignKey));
+        let err = crypt_error(decrypt(b"plain", &key).unwrap_err());
+        assert!(matches!(err, CryptError::NotEncrypted));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use similar::{ChangeTag, TextDiff};
+
+use crate::utils::walk_dir;
+
+/// Larger files are not diffed line by line.
+const TEXT_DIFF_LIMIT: u64 = 1024 * 1024;
+/// Unchanged lines shown around every change.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileInfo {
+    fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::symlink_metadata(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// A file that differs between two directories. `old` is `None` for added files and `new` for
+/// removed ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    /// Relative to the compared directories
+    pub path: PathBuf,
+    pub change: Change,
+    pub old: Option<FileInfo>,
+    pub new: Option<FileInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Equal(String),
+    Insert(String),
+    Delete(String),
+    /// Unchanged lines left out between changes
+    Skipped,
+}
+
+/// Compares the files in `old` and `new` by their contents, sorted by path. Unchanged files are
+/// left out.
+pub fn diff_dirs(old: &Path, new: &Path) -> Result<Vec<FileDiff>> {
+    let (old_files, _) =
+        walk_dir(old).with_context(|| format!("failed to read {}", old.display()))?;
+    let (new_files, _) =
+        walk_dir(new).with_context(|| format!("failed to read {}", new.display()))?;
+    let old_files = BTreeSet::from_iter(old_files);
+    let new_files = BTreeSet::from_iter(new_files);
+
+    let mut diffs = Vec::new();
+    for path in old_files.union(&new_files) {
+        let (old_path, new_path) = (old.join(path), new.join(path));
+        let old_info = old_files
+            .contains(path)
+            .then(|| FileInfo::read(&old_path))
+            .transpose()?;
+        let new_info = new_files
+            .contains(path)
+            .then(|| FileInfo::read(&new_path))
+            .transpose()?;
+        let change = match (old_info, new_info) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            (Some(old_info), Some(new_info)) => {
+                if old_info.size == new_info.size && fs::read(&old_path)? == fs::read(&new_path)? {
+                    continue;
+                }
+                Change::Modified
+            }
+        };
+        diffs.push(FileDiff {
+            path: path.clone(),
+            change,
+            old: old_info,
+            new: new_info,
+        });
+    }
+    Ok(diffs)
+}
+
+/// Line diff of the file at `path` in `old` and `new`, a missing file counting as empty.
+/// Returns `Ok(None)` if either side is not UTF-8 text or is too large.
+pub fn text_diff(old: &Path, new: &Path, path: &Path) -> Result<Option<Vec<DiffLine>>> {
+    let (Some(old_text), Some(new_text)) =
+        (read_text(&old.join(path))?, read_text(&new.join(path))?)
+    else {
+        return Ok(None);
+    };
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    let mut lines = Vec::new();
+    for (i, group) in diff.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        if i > 0 {
+            lines.push(DiffLine::Skipped);
+        }
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let text = change.value().trim_end_matches(['\r', '\n']).to_string();
+                lines.push(match change.tag() {
+                    ChangeTag::Equal => DiffLine::Equal(text),
+                    ChangeTag::Insert => DiffLine::Insert(text),
+                    ChangeTag::Delete => DiffLine::Delete(text),
+                });
+            }
+        }
+    }
+    Ok(Some(lines))
+}
+
+/// Reads a text file, `Some("")` if it does not exist and `None` if it is binary or too large.
+fn read_text(path: &Path) -> Result<Option<String>> {
+    if !path.exists() {
+        return Ok(Some(String::new()));
+    }
+    if fs::metadata(path)?.len() > TEXT_DIFF_LIMIT {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(fs::read(path)?)
+        .ok()
+        .filter(|text| !text.contains('\0')))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn added_removed_and_modified_files() {
+        let (old, new) = (tempdir().unwrap(), tempdir().unwrap());
+        fs::create_dir(old.path().join("profiles")).unwrap();
+        fs::create_dir(new.path().join("profiles")).unwrap();
+        fs::write(old.path().join("profiles/same.sav"), "same").unwrap();
+        fs::write(new.path().join("profiles/same.sav"), "same").unwrap();
+        fs::write(old.path().join("profiles/1.sav"), "level 1").unwrap();
+        fs::write(new.path().join("profiles/1.sav"), "level 2").unwrap();
+        fs::write(old.path().join("old.sav"), "").unwrap();
+        fs::write(new.path().join("new.sav"), "new").unwrap();
+
+        let diffs = diff_dirs(old.path(), new.path()).unwrap();
+        let changes: Vec<_> = diffs
+            .iter()
+            .map(|diff| (diff.path.to_str().unwrap(), diff.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("new.sav", Change::Added),
+                ("old.sav", Change::Removed),
+                ("profiles/1.sav", Change::Modified),
+            ]
+        );
+        assert_eq!(diffs[0].new.unwrap().size, 3);
+        assert!(diffs[0].old.is_none());
+    }
+
+    #[test]
+    fn text_files_are_diffed_by_line() {
+        let (old, new) = (tempdir().unwrap(), tempdir().unwrap());
+        let old_text: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let new_text = old_text
+            .replace("line 2\n", "line two\n")
+            .replace("line 19\n", "line 19\nline 19.5\n");
+        fs::write(old.path().join("config.ini"), old_text).unwrap();
+        fs::write(new.path().join("config.ini"), new_text).unwrap();
+        fs::write(old.path().join("save.bin"), [0, 159, 146, 150]).unwrap();
+
+        let lines = text_diff(old.path(), new.path(), Path::new("config.ini"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(lines[0], DiffLine::Equal("line 1".to_string()));
+        assert_eq!(lines[1], DiffLine::Delete("line 2".to_string()));
+        assert_eq!(lines[2], DiffLine::Insert("line two".to_string()));
+        assert!(lines.contains(&DiffLine::Skipped));
+        assert!(lines.contains(&DiffLine::Insert("line 19.5".to_string())));
+
+        assert!(
+            text_diff(old.path(), new.path(), Path::new("save.bin"))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
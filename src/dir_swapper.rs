@@ -1,33 +1,232 @@
-use anyhow::Result;
+mod link;
+
+pub use link::LinkState;
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::{
-    fs, io,
+    collections::{BTreeMap, HashMap},
+    fs, io, iter,
+    ops::Deref,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use strum::{Display, EnumString};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    compress,
+    consts::CACHE_DIR,
+    copy::DirCopier,
+    crypt::{self, Key},
+    diff::{self, FileDiff},
+    registry::WineRegistryKeys,
+    slot_name::SlotName,
+    usage::SlotUsage,
+    utils,
 };
 
-use crate::utils;
+/// Directory in the versions directory with automatic snapshots of every version as
+/// `<id>/<timestamp>/`, see [`DirSwapper::record_history`].
+const HISTORY_DIR: &str = ".history";
+
+/// File in the directory of a compressed version that holds its contents, see
+/// [`DirSwapper::set_compression`].
+const COMPRESSED_CONTENTS: &str = ".vittusave.tar.zst";
+
+/// Extension of the file next to a version directory with the name of the version, so that
+/// the name is kept when the versions directory is synced to another machine, see
+/// [`DirSwapper::adopt_versions`].
+const NAME_EXTENSION: &str = "name";
+
+/// Directory in `crate::consts::CACHE_DIR` that compressed versions are extracted to for
+/// reading, see [`VersionContents`].
+const EXTRACTED_DIR: &str = "extracted";
+
+/// Entries of a trash directory filled by [`DirSwapper::trash_version`].
+const TRASHED_VERSION: &str = "version";
+const TRASHED_REGISTRY: &str = "version.reg";
+const TRASHED_HISTORY: &str = "history";
+
+/// Where a version moved out by [`DirSwapper::trash_version`] came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashedVersion {
+    /// Name of the version directory in the versions directory
+    pub id: String,
+    /// The version was active, so the primary directory still has its contents
+    pub active: bool,
+}
+
+/// An inactive version whose archive is being written, see
+/// [`DirSwapper::prepare_compression`].
+#[derive(Debug)]
+pub struct PendingCompression {
+    pub name: SlotName,
+    dir: PathBuf,
+    archive: PathBuf,
+    /// Size and modification time of the contents when the compression was prepared
+    stamp: (u64, Option<SystemTime>),
+    /// Set if the archive is encrypted
+    key: Option<Key>,
+}
+
+impl PendingCompression {
+    /// Writes the archive next to the version directory without modifying it, so it can run
+    /// on another thread while the version is used.
+    pub fn run(&self) -> io::Result<()> {
+        compress::compress_dir(&self.dir, &self.archive, self.key.as_ref())
+    }
+    /// Removes the archive if it was written.
+    pub fn discard(&self) {
+        if let Err(err) = fs::remove_file(&self.archive)
+            && err.kind() != io::ErrorKind::NotFound
+        {
+            warn!("failed to remove {}: {err}", self.archive.display());
+        }
+    }
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// Directory with the contents of a version, see [`DirSwapper::contents_dir_of`]. Compressed
+/// versions are extracted to a temporary directory that is removed on drop, so that reading
+/// them leaves the stored archive as it is.
+#[derive(Debug)]
+pub struct VersionContents {
+    dir: PathBuf,
+    temporary: bool,
+}
+
+impl VersionContents {
+    fn stored(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            temporary: false,
+        }
+    }
+}
+
+impl Deref for VersionContents {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl AsRef<Path> for VersionContents {
+    fn as_ref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for VersionContents {
+    fn drop(&mut self) {
+        if self.temporary
+            && let Err(err) = fs::remove_dir_all(&self.dir)
+        {
+            warn!("failed to remove {}: {err}", self.dir.display());
+        }
+    }
+}
+
+/// Directory compared by [`DirSwapper::diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffSide<'a> {
+    /// The current contents of a version, see [`DirSwapper::contents_dir_of`]
+    Version(&'a SlotName),
+    /// The primary directory, i.e. what the game currently sees
+    Primary,
+}
+
+/// How versions are loaded into the primary directory.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum SwapMode {
+    /// The primary directory is a real directory that versions are moved or copied into
+    #[default]
+    Copy,
+    /// The primary directory is a link to the directory of the active version, so the game
+    /// writes into the version directly
+    Symlink,
+}
+
+/// What is known about the primary directory, see [`DirSwapper::primary_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimaryState {
+    Present,
+    /// Missing while the active version is empty, e.g. the game was never launched
+    NotCreated,
+    /// Missing while the active version has contents, e.g. the game moved its saves
+    Moved,
+    PermissionDenied,
+}
+
+/// Why the primary directory cannot be swapped. Versions are left untouched when this is
+/// returned.
+#[derive(Debug, Error)]
+pub enum PrimaryDirError {
+    #[error(
+        "save directory {0} is missing but the loaded slot has saves, relocate the game if they \
+         were moved"
+    )]
+    Moved(PathBuf),
+    #[error("no permission to access save directory {0}")]
+    PermissionDenied(PathBuf),
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DirSwapper {
     primary_dir: PathBuf,
     version_dir: PathBuf,
     // Should always be set unless there are no versions
-    active_version: Option<String>,
+    active_version: Option<SlotName>,
+    /// Directory names of versions in the versions directory, so that renaming a version does not
+    /// touch the disk. Directories missing from here are versions named after their directory.
+    #[serde(default)]
+    ids: BTreeMap<SlotName, String>,
+    /// Registry keys stored next to every version directory as `<id>.reg`
+    #[serde(default)]
+    registry: Option<WineRegistryKeys>,
+    /// The directory of the active version is an empty placeholder, as its contents were moved
+    /// into the primary directory instead of copied
+    #[serde(default)]
+    active_moved: bool,
+    #[serde(default)]
+    mode: SwapMode,
+    /// Inactive versions are stored as archives inside their directories
+    #[serde(default)]
+    compress: bool,
+    /// Inactive versions, snapshots, registry files and trashed versions are encrypted with the
+    /// key of the session
+    #[serde(default)]
+    encrypt: bool,
 }
 
 impl DirSwapper {
     /// Creates a new `DirSwapper`. The current contents of the primary directory will
     /// automatically get a version and corresponding directory assigned to it named `name`.
     /// Existing contents in the versions directory will be preserved.
-    pub fn build(primary_dir: PathBuf, version_dir: PathBuf, name: String) -> Result<Self> {
-        let swapper = Self {
+    pub fn build(primary_dir: PathBuf, version_dir: PathBuf, name: SlotName) -> Result<Self> {
+        let mut swapper = Self {
             primary_dir,
             version_dir,
-            active_version: Some(name),
+            active_version: None,
+            ids: BTreeMap::new(),
+            registry: None,
+            active_moved: false,
+            mode: SwapMode::Copy,
+            compress: false,
+            encrypt: false,
         };
-        if !fs::exists(swapper.build_version_dir(swapper.active_version.as_ref().unwrap()))? {
-            fs::create_dir(swapper.build_version_dir(swapper.active_version.as_ref().unwrap()))?;
-        }
+        swapper.add_version(&name)?;
+        swapper.active_version = Some(name);
         Ok(swapper)
     }
     /// If a version is active, it is stored here.
@@ -40,126 +239,1119 @@ impl DirSwapper {
     pub fn get_version_dir(&self) -> &Path {
         &self.version_dir
     }
+    /// Name of the directory of the specified version in the versions directory, whether or not it
+    /// exists.
+    fn id_of<'a>(&'a self, name: &'a SlotName) -> &'a str {
+        self.ids.get(name).map_or(name.as_str(), String::as_str)
+    }
     /// Build the full path of the specified version directory.
     #[inline]
-    fn build_version_dir(&self, name: &str) -> PathBuf {
-        self.version_dir.join(name)
+    fn build_version_dir(&self, name: &SlotName) -> PathBuf {
+        self.version_dir.join(self.id_of(name))
+    }
+    /// Build the full path of the registry file of the specified version.
+    #[inline]
+    fn build_registry_file(&self, name: &SlotName) -> PathBuf {
+        self.version_dir.join(format!("{}.reg", self.id_of(name)))
+    }
+    /// Build the full path of the name file of the specified version.
+    #[inline]
+    fn build_name_file(&self, name: &SlotName) -> PathBuf {
+        self.version_dir
+            .join(format!("{}.{NAME_EXTENSION}", self.id_of(name)))
+    }
+    /// Writes the name of the specified version next to its directory, if the directory is not
+    /// named after it.
+    fn write_name_file(&self, name: &SlotName) -> io::Result<()> {
+        if self.id_of(name) == name.as_str() {
+            return Ok(());
+        }
+        fs::write(self.build_name_file(name), name.as_str())
+    }
+    /// Removes the name file of the specified version, if any.
+    fn remove_name_file(&self, name: &SlotName) -> io::Result<()> {
+        let name_file = self.build_name_file(name);
+        if fs::exists(&name_file)? {
+            fs::remove_file(name_file)?;
+        }
+        Ok(())
+    }
+    /// Writes the name files of all versions, which versions created before they existed lack.
+    pub fn write_name_files(&self) -> Result<()> {
+        for name in self.ids.keys() {
+            if self.exists(name)? {
+                self.write_name_file(name)?;
+            }
+        }
+        Ok(())
+    }
+    /// Adds the directories in the versions directory that have a name file but are unknown to
+    /// this swapper, e.g. because they were pulled by `crate::sync::Syncer`, as versions under
+    /// that name. Taken names get a number, e.g. `main (2)`. Returns whether any were added.
+    pub fn adopt_versions(&mut self) -> Result<bool> {
+        let mut dirs = Vec::new();
+        for entry in fs::read_dir(&self.version_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let Ok(id) = entry.file_name().into_string() else {
+                continue;
+            };
+            if id.starts_with('.') || self.ids.values().any(|known| *known == id) {
+                continue;
+            }
+            let name_file = self.version_dir.join(format!("{id}.{NAME_EXTENSION}"));
+            if !fs::exists(&name_file)? {
+                continue;
+            }
+            if let Ok(name) = fs::read_to_string(name_file)?.trim().parse::<SlotName>()
+                && name.as_str() != id
+            {
+                dirs.push((id, name));
+            }
+        }
+        let mut adopted = false;
+        for (id, name) in dirs {
+            let mut free_name = None;
+            for candidate in iter::once(Some(name.clone()))
+                .chain((2..).map(|i| name.with_suffix(&i.to_string())))
+            {
+                let Some(candidate) = candidate else {
+                    break;
+                };
+                if !self.exists(&candidate)? {
+                    free_name = Some(candidate);
+                    break;
+                }
+            }
+            // Without a free name the directory stays a version named after itself
+            let Some(name) = free_name else {
+                continue;
+            };
+            self.ids.insert(name.clone(), id);
+            self.write_name_file(&name)?;
+            adopted = true;
+        }
+        Ok(adopted)
+    }
+    /// Build the full path of the snapshot history of the specified version.
+    #[inline]
+    fn build_history_dir(&self, name: &SlotName) -> PathBuf {
+        self.version_dir.join(HISTORY_DIR).join(self.id_of(name))
+    }
+    /// Whether the specified version exists. Directories used by another version are not
+    /// versions of their own.
+    fn exists(&self, name: &SlotName) -> io::Result<bool> {
+        if !self.ids.contains_key(name) && self.ids.values().any(|id| id == name.as_str()) {
+            return Ok(false);
+        }
+        fs::exists(self.build_version_dir(name))
     }
     /// Get the directory of the specified version, if it exists.
-    pub fn version_dir_of(&self, name: &str) -> Result<Option<PathBuf>> {
-        let version_dir = self.build_version_dir(name);
-        Ok(fs::exists(&version_dir)?.then_some(version_dir))
+    pub fn version_dir_of(&self, name: &SlotName) -> Result<Option<PathBuf>> {
+        Ok(self.exists(name)?.then(|| self.build_version_dir(name)))
     }
-    /// Returns a list of names of versions in their directory, sorted.
-    pub fn versions(&self) -> Result<Vec<String>> {
-        let vec = {
-            let mut vec = fs::read_dir(self.get_version_dir())?
-                .collect::<io::Result<Vec<_>>>()?
-                .iter()
-                .filter_map(|entry| match entry.file_type() {
-                    Ok(file_type) if file_type.is_dir() => {
-                        if let Ok(file_name) = entry.file_name().into_string() {
-                            Some(Ok(file_name))
-                        } else {
-                            None
-                        }
-                    }
-                    Ok(_) => None,
-                    Err(err) => Some(Err(err)),
-                })
-                .collect::<io::Result<Vec<_>>>()?;
-            vec.sort_unstable();
-            vec
+    /// Get the directory with the current contents of the specified version for reading, which
+    /// is the primary directory if it is active and exists. Compressed versions are extracted to
+    /// a temporary directory, see [`VersionContents`].
+    pub fn contents_dir_of(&self, name: &SlotName) -> Result<Option<VersionContents>> {
+        if self.active_version() == Some(name) && fs::exists(&self.primary_dir)? {
+            return Ok(Some(VersionContents::stored(self.primary_dir.clone())));
+        }
+        let Some(archive) = self.archive_of(name)? else {
+            return Ok(self.version_dir_of(name)?.map(VersionContents::stored));
         };
+        let dir = CACHE_DIR
+            .join(EXTRACTED_DIR)
+            .join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir)?;
+        // Removes the directory again if the extraction fails
+        let contents = VersionContents {
+            dir,
+            temporary: true,
+        };
+        compress::extract(&archive, &contents)?;
+        Ok(Some(contents))
+    }
+    /// Checks whether the primary directory exists and, if not, whether it is safe to create it.
+    pub fn primary_state(&self) -> Result<PrimaryState> {
+        match fs::read_dir(&self.primary_dir) {
+            Ok(_) => return Ok(PrimaryState::Present),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                return Ok(PrimaryState::PermissionDenied);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        let has_contents = match self.active_version.as_ref() {
+            Some(name) => match self.version_dir_of(name)? {
+                Some(dir) => self.active_moved || fs::read_dir(dir)?.next().is_some(),
+                None => false,
+            },
+            None => false,
+        };
+        Ok(if has_contents {
+            PrimaryState::Moved
+        } else {
+            PrimaryState::NotCreated
+        })
+    }
+    /// Returns `Ok(false)` if the primary directory was not created yet, and an error if it cannot
+    /// be used. Links are repaired in symlink mode.
+    fn check_primary_dir(&self) -> Result<bool> {
+        if self.mode == SwapMode::Symlink {
+            self.repair_link()?;
+            return Ok(self.link_state()? == LinkState::Linked);
+        }
+        match self.primary_state()? {
+            PrimaryState::Present => Ok(true),
+            PrimaryState::NotCreated => Ok(false),
+            PrimaryState::Moved => Err(PrimaryDirError::Moved(self.primary_dir.clone()).into()),
+            PrimaryState::PermissionDenied => {
+                Err(PrimaryDirError::PermissionDenied(self.primary_dir.clone()).into())
+            }
+        }
+    }
+    /// Creates the primary directory with the contents of the active version if it was not
+    /// created yet, so that the game finds the version when it first starts. In symlink mode the
+    /// link is repaired instead, see [`DirSwapper::repair_link`].
+    pub fn ensure_primary_dir(&self) -> Result<()> {
+        if self.mode == SwapMode::Symlink {
+            self.repair_link()?;
+            return Ok(());
+        }
+        if self.check_primary_dir()? {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.primary_dir)?;
+        if let Some(version_dir) = self
+            .active_version
+            .as_ref()
+            .map(|name| self.version_dir_of(name))
+            .transpose()?
+            .flatten()
+        {
+            utils::copy_dir_all(version_dir, &self.primary_dir)?;
+        }
+        Ok(())
+    }
+    /// Get the current registry keys of the specified version as a `.reg` file, if the swapper
+    /// has registry keys and the version has any stored.
+    pub fn registry_of(&self, name: &SlotName) -> Result<Option<String>> {
+        let Some(registry) = &self.registry else {
+            return Ok(None);
+        };
+        if self.active_version() == Some(name) {
+            return registry.export().map(Some);
+        }
+        let registry_file = self.build_registry_file(name);
+        Ok(if fs::exists(&registry_file)? {
+            Some(String::from_utf8(crypt::read_file(registry_file)?)?)
+        } else {
+            None
+        })
+    }
+    /// Get the directory of a side of a diff, if it exists.
+    pub fn dir_of(&self, side: DiffSide) -> Result<Option<VersionContents>> {
+        match side {
+            DiffSide::Version(name) => self.contents_dir_of(name),
+            DiffSide::Primary => Ok(Some(VersionContents::stored(self.primary_dir.clone()))),
+        }
+    }
+    /// Compares the files of two versions or a version and the primary directory, see
+    /// [`diff::diff_dirs`]. Returns `Ok(None)` if a version does not exist.
+    pub fn diff(&self, old: DiffSide, new: DiffSide) -> Result<Option<Vec<FileDiff>>> {
+        let (Some(old), Some(new)) = (self.dir_of(old)?, self.dir_of(new)?) else {
+            return Ok(None);
+        };
+        diff::diff_dirs(&old, &new).map(Some)
+    }
+    /// Returns a list of names of versions, sorted. Directories that are hidden, used by another
+    /// version or not valid names are not versions.
+    pub fn versions(&self) -> Result<Vec<SlotName>> {
+        let mut vec = Vec::new();
+        for entry in fs::read_dir(self.get_version_dir())? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if let Some((name, _)) = self.ids.iter().find(|(_, id)| **id == file_name) {
+                vec.push(name.clone());
+            } else if let Ok(name) = file_name.parse::<SlotName>()
+                && !self.ids.contains_key(&name)
+            {
+                vec.push(name);
+            }
+        }
+        vec.sort_unstable();
         Ok(vec)
     }
+    /// Moves `version_dir` to the primary directory, which must not exist, and leaves an empty
+    /// placeholder in its place. Returns `Ok(false)` if the directory could not be moved, e.g.
+    /// because it is on another filesystem, and has to be copied instead.
+    fn move_to_primary(&self, version_dir: &Path) -> io::Result<bool> {
+        if let Some(parent) = self.primary_dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(err) = fs::rename(version_dir, &self.primary_dir) {
+            info!("copying {} instead of moving: {err}", version_dir.display());
+            return Ok(false);
+        }
+        fs::create_dir(version_dir)?;
+        Ok(true)
+    }
+    /// How versions are loaded into the primary directory.
+    #[inline]
+    pub fn mode(&self) -> SwapMode {
+        self.mode
+    }
+    /// Converts the primary directory for `mode`. A link is replaced by a copy of the active
+    /// version, and a directory becomes the contents of the active version before it is replaced
+    /// by a link.
+    pub fn set_mode(&mut self, mode: SwapMode) -> Result<()> {
+        if mode == self.mode {
+            return Ok(());
+        }
+        match mode {
+            SwapMode::Symlink => {
+                self.check_primary_dir()?;
+                self.mode = mode;
+                self.active_moved = false;
+                if self.repair_link()? == LinkState::Directory && self.active_version.is_none() {
+                    bail!(
+                        "no slot is loaded to keep the contents of {}",
+                        self.primary_dir.display()
+                    );
+                }
+            }
+            SwapMode::Copy => {
+                if self.link_state()? != LinkState::Directory {
+                    link::remove_link(&self.primary_dir)?;
+                    fs::create_dir_all(&self.primary_dir)?;
+                    if let Some(name) = &self.active_version
+                        && let Some(version_dir) = self.version_dir_of(name)?
+                    {
+                        utils::copy_dir_all(version_dir, &self.primary_dir)?;
+                    }
+                }
+                self.mode = mode;
+            }
+        }
+        Ok(())
+    }
+    /// Inspects the link at the primary directory, which is only expected in symlink mode.
+    pub fn link_state(&self) -> Result<LinkState> {
+        let expected = self
+            .active_version
+            .as_ref()
+            .map(|name| self.build_version_dir(name));
+        Ok(LinkState::read(&self.primary_dir, expected.as_deref())?)
+    }
+    /// Points the primary directory at the active version again. A real directory in its place
+    /// has the latest saves, so it replaces the contents of the active version. Targets of foreign
+    /// links are left untouched. Returns the state before the repair, nothing is changed if no
+    /// version is active.
+    pub fn repair_link(&self) -> Result<LinkState> {
+        let state = self.link_state()?;
+        let Some(name) = self.active_version.as_ref() else {
+            return Ok(state);
+        };
+        let version_dir = self.build_version_dir(name);
+        match &state {
+            LinkState::Linked => {}
+            LinkState::Missing | LinkState::Broken(_) => {
+                fs::create_dir_all(&version_dir)?;
+                link::replace_link(&self.primary_dir, &version_dir)?;
+            }
+            LinkState::Foreign(target) => {
+                warn!(
+                    "replacing link from {} to {}",
+                    self.primary_dir.display(),
+                    target.display()
+                );
+                link::replace_link(&self.primary_dir, &version_dir)?;
+            }
+            LinkState::Directory => {
+                if fs::exists(&version_dir)? {
+                    fs::remove_dir_all(&version_dir)?;
+                }
+                if let Err(err) = fs::rename(&self.primary_dir, &version_dir) {
+                    info!("copying the primary directory instead of moving: {err}");
+                    utils::copy_dir_all(&self.primary_dir, &version_dir)?;
+                    fs::remove_dir_all(&self.primary_dir)?;
+                }
+                link::replace_link(&self.primary_dir, &version_dir)?;
+            }
+        }
+        Ok(state)
+    }
     /// Saves the contents of the primary directory to its correct location and replaces it with
-    /// the contents inside of version `name`'s directory. Returns `Ok(None)` if the version did
-    /// not exist.
-    pub fn set_active(&mut self, name: String) -> Result<Option<()>> {
-        if self.version_dir_of(&name)?.is_none() {
+    /// the contents inside of version `name`'s directory, creating the primary directory if it was
+    /// not created yet. Directories are moved when they are on the same filesystem and copied
+    /// otherwise. Returns `Ok(None)` if the version did not exist, and [`PrimaryDirError`] if the
+    /// primary directory cannot be used.
+    pub fn set_active(&mut self, name: SlotName) -> Result<Option<()>> {
+        let Some(new_version_dir) = self.version_dir_of(&name)? else {
             return Ok(None);
+        };
+        // Fails before anything changes if the old version cannot be encrypted
+        self.key()?;
+        self.decompress_version(&name)?;
+        let old_name = self.active_version.clone();
+        if self.mode == SwapMode::Symlink {
+            if self.repair_link()? == LinkState::Directory && self.active_version.is_none() {
+                bail!(
+                    "no slot is loaded to keep the contents of {}",
+                    self.primary_dir.display()
+                );
+            }
+            if let Some(registry) = &self.registry
+                && let Some(old_name) = &old_name
+            {
+                self.export_registry(registry, &self.build_registry_file(old_name))?;
+            }
+            link::replace_link(&self.primary_dir, &new_version_dir)?;
+            self.active_version = Some(name.clone());
+            self.import_registry(&name)?;
+            self.encrypt_inactive(old_name.as_ref());
+            return Ok(Some(()));
         }
-
+        if !self.check_primary_dir()? {
+            // Nothing to save, the active version keeps its contents
+            self.active_moved = self.move_to_primary(&new_version_dir)?;
+            if !self.active_moved {
+                utils::copy_dir_all(&new_version_dir, self.primary_dir())?;
+            }
+            self.active_version = Some(name.clone());
+            self.import_registry(&name)?;
+            return Ok(Some(()));
+        }
+        let old_name = old_name
+            .as_ref()
+            .expect("active version should be set if any other version exists");
+        // The directory can be removed by other means, e.g. by a sync
         let old_version_dir = self
-            .version_dir_of(
-                self.active_version
-                    .as_deref()
-                    .expect("active version should be set if any other version exists"),
-            )?
-            .expect("active version directory should exist");
-        let new_version_dir = self
-            .version_dir_of(&name)?
-            .expect(&format!("version \"{name}\" should exist"));
-
-        utils::remove_dir_contents(&old_version_dir)?;
-        utils::copy_dir_all(self.primary_dir(), &old_version_dir)?;
-        utils::remove_dir_contents(self.primary_dir())?;
-        utils::copy_dir_all(&new_version_dir, self.primary_dir())?;
-
-        self.active_version = Some(name.to_string());
+            .version_dir_of(old_name)?
+            .with_context(|| format!("directory of the loaded slot \"{old_name}\" is missing"))?;
+
+        // The keys of the new version are only imported once its files are in place, so that a
+        // failed swap leaves the keys of the old version
+        if let Some(registry) = &self.registry {
+            self.export_registry(registry, &self.build_registry_file(old_name))?;
+        }
+        // The stored copy of the old version is outdated, the primary directory replaces it.
+        // Symbolic links to the real save directory have to stay in place, so they are copied.
+        let moved = fs::symlink_metadata(&self.primary_dir)?.is_dir() && {
+            fs::remove_dir_all(&old_version_dir)?;
+            match fs::rename(&self.primary_dir, &old_version_dir) {
+                Ok(()) if self.move_to_primary(&new_version_dir)? => true,
+                Ok(()) => {
+                    fs::rename(&old_version_dir, &self.primary_dir)?;
+                    false
+                }
+                Err(err) => {
+                    info!("copying the primary directory instead of moving: {err}");
+                    fs::create_dir(&old_version_dir)?;
+                    false
+                }
+            }
+        };
+        if !moved {
+            utils::remove_dir_contents(&old_version_dir)?;
+            utils::copy_dir_all(self.primary_dir(), &old_version_dir)?;
+            utils::remove_dir_contents(self.primary_dir())?;
+            let stats = DirCopier::new().copy(&new_version_dir, self.primary_dir())?;
+            info!(
+                "loaded {name}: {} files, {} bytes, {} reflinked",
+                stats.files, stats.bytes, stats.reflinked
+            );
+        }
+
+        self.active_moved = moved;
+        self.active_version = Some(name.clone());
+        self.import_registry(&name)?;
+        self.encrypt_inactive(Some(old_name));
         Ok(Some(()))
     }
-    /// Add a new version and create a correponding directory. Returns `Ok(None)` if version already
-    /// exists.
-    pub fn add_version(&mut self, name: &str) -> Result<Option<()>> {
-        if fs::exists(self.build_version_dir(name))? {
+    /// Compresses a version that was just unloaded right away if encryption is enabled, so that
+    /// its contents do not stay readable until [`crate::compress::Recompressor`] gets to them.
+    /// Failures are only logged, as the version is compressed again later.
+    fn encrypt_inactive(&self, name: Option<&SlotName>) {
+        if let Some(name) = name
+            && self.encrypt
+            && let Err(err) = self.compress_version(name)
+        {
+            warn!("failed to compress slot \"{name}\": {err:#}");
+        }
+    }
+    /// Writes `reg`, the contents of a `.reg` file, to `path`, encrypted if encryption is
+    /// enabled.
+    fn write_registry_file(&self, path: &Path, reg: &str) -> Result<()> {
+        crypt::write_file(path, reg.as_bytes(), self.key()?.as_ref())?;
+        Ok(())
+    }
+    /// Exports the keys of `registry` to `path`, see [`DirSwapper::write_registry_file`].
+    fn export_registry(&self, registry: &WineRegistryKeys, path: &Path) -> Result<()> {
+        self.write_registry_file(path, &registry.export()?)
+    }
+    /// Imports the registry keys of the specified version into the Wine prefix, if any.
+    fn import_registry(&self, name: &SlotName) -> Result<()> {
+        if let Some(registry) = &self.registry {
+            registry.import_from(self.build_registry_file(name))?;
+        }
+        Ok(())
+    }
+    /// Add a new version and create a correponding directory with a generated name. Returns
+    /// `Ok(None)` if version already exists.
+    pub fn add_version(&mut self, name: &SlotName) -> Result<Option<()>> {
+        if self.exists(name)? {
             return Ok(None);
         }
-        fs::create_dir(self.build_version_dir(name))?;
+        let id = Uuid::new_v4().to_string();
+        fs::create_dir(self.version_dir.join(&id))?;
+        self.ids.insert(name.clone(), id);
+        self.write_name_file(name)?;
         Ok(Some(()))
     }
-    pub fn rename_version(&mut self, name: &str, new_name: &str) -> Result<Option<()>> {
-        if !fs::exists(self.build_version_dir(name))? {
+    /// Add a new version with a copy of `dir` and, if given, the registry file `registry`.
+    /// Returns `Ok(None)` if version already exists.
+    pub fn import_version(
+        &mut self,
+        name: &SlotName,
+        dir: &Path,
+        registry: Option<&Path>,
+    ) -> Result<Option<()>> {
+        if self.add_version(name)?.is_none() {
             return Ok(None);
         }
-        let path = self
-            .version_dir_of(name)?
-            .expect(&format!("version \"{name}\" should exist"));
-        let new_path = {
-            let mut new_path = path.clone();
-            new_path.set_file_name(new_name);
-            new_path
+        utils::copy_dir_all(dir, self.build_version_dir(name))?;
+        if let Some(registry) = registry {
+            let registry = fs::read_to_string(registry)?;
+            self.write_registry_file(&self.build_registry_file(name), &registry)?;
+        }
+        Ok(Some(()))
+    }
+    /// Add a new version `new_name` with a copy of the current contents and registry keys of
+    /// version `name`, which are read from the primary directory if it is active. Returns
+    /// `Ok(None)` if `name` does not exist or `new_name` already exists.
+    pub fn duplicate_version(
+        &mut self,
+        name: &SlotName,
+        new_name: &SlotName,
+    ) -> Result<Option<()>> {
+        let Some(dir) = self.contents_dir_of(name)? else {
+            return Ok(None);
         };
-        fs::rename(path, new_path)?;
+        if self.exists(new_name)? {
+            return Ok(None);
+        }
+        let registry = self.registry_of(name)?;
+        self.add_version(new_name)?;
+        utils::copy_dir_all(dir, self.build_version_dir(new_name))?;
+        if let Some(registry) = registry {
+            self.write_registry_file(&self.build_registry_file(new_name), &registry)?;
+        }
+        Ok(Some(()))
+    }
+    /// Add a new version with a copy of the primary directory and the registry keys, whether or
+    /// not a version is active. The version is empty if the primary directory was not created
+    /// yet. Returns `Ok(None)` if version already exists.
+    pub fn add_version_from_primary(&mut self, name: &SlotName) -> Result<Option<()>> {
+        let primary_exists = self.check_primary_dir()?;
+        if self.add_version(name)?.is_none() {
+            return Ok(None);
+        }
+        if primary_exists {
+            utils::copy_dir_all(self.primary_dir(), self.build_version_dir(name))?;
+        }
+        if let Some(registry) = &self.registry {
+            self.export_registry(registry, &self.build_registry_file(name))?;
+        }
+        Ok(Some(()))
+    }
+    /// Renames a version without touching its files, keeping it active if it was. Returns
+    /// `Ok(None)` if `name` does not exist or `new_name` already exists.
+    pub fn rename_version(&mut self, name: &SlotName, new_name: &SlotName) -> Result<Option<()>> {
+        if !self.exists(name)? || self.exists(new_name)? {
+            return Ok(None);
+        }
+        let id = self.id_of(name).to_string();
+        self.remove_name_file(name)?;
+        self.ids.remove(name);
+        self.ids.insert(new_name.clone(), id);
+        self.write_name_file(new_name)?;
+        if self.active_version.as_ref() == Some(name) {
+            self.active_version = Some(new_name.clone());
+        }
         Ok(Some(()))
     }
     /// Delete a version and its corresponding directory.
-    pub fn delete_version(&mut self, name: &str) -> Result<Option<()>> {
-        if !fs::exists(self.build_version_dir(name))? {
+    pub fn delete_version(&mut self, name: &SlotName) -> Result<Option<()>> {
+        let Some(version_dir) = self.version_dir_of(name)? else {
             return Ok(None);
+        };
+        if self.active_version.as_ref() == Some(name) {
+            self.active_version = None;
+            self.active_moved = false;
         }
-        if self
-            .active_version
-            .as_ref()
-            .is_some_and(|version_name| version_name == name)
+        fs::remove_dir_all(version_dir)?;
+        let registry_file = self.build_registry_file(name);
+        if fs::exists(&registry_file)? {
+            fs::remove_file(registry_file)?;
+        }
+        let history_dir = self.build_history_dir(name);
+        if fs::exists(&history_dir)? {
+            fs::remove_dir_all(history_dir)?;
+        }
+        self.remove_name_file(name)?;
+        self.ids.remove(name);
+
+        Ok(Some(()))
+    }
+    /// Moves a version with its registry keys and history into `trash_dir`, where
+    /// [`DirSwapper::restore_version`] can bring it back from. The active version is updated from
+    /// the primary directory first, and the version is compressed and encrypted before it is
+    /// moved if encryption is enabled. Returns `Ok(None)` if the version does not exist.
+    pub fn trash_version(
+        &mut self,
+        name: &SlotName,
+        trash_dir: &Path,
+    ) -> Result<Option<TrashedVersion>> {
+        let Some(version_dir) = self.version_dir_of(name)? else {
+            return Ok(None);
+        };
+        let key = self.key()?;
+        let active = self.active_version.as_ref() == Some(name);
+        if active && self.primary_state()? == PrimaryState::Present {
+            self.snapshot()?;
+        }
+        if let Some(key) = &key
+            && !self.is_compressed(name)?
         {
+            pack_dir(&version_dir, key)?;
+        }
+        fs::create_dir_all(trash_dir)?;
+        utils::move_path(version_dir, trash_dir.join(TRASHED_VERSION))?;
+        let registry_file = self.build_registry_file(name);
+        if fs::exists(&registry_file)? {
+            utils::move_path(registry_file, trash_dir.join(TRASHED_REGISTRY))?;
+        }
+        let history_dir = self.build_history_dir(name);
+        if fs::exists(&history_dir)? {
+            utils::move_path(history_dir, trash_dir.join(TRASHED_HISTORY))?;
+        }
+        let id = self.id_of(name).to_string();
+        if active {
             self.active_version = None;
+            self.active_moved = false;
+        }
+        self.remove_name_file(name)?;
+        self.ids.remove(name);
+        Ok(Some(TrashedVersion { id, active }))
+    }
+    /// Moves a version trashed by [`DirSwapper::trash_version`] back from `trash_dir` under
+    /// `name`. It becomes active again if it was and no other version was loaded since. Returns
+    /// `Ok(None)` if `name` or the directory of the version is taken.
+    pub fn restore_version(
+        &mut self,
+        name: &SlotName,
+        trashed: &TrashedVersion,
+        trash_dir: &Path,
+    ) -> Result<Option<()>> {
+        let version_dir = self.version_dir.join(&trashed.id);
+        if self.exists(name)? || fs::exists(&version_dir)? {
+            return Ok(None);
+        }
+        let activate = trashed.active && self.active_version.is_none();
+        let trashed_version = trash_dir.join(TRASHED_VERSION);
+        // The active version is never stored compressed
+        if activate {
+            unpack_dir(&trashed_version)?;
+        }
+        utils::move_path(trashed_version, version_dir)?;
+        if trashed.id != name.as_str() {
+            self.ids.insert(name.clone(), trashed.id.clone());
+            self.write_name_file(name)?;
+        }
+        let registry_file = trash_dir.join(TRASHED_REGISTRY);
+        if fs::exists(&registry_file)? {
+            utils::move_path(registry_file, self.build_registry_file(name))?;
+        }
+        let history_dir = trash_dir.join(TRASHED_HISTORY);
+        if fs::exists(&history_dir)? {
+            fs::create_dir_all(self.version_dir.join(HISTORY_DIR))?;
+            utils::move_path(history_dir, self.build_history_dir(name))?;
+        }
+        if activate {
+            self.active_version = Some(name.clone());
+        }
+        Ok(Some(()))
+    }
+    /// Copies the contents of the primary directory and the registry keys back into the active
+    /// version, e.g. after the game was played. The version is kept as is if the primary
+    /// directory was not created yet. Returns `Ok(None)` if no version is active.
+    pub fn snapshot(&self) -> Result<Option<()>> {
+        let Some(name) = self.active_version.as_ref() else {
+            return Ok(None);
+        };
+        if !self.check_primary_dir()? {
+            return Ok(Some(()));
+        }
+        let version_dir = self
+            .version_dir_of(name)?
+            .with_context(|| format!("directory of the loaded slot \"{name}\" is missing"))?;
+        if let Some(registry) = &self.registry {
+            self.export_registry(registry, &self.build_registry_file(name))?;
+        }
+        // The game already wrote into the version
+        if self.mode == SwapMode::Symlink {
+            return Ok(Some(()));
+        }
+        utils::remove_dir_contents(&version_dir)?;
+        utils::copy_dir_all(self.primary_dir(), &version_dir)?;
+        Ok(Some(()))
+    }
+    /// Records the contents of the primary directory and the registry keys as a snapshot in the
+    /// history of the active version, removing the oldest snapshots beyond `limit`. Snapshots
+    /// are stored as encrypted archives if encryption is enabled. Returns the name of the
+    /// snapshot, or `Ok(None)` if no version is active or the primary directory was not created
+    /// yet.
+    pub fn record_history(&self, limit: usize) -> Result<Option<String>> {
+        let Some(name) = self.active_version.as_ref() else {
+            return Ok(None);
+        };
+        if !self.check_primary_dir()? {
+            return Ok(None);
+        }
+        let key = self.key()?;
+        let history_dir = self.build_history_dir(name);
+        fs::create_dir_all(&history_dir)?;
+        let previous = self.history(name)?.pop();
+        // Milliseconds since the epoch, padded so that names sort chronologically
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let snapshot = (millis..)
+            .map(|millis| format!("{millis:015}"))
+            .find(|snapshot| !history_dir.join(snapshot).exists())
+            .expect("snapshot names should not run out");
+        let snapshot_dir = history_dir.join(&snapshot);
+        if let Some(key) = &key {
+            fs::create_dir(&snapshot_dir)?;
+            // The link in symlink mode is not archived itself
+            let contents = fs::canonicalize(self.primary_dir())?;
+            compress::compress_dir(
+                &contents,
+                &snapshot_dir.join(COMPRESSED_CONTENTS),
+                Some(key),
+            )?;
+        } else {
+            // Snapshots are never modified, so unchanged files can share their data
+            let mut copier = DirCopier::new();
+            let previous = previous.map(|previous| history_dir.join(previous));
+            if let Some(previous) = &previous {
+                copier = copier.link_dest(previous);
+            }
+            copier.copy(self.primary_dir(), &snapshot_dir)?;
+        }
+        if let Some(registry) = &self.registry {
+            self.export_registry(registry, &snapshot_dir.with_extension("reg"))?;
         }
-        fs::remove_dir_all(
-            self.version_dir_of(name)?
-                .expect("version directory should exist"),
-        )?;
 
+        let history = self.history(name)?;
+        for old in &history[..history.len().saturating_sub(limit)] {
+            self.remove_snapshot(name, old)?;
+        }
+        Ok(Some(snapshot))
+    }
+    /// Get the directory of a snapshot of the specified version, if it exists.
+    fn snapshot_dir(&self, name: &SlotName, snapshot: &str) -> io::Result<Option<PathBuf>> {
+        // Snapshot names are timestamps, anything else could point outside of the history
+        if snapshot.is_empty() || !snapshot.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Ok(None);
+        }
+        let snapshot_dir = self.build_history_dir(name).join(snapshot);
+        Ok(fs::exists(&snapshot_dir)?.then_some(snapshot_dir))
+    }
+    /// Removes a snapshot from the history of the specified version. Returns `Ok(None)` if it
+    /// does not exist.
+    pub fn remove_snapshot(&self, name: &SlotName, snapshot: &str) -> Result<Option<()>> {
+        let Some(snapshot_dir) = self.snapshot_dir(name, snapshot)? else {
+            return Ok(None);
+        };
+        fs::remove_dir_all(&snapshot_dir)?;
+        let registry_file = snapshot_dir.with_extension("reg");
+        if fs::exists(&registry_file)? {
+            fs::remove_file(registry_file)?;
+        }
+        Ok(Some(()))
+    }
+    /// Returns the disk space used by the stored copy and registry file of the specified version
+    /// and by each of its snapshots. Files hard linked between snapshots are counted once and
+    /// only add to the size of a snapshot if no other snapshot holds them.
+    pub fn usage(&self, name: &SlotName) -> Result<SlotUsage> {
+        let mut stored = HashMap::new();
+        if let Some(dir) = self.version_dir_of(name)? {
+            utils::collect_files(dir, &mut stored)?;
+        }
+        let registry_file = self.build_registry_file(name);
+        if fs::exists(&registry_file)? {
+            utils::collect_files(registry_file, &mut stored)?;
+        }
+        let size = stored.values().sum();
+
+        let history_dir = self.build_history_dir(name);
+        let mut snapshot_files = Vec::new();
+        // Number of snapshots and stored copies holding every file
+        let mut holders: HashMap<_, usize> = stored.keys().map(|id| (id.clone(), 1)).collect();
+        for snapshot in self.history(name)? {
+            let mut files = HashMap::new();
+            utils::collect_files(history_dir.join(&snapshot), &mut files)?;
+            let registry_file = history_dir.join(format!("{snapshot}.reg"));
+            if fs::exists(&registry_file)? {
+                utils::collect_files(registry_file, &mut files)?;
+            }
+            for id in files.keys() {
+                *holders.entry(id.clone()).or_default() += 1;
+            }
+            snapshot_files.push((snapshot, files));
+        }
+        let mut shared = HashMap::new();
+        let mut snapshots = Vec::new();
+        for (snapshot, files) in snapshot_files {
+            let mut snapshot_size = 0;
+            for (id, len) in files {
+                if holders[&id] == 1 {
+                    snapshot_size += len;
+                } else if !stored.contains_key(&id) {
+                    shared.insert(id, len);
+                }
+            }
+            snapshots.push((snapshot, snapshot_size));
+        }
+        Ok(SlotUsage {
+            name: name.clone(),
+            size,
+            snapshots,
+            shared_history: shared.values().sum(),
+        })
+    }
+    /// Returns the snapshots in the history of the specified version, oldest first.
+    pub fn history(&self, name: &SlotName) -> Result<Vec<String>> {
+        let history_dir = self.build_history_dir(name);
+        if !fs::exists(&history_dir)? {
+            return Ok(Vec::new());
+        }
+        let mut history = Vec::new();
+        for entry in fs::read_dir(history_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir()
+                && let Ok(snapshot) = entry.file_name().into_string()
+            {
+                history.push(snapshot);
+            }
+        }
+        history.sort_unstable();
+        Ok(history)
+    }
+    /// Replaces the contents of the specified version with a snapshot from its history, which is
+    /// loaded into the primary directory if the version is active. Returns `Ok(None)` if the
+    /// version or the snapshot does not exist.
+    pub fn restore_history(&self, name: &SlotName, snapshot: &str) -> Result<Option<()>> {
+        let Some(snapshot_dir) = self.snapshot_dir(name, snapshot)? else {
+            return Ok(None);
+        };
+        if self.active_version() == Some(name) {
+            self.ensure_primary_dir()?;
+        }
+        let target_dir = if self.active_version() == Some(name) {
+            self.primary_dir.clone()
+        } else {
+            // The archive of a compressed version is replaced along with everything else
+            let Some(version_dir) = self.version_dir_of(name)? else {
+                return Ok(None);
+            };
+            version_dir
+        };
+        let archive = snapshot_dir.join(COMPRESSED_CONTENTS);
+        let archived = fs::exists(&archive)?;
+        // Nothing is removed if the snapshot cannot be decrypted
+        if archived && crypt::is_encrypted(&archive)? {
+            crypt::session_key()?;
+        }
+        utils::remove_dir_contents(&target_dir)?;
+        if archived {
+            compress::extract(&archive, &target_dir)?;
+        } else {
+            utils::copy_dir_all(&snapshot_dir, &target_dir)?;
+        }
+        let registry_file = snapshot_dir.with_extension("reg");
+        if let Some(registry) = &self.registry {
+            if self.active_version() == Some(name) {
+                registry.import_from(&registry_file)?;
+            } else if fs::exists(&registry_file)? {
+                fs::copy(registry_file, self.build_registry_file(name))?;
+            }
+        }
+        self.encrypt_inactive(Some(name));
         Ok(Some(()))
     }
+    /// Whether inactive versions are stored compressed.
+    #[inline]
+    pub fn compression(&self) -> bool {
+        self.compress
+    }
+    /// Stores inactive versions compressed from now on, see
+    /// [`DirSwapper::prepare_compression`]. Disabling compression decompresses all versions and
+    /// disables encryption.
+    pub fn set_compression(&mut self, enabled: bool) -> Result<()> {
+        if !enabled && fs::exists(&self.version_dir)? {
+            for name in self.versions()? {
+                self.decompress_version(&name)?;
+            }
+        }
+        self.compress = enabled;
+        self.encrypt &= enabled;
+        Ok(())
+    }
+    /// Whether the archives of inactive versions are encrypted.
+    #[inline]
+    pub fn encryption(&self) -> bool {
+        self.encrypt
+    }
+    /// Encrypts inactive versions, snapshots, registry files and trashed versions from now on
+    /// with the key of the session, which enables compression. Archives written with the other
+    /// setting are unpacked, so that they are compressed again, and existing snapshots and
+    /// registry files are rewritten.
+    ///
+    /// Inactive versions are compressed when the active version changes, see
+    /// [`DirSwapper::encrypt_versions`] for the ones already inactive. Only the active version
+    /// stays readable on disk.
+    pub fn set_encryption(&mut self, enabled: bool) -> Result<()> {
+        let key = if enabled {
+            let key = crypt::session_key()?;
+            self.compress = true;
+            Some(key)
+        } else {
+            None
+        };
+        if fs::exists(&self.version_dir)? {
+            for name in self.versions()? {
+                if let Some(archive) = self.archive_of(&name)?
+                    && crypt::is_encrypted(&archive)? != enabled
+                {
+                    self.decompress_version(&name)?;
+                }
+                convert_file(&self.build_registry_file(&name), key.as_ref())?;
+                let history_dir = self.build_history_dir(&name);
+                for snapshot in self.history(&name)? {
+                    let snapshot_dir = history_dir.join(snapshot);
+                    match &key {
+                        Some(key) if !fs::exists(snapshot_dir.join(COMPRESSED_CONTENTS))? => {
+                            pack_dir(&snapshot_dir, key)?;
+                        }
+                        Some(_) => {}
+                        None => unpack_dir(&snapshot_dir)?,
+                    }
+                    convert_file(&snapshot_dir.with_extension("reg"), key.as_ref())?;
+                }
+            }
+        }
+        self.encrypt = enabled;
+        Ok(())
+    }
+    /// Compresses all inactive versions right away if encryption is enabled, e.g. before they
+    /// are moved to the trash.
+    pub fn encrypt_versions(&self) -> Result<()> {
+        if self.encrypt && fs::exists(&self.version_dir)? {
+            for name in self.versions()? {
+                self.compress_version(&name)?;
+            }
+        }
+        Ok(())
+    }
+    /// Key to encrypt with if encryption is enabled, failing if the session is locked.
+    fn key(&self) -> Result<Option<Key>> {
+        Ok(if self.encrypt {
+            Some(crypt::session_key()?)
+        } else {
+            None
+        })
+    }
+    /// Archive with the contents of the specified version if it is compressed.
+    fn archive_of(&self, name: &SlotName) -> Result<Option<PathBuf>> {
+        let Some(dir) = self.version_dir_of(name)? else {
+            return Ok(None);
+        };
+        let archive = dir.join(COMPRESSED_CONTENTS);
+        Ok(fs::exists(&archive)?.then_some(archive))
+    }
+    /// Whether the stored copy of the specified version is compressed.
+    pub fn is_compressed(&self, name: &SlotName) -> Result<bool> {
+        Ok(self.archive_of(name)?.is_some())
+    }
+    /// Unpacks the specified version if it is compressed. Encrypted versions need the key of the
+    /// session.
+    fn decompress_version(&self, name: &SlotName) -> Result<()> {
+        if let Some(dir) = self.version_dir_of(name)? {
+            unpack_dir(&dir)?;
+        }
+        Ok(())
+    }
+    /// Prepares compressing the specified version, which has to be inactive, stored raw, not
+    /// empty and unchanged for `min_idle`. Returns `Ok(None)` otherwise or if compression is
+    /// disabled, and fails if encryption is enabled without a key in the session. The archive is
+    /// written by [`PendingCompression::run`] and replaces the contents in
+    /// [`DirSwapper::finish_compression`].
+    pub fn prepare_compression(
+        &self,
+        name: &SlotName,
+        min_idle: Duration,
+    ) -> Result<Option<PendingCompression>> {
+        if !self.compress || self.active_version() == Some(name) || self.is_compressed(name)? {
+            return Ok(None);
+        }
+        let Some(dir) = self.version_dir_of(name)? else {
+            return Ok(None);
+        };
+        if fs::read_dir(&dir)?.next().is_none() {
+            return Ok(None);
+        }
+        let stamp = utils::dir_stats(&dir)?;
+        if stamp
+            .1
+            .is_some_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed < min_idle))
+        {
+            return Ok(None);
+        }
+        let key = self.key()?;
+        let archive = self
+            .version_dir
+            .join(format!("{}.tar.zst.partial", self.id_of(name)));
+        Ok(Some(PendingCompression {
+            name: name.clone(),
+            dir,
+            archive,
+            stamp,
+            key,
+        }))
+    }
+    /// Replaces the contents of a version with the archive written by
+    /// [`PendingCompression::run`]. Returns `Ok(false)` and discards the archive if the version
+    /// was loaded, changed or removed in the meantime.
+    pub fn finish_compression(&self, pending: &PendingCompression) -> Result<bool> {
+        let unchanged = self.active_version() != Some(&pending.name)
+            && self.version_dir_of(&pending.name)?.as_ref() == Some(&pending.dir)
+            && !self.is_compressed(&pending.name)?
+            && utils::dir_stats(&pending.dir)? == pending.stamp;
+        if !unchanged {
+            pending.discard();
+            return Ok(false);
+        }
+        replace_with_archive(&pending.dir, &pending.archive)?;
+        Ok(true)
+    }
+    /// Compresses the specified version right away, see [`DirSwapper::prepare_compression`].
+    /// Returns `Ok(false)` if it is not compressed.
+    pub fn compress_version(&self, name: &SlotName) -> Result<bool> {
+        let Some(pending) = self.prepare_compression(name, Duration::ZERO)? else {
+            return Ok(false);
+        };
+        if let Err(err) = pending.run() {
+            pending.discard();
+            return Err(err.into());
+        }
+        self.finish_compression(&pending)
+    }
     /// Returns version that is loaded in the primary directory, if any.
     #[inline]
-    pub fn active_version(&self) -> Option<&str> {
-        self.active_version.as_deref()
+    pub fn active_version(&self) -> Option<&SlotName> {
+        self.active_version.as_ref()
+    }
+
+    /// Does not modify old directory.
+    #[inline]
+    pub fn set_primary_dir(&mut self, primary_dir: PathBuf) {
+        self.primary_dir = primary_dir;
+    }
+
+    // Does not modify old directory.
+    #[inline]
+    pub fn set_version_dir(&mut self, version_dir: PathBuf) {
+        self.version_dir = version_dir;
+    }
+
+    /// Registry keys swapped together with the primary directory, if the game stores data in the
+    /// registry.
+    #[inline]
+    pub fn registry(&self) -> Option<&WineRegistryKeys> {
+        self.registry.as_ref()
     }
 
-    /// Does not modify old directory.
+    /// Does not modify the registry or existing registry files.
     #[inline]
-    pub fn set_primary_dir(&mut self, primary_dir: PathBuf) {
-        self.primary_dir = primary_dir;
+    pub fn set_registry(&mut self, registry: Option<WineRegistryKeys>) {
+        self.registry = registry;
     }
+}
 
-    // Does not modify old directory.
-    #[inline]
-    pub fn set_version_dir(&mut self, version_dir: PathBuf) {
-        self.version_dir = version_dir;
+/// Replaces the contents of `dir` with an archive of them encrypted with `key`, like a
+/// compressed version.
+fn pack_dir(dir: &Path, key: &Key) -> io::Result<()> {
+    let mut archive = dir.as_os_str().to_owned();
+    archive.push(".packed.partial");
+    let archive = PathBuf::from(archive);
+    if let Err(err) = compress::compress_dir(dir, &archive, Some(key)) {
+        if let Err(err) = fs::remove_file(&archive) {
+            warn!("failed to remove {}: {err}", archive.display());
+        }
+        return Err(err);
+    }
+    replace_with_archive(dir, &archive)
+}
+
+/// Moves `archive` into `dir` as [`COMPRESSED_CONTENTS`] and removes everything else in it.
+fn replace_with_archive(dir: &Path, archive: &Path) -> io::Result<()> {
+    // The archive is in place before anything is removed, so an interruption only leaves
+    // files that are unpacked again
+    utils::move_path(archive, dir.join(COMPRESSED_CONTENTS))?;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name() == COMPRESSED_CONTENTS {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks the archive in `dir` if its contents are compressed. Encrypted archives need the key
+/// of the session.
+fn unpack_dir(dir: &Path) -> io::Result<()> {
+    let archive = dir.join(COMPRESSED_CONTENTS);
+    if fs::exists(&archive)? {
+        compress::extract(&archive, dir)?;
+        fs::remove_file(&archive)?;
+    }
+    Ok(())
+}
+
+/// Rewrites the file at `path` if it exists, so that it is encrypted with `key` or, without a
+/// key, readable.
+fn convert_file(path: &Path, key: Option<&Key>) -> io::Result<()> {
+    if fs::exists(path)? && crypt::is_encrypted(path)? != key.is_some() {
+        let data = crypt::read_file(path)?;
+        crypt::write_file(path, &data, key)?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -182,6 +1374,10 @@ mod tests {
         tempfile::tempdir().expect("failed to create temporary test directory")
     }
 
+    fn name(name: &str) -> SlotName {
+        name.parse().unwrap()
+    }
+
     /// Set by create swapper as active name by default.
     const DEFAULT_NAME: &str = "Example1";
     /// Creates a new swapper with the provided paths or temporary directories, and a primary name
@@ -198,7 +1394,7 @@ mod tests {
             DirSwapper::build(
                 primary_dir.path().to_path_buf(),
                 version_dir.path().to_path_buf(),
-                DEFAULT_NAME.to_string(),
+                name(DEFAULT_NAME),
             )
             .unwrap(),
             primary_dir,
@@ -236,7 +1432,7 @@ mod tests {
             }
         }
     }
-    impl<'a> From<ReadDir> for FileTree {
+    impl From<ReadDir> for FileTree {
         fn from(value: ReadDir) -> Self {
             Self::from_iter(value)
         }
@@ -380,11 +1576,11 @@ mod tests {
         let (swapper, _temp_dir1, _temp_dir2) = new_swapper(None, Some(temp_dir));
 
         assert_eq!(
-            FileTree::from_path(swapper.version_dir_of("Example2").unwrap().unwrap()),
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
             *DUMMY_FILE_TREE_1,
         );
         assert_eq!(
-            FileTree::from_path(swapper.version_dir_of("Example3").unwrap().unwrap()),
+            FileTree::from_path(swapper.version_dir_of(&name("Example3")).unwrap().unwrap()),
             *DUMMY_FILE_TREE_1,
         );
     }
@@ -395,35 +1591,640 @@ mod tests {
         build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
 
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(Some(primary_dir), None);
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
+        build_file_tree(
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
+            &DUMMY_FILE_TREE_2,
+        );
+
+        swapper.set_active(name("Example2")).unwrap();
+        assert_eq!(
+            FileTree::from_path(swapper.primary_dir()),
+            *DUMMY_FILE_TREE_2
+        );
+    }
+
+    #[test]
+    fn snapshot_copies_primary_dir_into_active_version() {
+        let (swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+
+        swapper.snapshot().unwrap().unwrap();
+        assert_eq!(
+            FileTree::from_path(
+                swapper
+                    .version_dir_of(&name(DEFAULT_NAME))
+                    .unwrap()
+                    .unwrap()
+            ),
+            *DUMMY_FILE_TREE_1
+        );
+        assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_1);
+    }
+
+    #[test]
+    fn history_records_and_restores_snapshots() {
+        let (swapper, primary_dir, version_dir) = new_swapper(None, None);
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let first = swapper.record_history(2).unwrap().unwrap();
+        utils::remove_dir_contents(&primary_dir).unwrap();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_2);
+        let second = swapper.record_history(2).unwrap().unwrap();
+        let third = swapper.record_history(2).unwrap().unwrap();
+
+        // The oldest snapshot is pruned and the history is not listed as a version
+        assert_eq!(
+            swapper.history(&name(DEFAULT_NAME)).unwrap(),
+            [second.clone(), third]
+        );
+        assert_eq!(swapper.versions().unwrap(), [name(DEFAULT_NAME)]);
+        assert!(
+            swapper
+                .restore_history(&name(DEFAULT_NAME), &first)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            swapper
+                .restore_history(&name(DEFAULT_NAME), "../..")
+                .unwrap()
+                .is_none()
+        );
+
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        swapper
+            .restore_history(&name(DEFAULT_NAME), &second)
+            .unwrap()
+            .unwrap();
+        assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_2);
+        assert!(version_dir.path().join(HISTORY_DIR).exists());
+    }
+
+    #[test]
+    fn usage_counts_versions_and_snapshots() {
+        let (swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        fs::write(primary_dir.path().join("save.dat"), [0; 100]).unwrap();
+        let first = swapper.record_history(5).unwrap().unwrap();
+        let second = swapper.record_history(5).unwrap().unwrap();
+        swapper.snapshot().unwrap();
+
+        let usage = swapper.usage(&name(DEFAULT_NAME)).unwrap();
+        assert_eq!(usage.size, 100);
+
+        // Unchanged files are hard linked to the previous snapshot
+        fs::write(primary_dir.path().join("other.dat"), [0; 30]).unwrap();
+        let third = swapper.record_history(5).unwrap().unwrap();
+        let usage = swapper.usage(&name(DEFAULT_NAME)).unwrap();
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                usage.snapshots,
+                [(first.clone(), 0), (second.clone(), 0), (third, 30)]
+            );
+            assert_eq!(usage.shared_history, 100);
+            assert_eq!(usage.history_size(), 130);
+        }
+        #[cfg(not(unix))]
+        assert_eq!(usage.history_size(), 330);
+
+        swapper
+            .remove_snapshot(&name(DEFAULT_NAME), &first)
+            .unwrap()
+            .unwrap();
+        assert!(
+            swapper
+                .remove_snapshot(&name(DEFAULT_NAME), "..")
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(swapper.history(&name(DEFAULT_NAME)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn diff_compares_version_with_primary_dir() {
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        swapper.add_version(&name("Example2")).unwrap();
+
+        let diffs = swapper
+            .diff(DiffSide::Primary, DiffSide::Version(&name("Example2")))
+            .unwrap()
+            .unwrap();
+        assert!(!diffs.is_empty());
+        assert!(
+            diffs
+                .iter()
+                .all(|diff| diff.change == diff::Change::Removed)
+        );
+        // The active version is compared by its live contents
+        let diffs = swapper
+            .diff(DiffSide::Primary, DiffSide::Version(&name(DEFAULT_NAME)))
+            .unwrap()
+            .unwrap();
+        assert!(diffs.is_empty());
+        assert!(
+            swapper
+                .diff(DiffSide::Primary, DiffSide::Version(&name("Missing")))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn duplicate_copies_current_contents() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(Some(primary_dir), None);
+        swapper.add_version(&name("Example2")).unwrap();
+        build_file_tree(
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
+            &DUMMY_FILE_TREE_2,
+        );
+
+        // The active version is copied from the primary directory
+        swapper
+            .duplicate_version(&name(DEFAULT_NAME), &name("Fork1"))
+            .unwrap()
+            .unwrap();
+        swapper
+            .duplicate_version(&name("Example2"), &name("Fork2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            FileTree::from_path(swapper.version_dir_of(&name("Fork1")).unwrap().unwrap()),
+            *DUMMY_FILE_TREE_1
+        );
+        assert_eq!(
+            FileTree::from_path(swapper.version_dir_of(&name("Fork2")).unwrap().unwrap()),
+            *DUMMY_FILE_TREE_2
+        );
+        assert_eq!(swapper.active_version(), Some(&name(DEFAULT_NAME)));
+        assert!(
+            swapper
+                .duplicate_version(&name("Missing"), &name("Fork3"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            swapper
+                .duplicate_version(&name("Example2"), &name("Fork1"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn add_version_from_primary_copies_primary_dir() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(Some(primary_dir), None);
+        swapper.delete_version(&name(DEFAULT_NAME)).unwrap();
+
+        swapper
+            .add_version_from_primary(&name("Example2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
+            *DUMMY_FILE_TREE_1
+        );
+        assert!(
+            swapper
+                .add_version_from_primary(&name("Example2"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn missing_primary_dir_is_created_on_swap() {
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        swapper.add_version(&name("Example2")).unwrap();
+        build_file_tree(
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
+            &DUMMY_FILE_TREE_1,
+        );
+        fs::remove_dir(&primary_dir).unwrap();
+        assert_eq!(swapper.primary_state().unwrap(), PrimaryState::NotCreated);
+
+        swapper.snapshot().unwrap().unwrap();
+        swapper.set_active(name("Example2")).unwrap().unwrap();
+        assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_1);
+        // The version was moved into the new primary directory
+        assert_eq!(
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
+            FileTree::default()
+        );
+    }
+
+    #[test]
+    fn ensure_primary_dir_loads_active_version() {
+        let (swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        fs::remove_dir(&primary_dir).unwrap();
+
+        swapper.ensure_primary_dir().unwrap();
+        assert_eq!(FileTree::from_path(&primary_dir), FileTree::default());
+        assert_eq!(swapper.primary_state().unwrap(), PrimaryState::Present);
+    }
+
+    #[test]
+    fn moved_primary_dir_keeps_versions() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(Some(primary_dir), None);
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_2,
         );
+        swapper.set_active(name("Example2")).unwrap().unwrap();
+        let new_parent = new_temp_dir();
+        let new_primary_dir = new_parent.path().join("saves");
+        fs::rename(&primary_dir, &new_primary_dir).unwrap();
+        assert_eq!(swapper.primary_state().unwrap(), PrimaryState::Moved);
+
+        let err = swapper.set_active(name(DEFAULT_NAME)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PrimaryDirError>(),
+            Some(PrimaryDirError::Moved(_))
+        ));
+        assert!(swapper.snapshot().is_err());
+        assert!(swapper.ensure_primary_dir().is_err());
+        assert_eq!(swapper.active_version(), Some(&name("Example2")));
+        assert_eq!(
+            FileTree::from_path(
+                swapper
+                    .version_dir_of(&name(DEFAULT_NAME))
+                    .unwrap()
+                    .unwrap()
+            ),
+            *DUMMY_FILE_TREE_1
+        );
 
-        swapper.set_active("Example2".to_string()).unwrap();
+        // Relocating the game makes the versions usable again
+        swapper.set_primary_dir(new_primary_dir.clone());
+        swapper.set_active(name(DEFAULT_NAME)).unwrap().unwrap();
+        assert_eq!(FileTree::from_path(&new_primary_dir), *DUMMY_FILE_TREE_1);
+        assert_eq!(
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
+            *DUMMY_FILE_TREE_2
+        );
+    }
+
+    #[test]
+    fn swap_moves_dirs_on_the_same_filesystem() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(Some(primary_dir), None);
+        swapper.add_version(&name("Example2")).unwrap();
+        let example2_dir = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        build_file_tree(&example2_dir, &DUMMY_FILE_TREE_2);
+        let saved_file = example2_dir.join("Cargo.toml");
+        fs::write(&saved_file, "moved, not copied").unwrap();
+
+        swapper.set_active(name("Example2")).unwrap().unwrap();
+        assert!(swapper.active_moved);
+        assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_2);
+        // The placeholder keeps the version listed
+        assert_eq!(FileTree::from_path(&example2_dir), FileTree::default());
+        assert_eq!(
+            swapper.versions().unwrap(),
+            [name(DEFAULT_NAME), name("Example2")]
+        );
+        // Missing saves are not mistaken for a game that was never launched
+        fs::rename(&primary_dir, primary_dir.path().with_extension("moved")).unwrap();
+        assert_eq!(swapper.primary_state().unwrap(), PrimaryState::Moved);
+        fs::rename(primary_dir.path().with_extension("moved"), &primary_dir).unwrap();
+
+        swapper.set_active(name(DEFAULT_NAME)).unwrap().unwrap();
+        assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_1);
+        assert_eq!(
+            fs::read_to_string(&saved_file).unwrap(),
+            "moved, not copied"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_primary_dir_is_copied() {
+        let real_dir = new_temp_dir();
+        build_file_tree(&real_dir, &DUMMY_FILE_TREE_1);
+        let link_parent = new_temp_dir();
+        let link = link_parent.path().join("saves");
+        std::os::unix::fs::symlink(real_dir.path(), &link).unwrap();
+        let version_dir = new_temp_dir();
+        let mut swapper = DirSwapper::build(
+            link.clone(),
+            version_dir.path().to_path_buf(),
+            name(DEFAULT_NAME),
+        )
+        .unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
+
+        swapper.set_active(name("Example2")).unwrap().unwrap();
+        assert!(!swapper.active_moved);
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(FileTree::from_path(&real_dir), FileTree::default());
+        assert_eq!(
+            FileTree::from_path(
+                swapper
+                    .version_dir_of(&name(DEFAULT_NAME))
+                    .unwrap()
+                    .unwrap()
+            ),
+            *DUMMY_FILE_TREE_1
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_mode_swaps_links() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(Some(primary_dir), None);
+        swapper.add_version(&name("Example2")).unwrap();
+        let example1_dir = swapper
+            .version_dir_of(&name(DEFAULT_NAME))
+            .unwrap()
+            .unwrap();
+        let example2_dir = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        build_file_tree(&example2_dir, &DUMMY_FILE_TREE_2);
+
+        // The primary directory becomes the contents of the active version
+        swapper.set_mode(SwapMode::Symlink).unwrap();
+        assert_eq!(swapper.link_state().unwrap(), LinkState::Linked);
+        assert_eq!(FileTree::from_path(&example1_dir), *DUMMY_FILE_TREE_1);
+
+        swapper.set_active(name("Example2")).unwrap().unwrap();
+        assert_eq!(fs::read_link(&primary_dir).unwrap(), example2_dir);
+        fs::write(primary_dir.path().join("new.sav"), "written by the game").unwrap();
+        swapper.snapshot().unwrap().unwrap();
+        assert!(example2_dir.join("new.sav").exists());
+
+        swapper.set_mode(SwapMode::Copy).unwrap();
+        assert!(!fs::symlink_metadata(&primary_dir).unwrap().is_symlink());
+        assert!(primary_dir.path().join("new.sav").exists());
+        assert!(example2_dir.join("new.sav").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_mode_repairs_links() {
+        let (mut swapper, primary_dir, _version_dir) = new_swapper(None, None);
+        swapper.set_mode(SwapMode::Symlink).unwrap();
+        let version_dir = swapper
+            .version_dir_of(&name(DEFAULT_NAME))
+            .unwrap()
+            .unwrap();
+
+        let foreign_dir = new_temp_dir();
+        build_file_tree(&foreign_dir, &DUMMY_FILE_TREE_1);
+        link::replace_link(primary_dir.path(), foreign_dir.path()).unwrap();
+        assert_eq!(
+            swapper.repair_link().unwrap(),
+            LinkState::Foreign(foreign_dir.path().to_path_buf())
+        );
+        assert_eq!(swapper.link_state().unwrap(), LinkState::Linked);
+        assert_eq!(FileTree::from_path(&foreign_dir), *DUMMY_FILE_TREE_1);
+
+        let missing = foreign_dir.path().join("missing");
+        link::replace_link(primary_dir.path(), &missing).unwrap();
+        assert_eq!(swapper.repair_link().unwrap(), LinkState::Broken(missing));
+        assert_eq!(swapper.link_state().unwrap(), LinkState::Linked);
+
+        // A directory created by the game has the latest saves
+        link::remove_link(primary_dir.path()).unwrap();
+        fs::create_dir(&primary_dir).unwrap();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_2);
+        assert_eq!(swapper.repair_link().unwrap(), LinkState::Directory);
+        assert_eq!(swapper.link_state().unwrap(), LinkState::Linked);
+        assert_eq!(FileTree::from_path(&version_dir), *DUMMY_FILE_TREE_2);
+    }
+
+    #[test]
+    fn compressed_versions_are_unpacked_on_swap() {
+        let primary_dir = new_temp_dir();
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(Some(primary_dir), None);
+        swapper.add_version(&name("Example2")).unwrap();
+        let example2 = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        build_file_tree(&example2, &DUMMY_FILE_TREE_2);
+
+        swapper.set_compression(true).unwrap();
+        assert!(!swapper.compress_version(&name(DEFAULT_NAME)).unwrap());
+        assert!(swapper.compress_version(&name("Example2")).unwrap());
+        assert!(swapper.is_compressed(&name("Example2")).unwrap());
+        assert_eq!(
+            FileTree::from_path(&example2),
+            FileTree(HashSet::from([Node::File(COMPRESSED_CONTENTS.into())]))
+        );
+
+        swapper.set_active(name("Example2")).unwrap();
+        assert!(!swapper.is_compressed(&name("Example2")).unwrap());
         assert_eq!(
             FileTree::from_path(swapper.primary_dir()),
             *DUMMY_FILE_TREE_2
         );
+
+        // Versions modified while their archive is written stay raw
+        let pending = swapper
+            .prepare_compression(&name(DEFAULT_NAME), Duration::ZERO)
+            .unwrap()
+            .unwrap();
+        pending.run().unwrap();
+        fs::write(pending.dir().join("file1.txt"), "changed").unwrap();
+        assert!(!swapper.finish_compression(&pending).unwrap());
+        assert!(!fs::exists(&pending.archive).unwrap());
+
+        assert!(swapper.compress_version(&name(DEFAULT_NAME)).unwrap());
+        swapper.set_compression(false).unwrap();
+        let example1 = swapper
+            .version_dir_of(&name(DEFAULT_NAME))
+            .unwrap()
+            .unwrap();
+        assert_eq!(FileTree::from_path(example1), *DUMMY_FILE_TREE_1);
+    }
+
+    #[test]
+    fn reading_compressed_versions_keeps_the_archive() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        swapper.add_version(&name("Example2")).unwrap();
+        let example2 = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        build_file_tree(&example2, &DUMMY_FILE_TREE_2);
+        swapper.set_compression(true).unwrap();
+        assert!(swapper.compress_version(&name("Example2")).unwrap());
+
+        let contents = swapper.contents_dir_of(&name("Example2")).unwrap().unwrap();
+        let extracted = contents.to_path_buf();
+        assert_ne!(extracted, example2);
+        assert_eq!(FileTree::from_path(&extracted), *DUMMY_FILE_TREE_2);
+        assert!(swapper.is_compressed(&name("Example2")).unwrap());
+        drop(contents);
+        assert!(!extracted.exists());
+
+        assert!(
+            !swapper
+                .diff(DiffSide::Primary, DiffSide::Version(&name("Example2")))
+                .unwrap()
+                .unwrap()
+                .is_empty()
+        );
+        swapper
+            .duplicate_version(&name("Example2"), &name("Example3"))
+            .unwrap()
+            .unwrap();
+        let example3 = swapper.version_dir_of(&name("Example3")).unwrap().unwrap();
+        assert_eq!(FileTree::from_path(example3), *DUMMY_FILE_TREE_2);
+        assert!(swapper.is_compressed(&name("Example2")).unwrap());
+    }
+
+    #[test]
+    fn encrypted_versions_need_the_session_key() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        for version in ["Example2", "Example3"] {
+            swapper.add_version(&name(version)).unwrap();
+            let dir = swapper.version_dir_of(&name(version)).unwrap().unwrap();
+            build_file_tree(&dir, &DUMMY_FILE_TREE_2);
+        }
+        let (_, key) = crypt::EncryptionConfig::new("hunter2");
+
+        assert!(crypt::with_session_key(None, || swapper.set_encryption(true)).is_err());
+        assert!(!swapper.encryption());
+
+        crypt::with_session_key(Some(key.clone()), || {
+            swapper.set_encryption(true).unwrap();
+            assert!(swapper.compression());
+            let pending = swapper
+                .prepare_compression(&name("Example2"), Duration::ZERO)
+                .unwrap()
+                .unwrap();
+            assert!(pending.key.is_some());
+            pending.run().unwrap();
+            assert!(swapper.finish_compression(&pending).unwrap());
+            let archive = swapper.archive_of(&name("Example2")).unwrap().unwrap();
+            assert!(crypt::is_encrypted(archive).unwrap());
+        });
+
+        crypt::with_session_key(None, || {
+            assert!(
+                swapper
+                    .prepare_compression(&name("Example3"), Duration::ZERO)
+                    .is_err()
+            );
+            assert!(swapper.set_active(name("Example2")).is_err());
+        });
+        assert_eq!(swapper.active_version(), Some(&name(DEFAULT_NAME)));
+
+        // Disabling encryption unpacks the encrypted archives
+        crypt::with_session_key(Some(key), || swapper.set_encryption(false)).unwrap();
+        assert!(!swapper.is_compressed(&name("Example2")).unwrap());
+        let example2 = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        assert_eq!(FileTree::from_path(example2), *DUMMY_FILE_TREE_2);
+    }
+
+    #[test]
+    fn encryption_covers_snapshots_unloaded_and_trashed_versions() {
+        let (mut swapper, primary_dir, version_dir) = new_swapper(None, None);
+        let (trash_dir, prefix) = (new_temp_dir(), new_temp_dir());
+        swapper.set_registry(Some(WineRegistryKeys::new(
+            prefix.path().to_path_buf(),
+            Vec::new(),
+        )));
+        build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
+        for version in ["Example2", "Example3"] {
+            swapper.add_version(&name(version)).unwrap();
+            let dir = swapper.version_dir_of(&name(version)).unwrap().unwrap();
+            build_file_tree(&dir, &DUMMY_FILE_TREE_2);
+        }
+        let (_, key) = crypt::EncryptionConfig::new("hunter2");
+
+        crypt::with_session_key(Some(key), || {
+            swapper.set_encryption(true).unwrap();
+            let snapshot = swapper.record_history(5).unwrap().unwrap();
+            let snapshot_dir = version_dir
+                .path()
+                .join(HISTORY_DIR)
+                .join(swapper.id_of(&name(DEFAULT_NAME)))
+                .join(&snapshot);
+            let archive = snapshot_dir.join(COMPRESSED_CONTENTS);
+            assert_eq!(fs::read_dir(&snapshot_dir).unwrap().count(), 1);
+            assert!(crypt::is_encrypted(&archive).unwrap());
+            assert!(crypt::is_encrypted(snapshot_dir.with_extension("reg")).unwrap());
+
+            // The unloaded version is encrypted right away
+            swapper.set_active(name("Example2")).unwrap().unwrap();
+            let archive = swapper.archive_of(&name(DEFAULT_NAME)).unwrap().unwrap();
+            assert!(crypt::is_encrypted(archive).unwrap());
+            let registry_file = swapper.build_registry_file(&name(DEFAULT_NAME));
+            assert!(crypt::is_encrypted(&registry_file).unwrap());
+            assert!(swapper.registry_of(&name(DEFAULT_NAME)).unwrap().is_some());
+
+            swapper
+                .restore_history(&name(DEFAULT_NAME), &snapshot)
+                .unwrap()
+                .unwrap();
+            assert!(swapper.is_compressed(&name(DEFAULT_NAME)).unwrap());
+            let contents = swapper
+                .contents_dir_of(&name(DEFAULT_NAME))
+                .unwrap()
+                .unwrap();
+            assert_eq!(FileTree::from_path(&contents), *DUMMY_FILE_TREE_1);
+
+            let trashed = swapper
+                .trash_version(&name("Example3"), trash_dir.path())
+                .unwrap()
+                .unwrap();
+            let archive = trash_dir
+                .path()
+                .join(TRASHED_VERSION)
+                .join(COMPRESSED_CONTENTS);
+            assert!(crypt::is_encrypted(archive).unwrap());
+            swapper
+                .restore_version(&name("Example3"), &trashed, trash_dir.path())
+                .unwrap()
+                .unwrap();
+            swapper.set_active(name("Example3")).unwrap().unwrap();
+            assert_eq!(FileTree::from_path(&primary_dir), *DUMMY_FILE_TREE_2);
+
+            // Disabling encryption makes everything readable again
+            swapper.set_encryption(false).unwrap();
+            assert!(!crypt::is_encrypted(&registry_file).unwrap());
+            assert!(!fs::exists(snapshot_dir.join(COMPRESSED_CONTENTS)).unwrap());
+            assert_eq!(FileTree::from_path(&snapshot_dir), *DUMMY_FILE_TREE_1);
+        });
+    }
+
+    #[test]
+    fn missing_active_version_dir_is_an_error() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        swapper.add_version(&name("Example2")).unwrap();
+        let active_dir = swapper
+            .version_dir_of(&name(DEFAULT_NAME))
+            .unwrap()
+            .unwrap();
+        fs::remove_dir_all(active_dir).unwrap();
+
+        assert!(swapper.snapshot().is_err());
+        assert!(swapper.set_active(name("Example2")).is_err());
+        assert_eq!(swapper.active_version(), Some(&name(DEFAULT_NAME)));
     }
 
     #[test]
     fn swap_updates_version_identifier() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
 
-        swapper.set_active("Example2".to_string()).unwrap();
+        swapper.set_active(name("Example2")).unwrap();
 
-        assert_eq!(swapper.active_version(), Some("Example2"));
+        assert_eq!(swapper.active_version(), Some(&name("Example2")));
     }
 
     #[test]
     fn non_existent_name_is_invalid() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        assert!(swapper.set_active("Invalid".to_string()).unwrap().is_none())
+        assert!(swapper.set_active(name("Invalid")).unwrap().is_none())
     }
 
     #[test]
@@ -432,14 +2233,14 @@ mod tests {
         build_file_tree(&primary_dir, &DUMMY_FILE_TREE_1);
 
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(Some(primary_dir), None);
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_2,
         );
 
-        swapper.set_active("Example2".to_string()).unwrap();
-        swapper.set_active(DEFAULT_NAME.to_string()).unwrap();
+        swapper.set_active(name("Example2")).unwrap();
+        swapper.set_active(name(DEFAULT_NAME)).unwrap();
 
         assert_eq!(
             FileTree::from_path(swapper.primary_dir()),
@@ -451,22 +2252,22 @@ mod tests {
     fn double_swap_restores_orginal_version_identifier() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
 
-        swapper.set_active("Example2".to_string()).unwrap();
-        swapper.set_active(DEFAULT_NAME.to_string()).unwrap();
+        swapper.set_active(name("Example2")).unwrap();
+        swapper.set_active(name(DEFAULT_NAME)).unwrap();
 
-        assert_eq!(swapper.active_version(), Some(DEFAULT_NAME));
+        assert_eq!(swapper.active_version(), Some(&name(DEFAULT_NAME)));
     }
 
     #[test]
     fn add_version_creates_an_empty_dir() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
 
         assert_eq!(
-            FileTree::from_path(swapper.version_dir_of("Example2").unwrap().unwrap()),
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
             FileTree::default(),
         );
     }
@@ -475,22 +2276,22 @@ mod tests {
     fn swap_replaces_version_dir_contents() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_1,
         );
 
-        swapper.set_active("Example2".to_string()).unwrap();
+        swapper.set_active(name("Example2")).unwrap();
 
         fs::remove_dir_all(swapper.primary_dir()).unwrap();
         fs::create_dir(swapper.primary_dir()).unwrap();
         build_file_tree(swapper.primary_dir(), &DUMMY_FILE_TREE_2);
 
-        swapper.set_active(DEFAULT_NAME.to_string()).unwrap();
+        swapper.set_active(name(DEFAULT_NAME)).unwrap();
 
         assert_eq!(
-            FileTree::from_path(swapper.version_dir_of("Example2").unwrap().unwrap()),
+            FileTree::from_path(swapper.version_dir_of(&name("Example2")).unwrap().unwrap()),
             *DUMMY_FILE_TREE_2
         );
     }
@@ -499,14 +2300,14 @@ mod tests {
     fn delete_version_removes_version_dir() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_1,
         );
 
-        let version_dir = swapper.version_dir_of("Example2").unwrap().unwrap();
-        swapper.delete_version("Example2").unwrap();
+        let version_dir = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        swapper.delete_version(&name("Example2")).unwrap();
 
         assert!(!fs::exists(version_dir).unwrap());
     }
@@ -515,14 +2316,14 @@ mod tests {
     fn deleted_version_is_deactivated() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_1,
         );
 
-        swapper.set_active("Example2".to_string()).unwrap();
-        swapper.delete_version("Example2").unwrap();
+        swapper.set_active(name("Example2")).unwrap();
+        swapper.delete_version(&name("Example2")).unwrap();
 
         assert!(swapper.active_version().is_none());
     }
@@ -531,27 +2332,99 @@ mod tests {
     fn delete_version_does_not_delete_primary_dir() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
 
-        swapper.add_version("Example2").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
         build_file_tree(
-            swapper.version_dir_of("Example2").unwrap().unwrap(),
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
             &DUMMY_FILE_TREE_1,
         );
 
-        swapper.delete_version("Example2").unwrap();
+        swapper.delete_version(&name("Example2")).unwrap();
 
         assert!(fs::exists(swapper.primary_dir()).unwrap());
     }
 
+    #[test]
+    fn trashed_version_can_be_restored() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        let (trash_dir, prefix) = (new_temp_dir(), new_temp_dir());
+        swapper.set_registry(Some(WineRegistryKeys::new(
+            prefix.path().to_path_buf(),
+            Vec::new(),
+        )));
+        swapper.add_version(&name("Example2")).unwrap();
+        let version_dir = swapper.version_dir_of(&name("Example2")).unwrap().unwrap();
+        build_file_tree(&version_dir, &DUMMY_FILE_TREE_1);
+        fs::write(swapper.build_registry_file(&name("Example2")), "REGEDIT4").unwrap();
+
+        let trashed = swapper
+            .trash_version(&name("Example2"), trash_dir.path())
+            .unwrap()
+            .unwrap();
+        assert!(!trashed.active);
+        assert!(!fs::exists(&version_dir).unwrap());
+        assert_eq!(swapper.versions().unwrap(), [name(DEFAULT_NAME)]);
+
+        swapper
+            .restore_version(&name("Example2"), &trashed, trash_dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            swapper.version_dir_of(&name("Example2")).unwrap().unwrap(),
+            version_dir
+        );
+        assert_eq!(FileTree::from_path(&version_dir), *DUMMY_FILE_TREE_1);
+        assert_eq!(
+            swapper.registry_of(&name("Example2")).unwrap().as_deref(),
+            Some("REGEDIT4")
+        );
+    }
+
+    #[test]
+    fn trashed_active_version_keeps_primary_dir() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        let trash_dir = new_temp_dir();
+        build_file_tree(swapper.primary_dir(), &DUMMY_FILE_TREE_1);
+
+        let trashed = swapper
+            .trash_version(&name(DEFAULT_NAME), trash_dir.path())
+            .unwrap()
+            .unwrap();
+        assert!(trashed.active);
+        assert!(swapper.active_version().is_none());
+        assert_eq!(
+            FileTree::from_path(trash_dir.path().join(TRASHED_VERSION)),
+            *DUMMY_FILE_TREE_1
+        );
+        assert_eq!(
+            FileTree::from_path(swapper.primary_dir()),
+            *DUMMY_FILE_TREE_1
+        );
+
+        // Another version took the name in the meantime
+        swapper.add_version(&name(DEFAULT_NAME)).unwrap();
+        assert!(
+            swapper
+                .restore_version(&name(DEFAULT_NAME), &trashed, trash_dir.path())
+                .unwrap()
+                .is_none()
+        );
+        swapper
+            .restore_version(&name("Restored"), &trashed, trash_dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(swapper.active_version(), Some(&name("Restored")));
+    }
+
     #[test]
     fn list_versions_finds_newly_added() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
-        swapper.add_version("Example2").unwrap();
-        swapper.add_version("Example3").unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
+        swapper.add_version(&name("Example3")).unwrap();
 
         let versions = swapper.versions().unwrap();
-        assert!(versions.contains(&"Example1".to_string()));
-        assert!(versions.contains(&"Example1".to_string()));
-        assert!(versions.contains(&"Example3".to_string()));
+        assert!(versions.contains(&name("Example1")));
+        assert!(versions.contains(&name("Example1")));
+        assert!(versions.contains(&name("Example3")));
     }
 
     #[test]
@@ -565,16 +2438,16 @@ mod tests {
         let (swapper, _temp_dir1, _temp_dir2) = new_swapper(None, Some(temp_dir));
 
         let versions = swapper.versions().unwrap();
-        assert!(versions.contains(&"Example3".to_string()));
-        assert!(versions.contains(&"Example4".to_string()));
+        assert!(versions.contains(&name("Example3")));
+        assert!(versions.contains(&name("Example4")));
     }
 
     #[test]
     fn list_versions_returns_sorted() {
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
-        swapper.add_version("Example1").unwrap();
-        swapper.add_version("Example2").unwrap();
-        swapper.add_version("Example3").unwrap();
+        swapper.add_version(&name("Example1")).unwrap();
+        swapper.add_version(&name("Example2")).unwrap();
+        swapper.add_version(&name("Example3")).unwrap();
 
         let versions = {
             let mut versions = swapper.versions().unwrap();
@@ -585,26 +2458,112 @@ mod tests {
     }
 
     #[test]
-    fn rename_version_changes_dir_name() {
+    fn swap_exports_and_imports_registry_keys() {
+        let prefix = new_temp_dir();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/registry");
+        for file in ["user.reg", "system.reg"] {
+            fs::copy(fixtures.join(file), prefix.path().join(file)).unwrap();
+        }
         let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        let registry = WineRegistryKeys::new(
+            prefix.path().to_path_buf(),
+            vec![
+                r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"
+                    .parse()
+                    .unwrap(),
+            ],
+        );
+        swapper.set_registry(Some(registry.clone()));
+        let original = registry.export().unwrap();
+
+        swapper.add_version(&name("Example2")).unwrap();
+        swapper.set_active(name("Example2")).unwrap();
+        // The new version has no registry file, so its keys start out empty
+        assert_ne!(registry.export().unwrap(), original);
+        fs::copy(
+            fixtures.join("my_summer_car.reg"),
+            prefix.path().join("new.reg"),
+        )
+        .unwrap();
+        registry.import_from(prefix.path().join("new.reg")).unwrap();
+
+        swapper.set_active(name(DEFAULT_NAME)).unwrap();
+        assert_eq!(registry.export().unwrap(), original);
+        swapper.set_active(name("Example2")).unwrap();
         assert_eq!(
-            swapper
-                .version_dir_of("Example1")
-                .unwrap()
-                .unwrap()
-                .file_name()
-                .unwrap(),
-            OsString::from("Example1")
+            registry.export().unwrap(),
+            fs::read_to_string(fixtures.join("my_summer_car.reg")).unwrap()
+        );
+
+        // The registry file is named after the directory, which renaming does not change
+        let registry_file = swapper.build_registry_file(&name(DEFAULT_NAME));
+        assert!(fs::exists(&registry_file).unwrap());
+        swapper
+            .rename_version(&name(DEFAULT_NAME), &name("Example3"))
+            .unwrap();
+        assert_eq!(
+            swapper.build_registry_file(&name("Example3")),
+            registry_file
         );
-        swapper.rename_version("Example1", "Example2").unwrap();
+        swapper.delete_version(&name("Example3")).unwrap();
+        assert!(!fs::exists(registry_file).unwrap());
+    }
+
+    #[test]
+    fn rename_version_keeps_dir_and_active_version() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        let dir = swapper.version_dir_of(&name("Example1")).unwrap().unwrap();
+        assert_ne!(dir.file_name().unwrap(), OsString::from("Example1"));
+
+        swapper
+            .rename_version(&name("Example1"), &name("Example2"))
+            .unwrap()
+            .unwrap();
         assert_eq!(
+            swapper.version_dir_of(&name("Example2")).unwrap(),
+            Some(dir)
+        );
+        assert!(swapper.version_dir_of(&name("Example1")).unwrap().is_none());
+        assert_eq!(swapper.active_version(), Some(&name("Example2")));
+        assert_eq!(swapper.versions().unwrap(), [name("Example2")]);
+    }
+
+    #[test]
+    fn rename_version_rejects_existing_names() {
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, None);
+        swapper.add_version(&name("Example2")).unwrap();
+
+        assert!(
             swapper
-                .version_dir_of("Example2")
+                .rename_version(&name("Example1"), &name("Example2"))
                 .unwrap()
+                .is_none()
+        );
+        assert!(
+            swapper
+                .rename_version(&name("Missing"), &name("Example3"))
                 .unwrap()
-                .file_name()
-                .unwrap(),
-            OsString::from("Example2")
+                .is_none()
+        );
+        assert_eq!(swapper.active_version(), Some(&name("Example1")));
+    }
+
+    #[test]
+    fn legacy_version_dirs_can_be_renamed() {
+        let temp_dir = new_temp_dir();
+        fs::create_dir(temp_dir.path().join("Example2")).unwrap();
+        let (mut swapper, _temp_dir1, _temp_dir2) = new_swapper(None, Some(temp_dir));
+
+        swapper
+            .rename_version(&name("Example2"), &name("Example3"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            swapper.versions().unwrap(),
+            [name("Example1"), name("Example3")]
         );
+        // The old directory is now the id of the renamed version, not a version of its own
+        assert!(swapper.version_dir_of(&name("Example2")).unwrap().is_none());
+        assert!(swapper.add_version(&name("Example2")).unwrap().is_some());
     }
 }
@@ -0,0 +1,111 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// What is at the primary directory in symlink mode, see [`super::DirSwapper::link_state`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkState {
+    /// A link to the directory of the active version
+    Linked,
+    /// Nothing, e.g. the game was never launched
+    Missing,
+    /// A link to a directory that does not exist
+    Broken(PathBuf),
+    /// A link to a directory that is not the active version
+    Foreign(PathBuf),
+    /// A real directory, e.g. the game replaced the link or the mode was just changed
+    Directory,
+}
+
+impl LinkState {
+    /// Inspects `path`, which should be a link to `expected`.
+    pub fn read(path: &Path, expected: Option<&Path>) -> io::Result<Self> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::Missing),
+            Err(err) => return Err(err),
+        };
+        if !metadata.is_symlink() {
+            return if metadata.is_dir() {
+                Ok(Self::Directory)
+            } else {
+                Err(io::Error::other(format!(
+                    "{} is not a directory",
+                    path.display()
+                )))
+            };
+        }
+        let target = fs::read_link(path)?;
+        // Relative links are relative to the directory containing them
+        let target = path
+            .parent()
+            .map_or(target.clone(), |parent| parent.join(target));
+        match fs::canonicalize(path) {
+            Ok(resolved) => Ok(
+                if expected.is_some_and(|expected| {
+                    fs::canonicalize(expected).is_ok_and(|expected| expected == resolved)
+                }) {
+                    Self::Linked
+                } else {
+                    Self::Foreign(target)
+                },
+            ),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::Broken(target)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linked => write!(f, "linked to the loaded slot"),
+            Self::Missing => write!(f, "missing"),
+            Self::Broken(target) => write!(f, "broken link to {}", target.display()),
+            Self::Foreign(target) => write!(f, "link to {}", target.display()),
+            Self::Directory => write!(f, "directory"),
+        }
+    }
+}
+
+/// Points `link` at `target`, replacing an existing link in a single step on Unix so that the
+/// game never sees a missing directory.
+#[cfg(unix)]
+pub fn replace_link(link: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temporary = link.as_os_str().to_owned();
+    temporary.push(".vittusave-link");
+    let temporary = PathBuf::from(temporary);
+    // Left over if a previous swap was interrupted
+    if fs::symlink_metadata(&temporary).is_ok() {
+        fs::remove_file(&temporary)?;
+    }
+    std::os::unix::fs::symlink(target, &temporary)?;
+    fs::rename(temporary, link)
+}
+
+/// Points `link` at `target`, replacing an existing link.
+#[cfg(windows)]
+pub fn replace_link(link: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    remove_link(link)?;
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+/// Removes the link at `path` without touching its target, if there is one.
+pub fn remove_link(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        // Directory links are directories on Windows
+        #[cfg(windows)]
+        Ok(metadata) if metadata.is_symlink() => fs::remove_dir(path),
+        Ok(metadata) if metadata.is_symlink() => fs::remove_file(path),
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
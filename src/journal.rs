@@ -0,0 +1,190 @@
+use std::{collections::VecDeque, fmt, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dir_swapper::TrashedVersion,
+    save_manager::{GameId, SlotMeta},
+    slot_name::SlotName,
+};
+
+/// Number of operations that can be undone.
+pub const UNDO_LIMIT: usize = 50;
+
+/// Slot operation of [`crate::save_manager::SaveManager`] that can be undone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Operation {
+    /// The slot was moved to the trash as item `trash`, see [`crate::trash::Trash`]
+    Delete {
+        game: GameId,
+        name: SlotName,
+        meta: SlotMeta,
+        trash: String,
+        version: TrashedVersion,
+    },
+    Rename {
+        game: GameId,
+        name: SlotName,
+        new_name: SlotName,
+    },
+    /// The slot was replaced by an imported one and moved to the trash as item `trash`. Undoing
+    /// swaps the two slots, the imported one becomes trash item `imported`
+    Overwrite {
+        game: GameId,
+        name: SlotName,
+        trash: String,
+        imported: String,
+    },
+    /// Only recorded if another slot was loaded before
+    Load {
+        game: GameId,
+        previous: SlotName,
+        name: SlotName,
+    },
+    SetPath {
+        game: GameId,
+        previous: PathBuf,
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Delete { game, name, .. } => write!(f, "delete slot \"{name}\" of {game}"),
+            Operation::Rename {
+                game,
+                name,
+                new_name,
+            } => write!(f, "rename slot \"{name}\" of {game} to \"{new_name}\""),
+            Operation::Overwrite { game, name, .. } => {
+                write!(f, "import over slot \"{name}\" of {game}")
+            }
+            Operation::Load { game, name, .. } => write!(f, "load slot \"{name}\" of {game}"),
+            Operation::SetPath { game, path, .. } => {
+                write!(f, "move save directory of {game} to {}", path.display())
+            }
+        }
+    }
+}
+
+/// Operations that were done and undone, most recent last. Only the last [`UNDO_LIMIT`]
+/// operations are kept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    done: VecDeque<Operation>,
+    undone: Vec<Operation>,
+}
+
+impl Journal {
+    /// Records an operation that was just done, which makes undone operations impossible to redo.
+    /// Deleted slots stay in the trash when their operation is forgotten.
+    pub fn record(&mut self, operation: Operation) {
+        self.undone.clear();
+        self.done.push_back(operation);
+        if self.done.len() > UNDO_LIMIT {
+            self.done.pop_front();
+        }
+    }
+    /// Takes the most recent operation to undo.
+    pub fn undo(&mut self) -> Option<Operation> {
+        self.done.pop_back()
+    }
+    /// Records an operation as undone after [`Journal::undo`], so that it can be redone.
+    pub fn undone(&mut self, operation: Operation) {
+        self.undone.push(operation);
+    }
+    /// Takes the most recently undone operation to redo.
+    pub fn redo(&mut self) -> Option<Operation> {
+        self.undone.pop()
+    }
+    /// Records an operation as done again after [`Journal::redo`].
+    pub fn redone(&mut self, operation: Operation) {
+        self.done.push_back(operation);
+    }
+    /// The operation [`Journal::undo`] would return.
+    #[inline]
+    pub fn last_done(&self) -> Option<&Operation> {
+        self.done.back()
+    }
+    /// The operation [`Journal::redo`] would return.
+    #[inline]
+    pub fn last_undone(&self) -> Option<&Operation> {
+        self.undone.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(name: &str, new_name: &str) -> Operation {
+        Operation::Rename {
+            game: GameId::Steam(1),
+            name: name.parse().unwrap(),
+            new_name: new_name.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_in_order() {
+        let mut journal = Journal::default();
+        journal.record(rename("a", "b"));
+        journal.record(rename("b", "c"));
+
+        let undone = journal.undo().unwrap();
+        assert_eq!(undone, rename("b", "c"));
+        journal.undone(undone);
+        assert_eq!(journal.last_done(), Some(&rename("a", "b")));
+
+        let redone = journal.redo().unwrap();
+        assert_eq!(redone, rename("b", "c"));
+        journal.redone(redone);
+        assert!(journal.redo().is_none());
+    }
+
+    #[test]
+    fn recording_forgets_undone_and_old_operations() {
+        let mut journal = Journal::default();
+        for i in 0..UNDO_LIMIT {
+            journal.record(rename(&i.to_string(), "x"));
+        }
+        let undone = journal.undo().unwrap();
+        journal.undone(undone);
+
+        journal.record(rename("y", "z"));
+        assert!(journal.last_undone().is_none());
+        journal.record(rename("z", "w"));
+        let mut oldest = None;
+        while let Some(operation) = journal.undo() {
+            oldest = Some(operation);
+        }
+        assert_eq!(oldest, Some(rename("1", "x")));
+    }
+
+    #[test]
+    fn journal_survives_a_round_trip() {
+        let mut journal = Journal::default();
+        journal.record(Operation::Delete {
+            game: GameId::Steam(1),
+            name: "a".parse().unwrap(),
+            meta: SlotMeta::default(),
+            trash: "trash".to_string(),
+            version: TrashedVersion {
+                id: "id".to_string(),
+                active: true,
+            },
+        });
+        journal.record(Operation::SetPath {
+            game: GameId::Steam(1),
+            previous: PathBuf::from("old"),
+            path: PathBuf::from("new"),
+        });
+
+        let journal: Journal = toml::from_str(&toml::to_string(&journal).unwrap()).unwrap();
+        assert!(matches!(
+            journal.last_done(),
+            Some(Operation::SetPath { .. })
+        ));
+    }
+}
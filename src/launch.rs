@@ -0,0 +1,219 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use log::{error, info, warn};
+use steamlocate::SteamDir;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::{
+    save_manager::{GameId, SaveManager},
+    slot_name::SlotName,
+    usage::QuotaConfig,
+    watcher::{self, DirWatcher, SnapshotPolicy},
+};
+
+/// How often Steam games are checked for having started or exited.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long Steam may take to start a game, e.g. to install an update first.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How a game is started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Launcher {
+    /// Opens `steam://rungameid/<id>` and waits for the processes running from the install
+    /// directory of the game.
+    Steam(u32),
+    /// Runs a command, e.g. a configured executable or the command Steam passes to `wrap`
+    Command(Vec<OsString>),
+}
+
+impl Launcher {
+    /// The configured executable of `id`, or Steam.
+    pub fn of(save_manager: &SaveManager, id: GameId) -> Self {
+        match save_manager
+            .get(id)
+            .and_then(|game| game.executable.as_ref())
+        {
+            Some(executable) => Launcher::Command(vec![executable.into()]),
+            None => match id {
+                GameId::Steam(app_id) => Launcher::Steam(app_id),
+            },
+        }
+    }
+
+    /// Starts the game and blocks until it exits. Returns the exit code if it is known.
+    pub fn run(&self) -> Result<Option<i32>> {
+        match self {
+            Launcher::Command(command) => {
+                let (program, args) = command.split_first().context("empty launch command")?;
+                let status = Command::new(program)
+                    .args(args)
+                    .status()
+                    .with_context(|| format!("failed to run {}", program.display()))?;
+                Ok(status.code())
+            }
+            Launcher::Steam(app_id) => {
+                let install_dir = install_dir(*app_id)?;
+                open_url(&format!("steam://rungameid/{app_id}"))?;
+                let mut system = System::new();
+                let started = Instant::now();
+                while !is_running(&mut system, &install_dir) {
+                    if started.elapsed() > STARTUP_TIMEOUT {
+                        bail!("game {app_id} did not start");
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                while is_running(&mut system, &install_dir) {
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Loads `slot` of `id`, or keeps the loaded one if `None`, plays the game with `launcher` and
+/// snapshots the primary directory back into the slot once the game exits. With `watch`, every
+/// save is also recorded in the history of the slot while the game runs. Returns the exit code
+/// of the game if it is known.
+pub fn launch(
+    save_manager: &mut SaveManager,
+    id: GameId,
+    slot: Option<&SlotName>,
+    launcher: &Launcher,
+    watch: bool,
+) -> Result<Option<i32>> {
+    if let Some(slot) = slot
+        && save_manager.is_loaded(id, slot) != Some(true)
+    {
+        save_manager
+            .load(id, slot.clone())?
+            .with_context(|| format!("game {id} has no slot \"{slot}\""))?;
+        save_manager.save()?;
+    }
+    // Refuses to start the game if its saves went missing, the snapshot afterwards would
+    // overwrite the slot
+    save_manager
+        .get(id)
+        .with_context(|| format!("game {id} was not added"))?
+        .slot_swapper
+        .ensure_primary_dir()?;
+    let code = if watch {
+        let swapper = &save_manager
+            .get(id)
+            .with_context(|| format!("game {id} was not added"))?
+            .slot_swapper;
+        let policy = SnapshotPolicy::default();
+        let watcher = DirWatcher::new(swapper.primary_dir(), policy.poll_interval)?;
+        let stop = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let stop = &stop;
+            let recorder =
+                scope.spawn(move || watcher::record_while(swapper, &watcher, policy, stop));
+            let code = launcher.run();
+            stop.store(true, Ordering::Relaxed);
+            match recorder.join().expect("snapshot thread should not panic") {
+                Ok(count) => info!("recorded {count} snapshots of game {id}"),
+                Err(err) => error!("automatic snapshots of game {id} stopped: {err:#}"),
+            }
+            code
+        })
+    } else {
+        launcher.run()
+    };
+    // The game may have written its saves before the launcher failed, so they are kept anyway
+    save_manager
+        .snapshot(id)?
+        .with_context(|| format!("game {id} has no loaded slot"))?;
+    // The saves are safe at this point, quota problems should not hide the exit code
+    match QuotaConfig::read().and_then(|config| save_manager.enforce_quota(&config)) {
+        Ok((pruned, warnings)) => {
+            if pruned > 0 {
+                info!("pruned {pruned} snapshots to stay within the disk quota");
+            }
+            for warning in warnings {
+                warn!("{warning}");
+            }
+        }
+        Err(err) => warn!("failed to check the disk quota: {err:#}"),
+    }
+    code
+}
+
+fn install_dir(app_id: u32) -> Result<PathBuf> {
+    let (app, library) = SteamDir::locate()?
+        .find_app(app_id)?
+        .with_context(|| format!("game {app_id} is not installed"))?;
+    Ok(library.resolve_app_dir(&app))
+}
+
+/// Whether any process runs from `dir`. Games running through Proton are found by the Windows
+/// path of their executable in the command line of Wine.
+fn is_running(system: &mut System, dir: &Path) -> bool {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_exe(UpdateKind::OnlyIfNotSet)
+            .with_cmd(UpdateKind::OnlyIfNotSet),
+    );
+    let own_pid = sysinfo::get_current_pid().ok();
+    let dir_str = dir.to_string_lossy();
+    system
+        .processes()
+        .iter()
+        .filter(|(pid, _)| Some(**pid) != own_pid)
+        .any(|(_, process)| {
+            process.exe().is_some_and(|exe| exe.starts_with(dir))
+                || process
+                    .cmd()
+                    .iter()
+                    .any(|arg| arg.to_string_lossy().contains(dir_str.as_ref()))
+        })
+}
+
+fn open_url(url: &str) -> Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = command
+        .arg(url)
+        .status()
+        .with_context(|| format!("failed to open {url}"))?;
+    if !status.success() {
+        bail!("failed to open {url}: {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn command_exit_code_is_returned() {
+        let launcher = Launcher::Command(vec!["sh".into(), "-c".into(), "exit 3".into()]);
+        assert_eq!(launcher.run().unwrap(), Some(3));
+    }
+
+    #[test]
+    fn missing_command_is_an_error() {
+        assert!(Launcher::Command(Vec::new()).run().is_err());
+        let launcher = Launcher::Command(vec!["/nonexistent/vittusave-game".into()]);
+        assert!(launcher.run().is_err());
+    }
+}
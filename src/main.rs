@@ -3,7 +3,6 @@
 // TODO: do some sort of integrity check before loading saves
 // TODO: steam Cloud support (info UT favorites)
 // TODO: docs
-// FIXME: bug when game save files don't exist
 // TODO: unit tests
 // TODO: use Cow for strings?
 // TODO: alphabetical ordering of games, should be configurable
@@ -11,18 +10,36 @@
 // TODO: replace legacy system
 
 mod app;
+mod archive;
+mod cli;
+mod compress;
 mod consts;
+mod copy;
+mod crypt;
+mod diff;
 mod dir_swapper;
+mod journal;
+mod launch;
 mod pcgw;
+mod registry;
 mod save_manager;
+mod slot_name;
+mod sources;
+mod sync;
+mod trash;
+mod usage;
 mod utils;
+mod watcher;
+
+use std::process::ExitCode;
 
 use anyhow::Result;
+use clap::Parser;
 
-use crate::app::App;
+use crate::cli::Cli;
 
 // TODO: use async Steam API in the future
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     env_logger::init();
 
     // let steam_dir = SteamDir::locate()?;
@@ -57,8 +74,5 @@ fn main() -> Result<()> {
     //     .chain([Ok(String::from("Add")), Ok(String::from("Settings"))])
     //     .collect::<Result<_>>()?;
     //
-    let terminal = ratatui::init();
-    let result = App::build()?.run(terminal);
-    ratatui::restore();
-    result
+    Cli::parse().run()
 }
@@ -1,20 +1,59 @@
 mod api;
+pub(crate) mod client;
 mod utils;
 
-pub use utils::fetch_page_by_id;
+pub use api::{Candidate, Location, LocationKind, PCGWSaveMeta};
+pub use client::WikiClient;
+pub use utils::{ExpansionParams, fetch_page_by_id};
 
 use mediawiki::MediaWikiError;
 use thiserror::Error;
 
+use crate::save_manager::GameId;
+
 // TODO: look into Anyhow's `Error` and `Context` instead of this
 #[derive(Debug, Error)]
 pub enum PCGWError {
     #[error("failed to fetch data from MediaWiki API")]
-    MediaWikiError(#[from] MediaWikiError),
-    #[error("parse error")]
-    ParseError,
+    Network(#[source] MediaWikiError),
+    #[error("PCGamingWiki is rate limiting requests, try again later")]
+    RateLimited,
+    #[error("MediaWiki API returned error `{code}`: {info}")]
+    Api { code: String, info: String },
+    #[error("unexpected MediaWiki API response: {0}")]
+    MalformedResponse(&'static str),
+    #[error("no PCGamingWiki page found for {0:?}")]
+    PageNotFound(GameId),
+    #[error("page \"{page}\" has no \"{section}\" section")]
+    SectionNotFound { page: String, section: &'static str },
+    #[error("page \"{page}\" has no \"{table}\" table")]
+    TableNotFound { page: String, table: &'static str },
+    #[error("row {row} of \"{table}\" on page \"{page}\" is malformed: {reason}")]
+    MalformedRow {
+        page: String,
+        table: &'static str,
+        row: usize,
+        reason: &'static str,
+    },
     #[error("error reading or rendering note HTML")]
     NoteError(#[from] html2text::Error),
-    #[error("no data returned by the server")]
-    NotFound,
+}
+
+impl From<MediaWikiError> for PCGWError {
+    fn from(err: MediaWikiError) -> Self {
+        match &err {
+            // `ApiSync` gives up with this message after retrying on maxlag errors
+            MediaWikiError::String(message) if message.contains("[MAXLAG]") => {
+                PCGWError::RateLimited
+            }
+            MediaWikiError::Reqwest(reqwest_err)
+                if reqwest_err
+                    .status()
+                    .is_some_and(|status| status.as_u16() == 429) =>
+            {
+                PCGWError::RateLimited
+            }
+            _ => PCGWError::Network(err),
+        }
+    }
 }
@@ -1,18 +1,22 @@
 use std::{
+    cmp::Reverse,
     collections::HashMap,
-    fs,
+    fmt, fs,
     hash::Hash,
     io,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use mediawiki::api_sync::ApiSync;
 use thiserror::Error;
 
 use super::utils;
 use crate::{
-    pcgw::{PCGWError, utils::ExpansionParams},
+    pcgw::{PCGWError, WikiClient, utils::ExpansionParams},
+    registry::RegistryKey,
     save_manager::GameId,
+    sources::SaveLocations,
+    utils::dir_stats,
 };
 
 // TODO: rename this error type
@@ -21,22 +25,76 @@ use crate::{
 pub enum LocationError {
     #[error("built path does not exist")]
     InvalidPath(#[from] io::Error),
-    #[error("undefined abbreviation in path")]
-    UndefinedAbbr,
+    #[error(transparent)]
+    Expansion(#[from] ExpansionError),
+    #[error("invalid wildcard pattern")]
+    Pattern(#[from] glob::PatternError),
+}
+
+/// A path on disk matching a location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    /// Most recent modification of the path or anything inside it
+    pub modified: Option<SystemTime>,
+    /// Total size of the files in bytes
+    pub size: u64,
+}
+
+/// Placeholders of a location that are unknown or have no value.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("cannot expand `{path}`, unresolved: {}", tokens.join(", "))]
+pub struct ExpansionError {
+    pub path: String,
+    /// Every unresolved placeholder once, in the order they appear
+    pub tokens: Vec<String>,
+}
+
+/// A location with its placeholders replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpandedPath {
+    Path(PathBuf),
+    /// A path with `*` or `?` wildcards
+    Pattern(PathBuf),
+    /// A Windows registry key, e.g. `HKEY_CURRENT_USER\Software\Game`
+    Registry(String),
+}
+impl ExpandedPath {
+    /// The file system path, `None` for registry keys.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ExpandedPath::Path(path) | ExpandedPath::Pattern(path) => Some(path),
+            ExpandedPath::Registry(_) => None,
+        }
+    }
+}
+
+/// Thumbs-up or thumbs-down icon shown next to a note on the wiki.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reliability {
+    Reliable,
+    Unreliable,
+}
+
+/// Footnote or note below a table, rendered to plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub text: String,
+    pub reliability: Option<Reliability>,
 }
 
 /// Pre-processed location
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Location {
-    path: Option<PathBuf>,
+    expanded: Option<ExpandedPath>,
     path_str: String,
-    note: Option<String>,
+    notes: Vec<Note>,
 }
 impl Location {
-    pub fn new(path_str: String, note: Option<String>) -> Self {
+    pub fn new(path_str: String, notes: Vec<Note>) -> Self {
         Self {
             path_str,
-            note,
+            notes,
             ..Default::default()
         }
     }
@@ -44,56 +102,213 @@ impl Location {
     pub fn path_str(&self) -> &str {
         &self.path_str
     }
+    /// Footnotes attached to the location, in the order they appear.
+    #[inline]
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+    /// A thumbs-down on any note marks the location as unreliable, otherwise a thumbs-up marks it
+    /// as reliable.
+    pub fn reliability(&self) -> Option<Reliability> {
+        let mut reliabilities = self.notes.iter().filter_map(|note| note.reliability);
+        if reliabilities.clone().any(|r| r == Reliability::Unreliable) {
+            Some(Reliability::Unreliable)
+        } else {
+            reliabilities.next()
+        }
+    }
+    /// The expanded file system path, `None` before `Location::expand_path` or for registry keys.
     #[inline]
     pub fn path(&self) -> Option<&Path> {
-        self.path.as_deref()
-    }
-    pub fn expand_path(&mut self, install_dir: &Path, user_id: u64) -> Result<(), LocationError> {
-        self.path.replace(
-            utils::replace_path_abbrs(
-                &self.path_str,
-                None,
-                ExpansionParams {
-                    install_dir,
-                    user_id,
-                },
-            )
-            .ok_or(LocationError::UndefinedAbbr)
-            .map_or_else(Err, |path| {
-                fs::exists(&path)
-                    .map_err(LocationError::InvalidPath)
-                    .map(|_| path)
-            })?,
-        );
+        self.expanded.as_ref()?.path()
+    }
+    /// The registry key of the location, if it is one.
+    pub fn registry_key(&self) -> Option<RegistryKey> {
+        self.path_str.parse().ok()
+    }
+    #[inline]
+    pub fn expanded(&self) -> Option<&ExpandedPath> {
+        self.expanded.as_ref()
+    }
+    /// Finds every path on disk matching the location, most recently modified first. Wildcards are
+    /// expanded and ids that are not in `params`, like `<user-id>`, match any folder name.
+    /// Registry keys have no candidates.
+    pub fn resolve(&self, params: ExpansionParams) -> Result<Vec<Candidate>, LocationError> {
+        self.resolve_for(None, params)
+    }
+    pub(super) fn resolve_for(
+        &self,
+        os: Option<&str>,
+        params: ExpansionParams,
+    ) -> Result<Vec<Candidate>, LocationError> {
+        let template = if params.user_id.is_none() {
+            utils::replace_ignore_case(&self.path_str, utils::USER_ID, "*")
+        } else {
+            self.path_str.clone()
+        };
+        let pattern = match utils::replace_path_abbrs(&template, os, params)? {
+            ExpandedPath::Registry(_) => return Ok(Vec::new()),
+            ExpandedPath::Path(path) | ExpandedPath::Pattern(path) => path,
+        };
+        // Only `*` and `?` are wildcards on the wiki
+        let pattern = pattern
+            .to_string_lossy()
+            .replace('[', "[[]")
+            .replace(']', "[]]");
+        let options = glob::MatchOptions {
+            case_sensitive: os.unwrap_or(std::env::consts::OS) != "windows",
+            ..Default::default()
+        };
+        let mut candidates = glob::glob_with(&pattern, options)?
+            .filter_map(|path| path.ok())
+            .map(|path| {
+                let (size, modified) = dir_stats(&path)?;
+                Ok(Candidate {
+                    path,
+                    modified,
+                    size,
+                })
+            })
+            .collect::<Result<Vec<_>, io::Error>>()?;
+        candidates.sort_by_key(|candidate| Reverse(candidate.modified));
+        Ok(candidates)
+    }
+    /// Replaces the placeholders of the location for the current OS.
+    pub fn expand_path(&mut self, params: ExpansionParams) -> Result<(), LocationError> {
+        let expanded = utils::replace_path_abbrs(&self.path_str, None, params)?;
+        if let Some(path) = expanded.path() {
+            fs::exists(path)?;
+        }
+        self.expanded = Some(expanded);
         Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LocationKind {
     OS(String),
     Steam,
 }
+impl From<&str> for LocationKind {
+    /// Parses the system name used in the PCGamingWiki tables.
+    fn from(name: &str) -> Self {
+        match name {
+            "Steam" => LocationKind::Steam,
+            other => LocationKind::OS(other.to_string()),
+        }
+    }
+}
+impl fmt::Display for LocationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationKind::OS(os) => f.write_str(os),
+            LocationKind::Steam => f.write_str("Steam"),
+        }
+    }
+}
+
+/// Store or launcher listed in the "Save game cloud syncing" table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CloudSyncSystem {
+    Steam,
+    GogGalaxy,
+    Epic,
+    EaApp,
+    UbisoftConnect,
+    Xbox,
+    BattleNet,
+    Other(String),
+}
+impl From<&str> for CloudSyncSystem {
+    fn from(name: &str) -> Self {
+        match name {
+            "Steam Cloud" | "Steam" => CloudSyncSystem::Steam,
+            "GOG Galaxy" => CloudSyncSystem::GogGalaxy,
+            "Epic Games Launcher" | "Epic Games Store" => CloudSyncSystem::Epic,
+            "EA app" | "Origin" => CloudSyncSystem::EaApp,
+            "Ubisoft Connect" | "Uplay" => CloudSyncSystem::UbisoftConnect,
+            "Xbox Cloud" | "Xbox" | "OneDrive" => CloudSyncSystem::Xbox,
+            "Battle.net" => CloudSyncSystem::BattleNet,
+            other => CloudSyncSystem::Other(other.to_string()),
+        }
+    }
+}
+
+/// Rating icon shown next to a cloud sync system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudSyncSupport {
+    Supported,
+    Unsupported,
+    Limited,
+    Hackable,
+    Unknown,
+}
+impl CloudSyncSupport {
+    /// Whether the launcher may overwrite the save directory behind our back.
+    #[inline]
+    pub fn may_sync(self) -> bool {
+        matches!(
+            self,
+            CloudSyncSupport::Supported | CloudSyncSupport::Limited
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CloudSync {
+    pub system: CloudSyncSystem,
+    pub support: CloudSyncSupport,
+    pub note: Option<String>,
+}
 
 #[derive(Debug, Default)]
 pub struct PCGWSaveMeta {
-    locations: HashMap<LocationKind, Vec<Location>>,
-    extra_notes: Vec<String>,
+    pub(super) save_locations: HashMap<LocationKind, Vec<Location>>,
+    pub(super) config_locations: HashMap<LocationKind, Vec<Location>>,
+    pub(super) cloud_sync: Vec<CloudSync>,
+    pub(super) extra_notes: Vec<Note>,
+}
+
+impl From<PCGWSaveMeta> for SaveLocations {
+    fn from(meta: PCGWSaveMeta) -> Self {
+        SaveLocations {
+            saves: meta.save_locations,
+            configs: meta.config_locations,
+        }
+    }
 }
 
-// TODO: Can user ID, steam path, etc. be turned optional somewhow?
 impl PCGWSaveMeta {
     // TODO: return Self back in error
-    pub fn build(api: &ApiSync, id: GameId) -> Result<Self, PCGWError> {
-        Ok(PCGWSaveMeta {
-            locations: utils::get_location_data(api, id)?,
-            extra_notes: Vec::new(),
-        })
+    pub fn build(api: &impl WikiClient, id: GameId) -> Result<Self, PCGWError> {
+        utils::get_game_data(api, id)
     }
     pub fn get_locations(&mut self, kind: LocationKind) -> &mut [Location] {
-        self.locations
+        self.save_locations
+            .get_mut(&kind)
+            .map(|vec| vec.as_mut_slice())
+            .unwrap_or(&mut [])
+    }
+    pub fn get_config_locations(&mut self, kind: LocationKind) -> &mut [Location] {
+        self.config_locations
             .get_mut(&kind)
             .map(|vec| vec.as_mut_slice())
             .unwrap_or(&mut [])
     }
+    /// Notes listed below the location tables.
+    #[inline]
+    pub fn extra_notes(&self) -> &[Note] {
+        &self.extra_notes
+    }
+    /// Every row of the cloud syncing table, in wiki order.
+    #[inline]
+    pub fn cloud_sync(&self) -> &[CloudSync] {
+        &self.cloud_sync
+    }
+    /// Cloud sync systems that may restore saves on their own, which can undo a swap.
+    pub fn active_cloud_sync(&self) -> impl Iterator<Item = &CloudSync> {
+        self.cloud_sync
+            .iter()
+            .filter(|cloud_sync| cloud_sync.support.may_sync())
+    }
 }
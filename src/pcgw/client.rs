@@ -0,0 +1,100 @@
+use mediawiki::{ApiSync, MediaWikiError};
+use serde_json::Value;
+
+/// The part of the MediaWiki API used to look up save data. Implemented by `ApiSync`, and by a
+/// fixture-backed client in tests so the whole fetch and parse pipeline can run offline.
+pub trait WikiClient {
+    /// Runs a GET query, continuing and merging all results.
+    fn query(&self, params: &[(&str, &str)]) -> Result<Value, MediaWikiError>;
+}
+
+impl WikiClient for ApiSync {
+    fn query(&self, params: &[(&str, &str)]) -> Result<Value, MediaWikiError> {
+        self.get_query_api_json_all(&self.params_into(params))
+    }
+}
+
+#[cfg(test)]
+pub mod fixture {
+    use std::{
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use mediawiki::MediaWikiError;
+    use serde_json::{Value, json};
+
+    use super::WikiClient;
+
+    /// Answers queries from the recorded responses in `tests/fixtures/pcgw`:
+    /// - `games.json` maps Steam app IDs to page names, standing in for the cargo query.
+    /// - `pages/<page>.html` is the parsed text of a page.
+    /// - `pages/<page>.json` is a raw API response, used instead of the HTML when present (e.g.
+    ///   to record error responses).
+    pub struct FixtureClient {
+        dir: PathBuf,
+        games: HashMap<String, String>,
+    }
+
+    impl FixtureClient {
+        pub fn new() -> Self {
+            let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pcgw");
+            let games = serde_json::from_slice(
+                &fs::read(dir.join("games.json")).expect("games fixture should exist"),
+            )
+            .expect("games fixture should be valid JSON");
+            Self { dir, games }
+        }
+        /// Names of all recorded pages.
+        pub fn pages(&self) -> impl Iterator<Item = &str> {
+            self.games.values().map(String::as_str)
+        }
+        fn page_path(&self, page: &str, extension: &str) -> PathBuf {
+            let mut path = self
+                .dir
+                .join("pages")
+                .join(page.replace(['/', ' ', ':'], "_"));
+            path.set_extension(extension);
+            path
+        }
+        fn cargo_query(&self, params: &HashMap<&str, &str>) -> Value {
+            let steam_id = params["where"]
+                .strip_prefix("Steam_AppID HOLDS ")
+                .expect("only Steam ID lookups are recorded");
+            match self.games.get(steam_id) {
+                Some(page) => json!({ "cargoquery": [{ "title": { "Page": page } }] }),
+                None => json!({ "cargoquery": [] }),
+            }
+        }
+        fn parse(&self, params: &HashMap<&str, &str>) -> Result<Value, MediaWikiError> {
+            let page = params["page"];
+            let raw_path = self.page_path(page, "json");
+            if raw_path.exists() {
+                return Ok(serde_json::from_slice(&fs::read(raw_path).unwrap())?);
+            }
+            match fs::read_to_string(self.page_path(page, "html")) {
+                Ok(html) => Ok(json!({ "parse": { "title": page, "text": { "*": html } } })),
+                Err(_) => Ok(json!({
+                    "error": {
+                        "code": "missingtitle",
+                        "info": "The page you specified doesn't exist."
+                    }
+                })),
+            }
+        }
+    }
+
+    impl WikiClient for FixtureClient {
+        fn query(&self, params: &[(&str, &str)]) -> Result<Value, MediaWikiError> {
+            let params: HashMap<_, _> = params.iter().copied().collect();
+            match params["action"] {
+                "cargoquery" => Ok(self.cargo_query(&params)),
+                "parse" => self.parse(&params),
+                action => Err(MediaWikiError::String(format!(
+                    "action `{action}` is not recorded"
+                ))),
+            }
+        }
+    }
+}
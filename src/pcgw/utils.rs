@@ -1,257 +1,418 @@
 use std::{
-    cell::{Ref, RefCell},
     collections::HashMap,
     env,
     ffi::OsString,
-    fs, io,
+    fs,
     path::{Path, PathBuf},
-    sync::LazyLock,
 };
 
-use mediawiki::ApiSync;
+use html2text::render::TrivialDecorator;
 use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::Value;
 
 use log::{debug, info, warn /*, trace*/};
 
 use itertools::Itertools;
-use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use uuid::Uuid;
 
 use crate::{
-    consts::CACHE_DIR,
     pcgw::{
-        PCGWError,
-        api::{Location, LocationKind},
+        PCGWError, WikiClient,
+        api::{
+            CloudSync, CloudSyncSupport, CloudSyncSystem, ExpandedPath, ExpansionError, Location,
+            LocationKind, Note, PCGWSaveMeta, Reliability,
+        },
     },
     save_manager::GameId,
 };
 
 // TODO: Move methods to relevant struct or remove them
 /// Looks up a Steam ID in the PCGW and returns the name of the page, if it exists
-pub fn fetch_page_by_id(api: &ApiSync, steam_id: GameId) -> Result<String, PCGWError> {
+pub fn fetch_page_by_id(api: &impl WikiClient, steam_id: GameId) -> Result<String, PCGWError> {
     #[allow(unreachable_patterns)]
     let steam_id = match steam_id {
         GameId::Steam(id) => id,
         _ => todo!("non-Steam `GameId`s are not yet supported"),
     };
     // Query parameters
-    let params = api.params_into(&[
+    let params = [
         ("action", "cargoquery"),
         ("tables", "Infobox_game"),
         ("fields", "Infobox_game._pageName=Page"),
         ("where", &format!("Steam_AppID HOLDS {steam_id}")),
-    ]);
+    ];
 
     // Run query; this will automatically continue if more results are available, and merge all results into one
-    let res = api.get_query_api_json_all(&params)?;
+    let res = query(api, &params, GameId::Steam(steam_id))?;
 
     res["cargoquery"]
         .as_array()
-        .ok_or(PCGWError::ParseError)?
+        .ok_or(PCGWError::MalformedResponse(
+            "cargo query returned no results array",
+        ))?
         .first()
-        .ok_or(PCGWError::NotFound)?["title"]["Page"]
+        .ok_or(PCGWError::PageNotFound(GameId::Steam(steam_id)))?["title"]["Page"]
         .as_str()
         .map(str::to_string)
-        .ok_or(PCGWError::ParseError)
+        .ok_or(PCGWError::MalformedResponse(
+            "cargo query result has no page name",
+        ))
 }
 
-#[derive(Debug)]
-enum HtmlIdTy {
-    CiteRef,
-    CiteNote,
+/// Runs a query about `game` and turns errors reported in the response body into `PCGWError`s.
+fn query(api: &impl WikiClient, params: &[(&str, &str)], game: GameId) -> Result<Value, PCGWError> {
+    let res = api.query(params)?;
+    check_response(res, game)
 }
 
-/// This is function is nescessary because the numbers that prefix IDs are different when requesting for only a specific section. I don't know why.
-fn format_id(id: &str, ty: HtmlIdTy) -> Option<&str> {
-    let (start, separator) = match ty {
-        HtmlIdTy::CiteRef => ("cite_ref-", '_'),
-        HtmlIdTy::CiteNote => ("cite_note-", '-'),
+fn check_response(res: Value, game: GameId) -> Result<Value, PCGWError> {
+    let Some(code) = res["error"]["code"].as_str() else {
+        return Ok(res);
     };
-    let begin = id.find(start)? + start.len();
-    let end = id.rfind(separator)?;
-    // CiteRef: When there is no note ID, CiteRef is formatted just like CiteNote, it separates the numbers at the end with '-' instead of '_'.
-    // `str::rfind` would thus only find the `_` that is in-between `cite_ref, which stands at index 4.
-    // Example: "cite_ref-2"
-    if end == 4 {
-        return Some("");
-    }
-    // CiteNote: `begin` is one ahead of the `start`` identifier, while `end` is one behind. `begin` will be one greater than `end` when there is no note name.
-    // Example: "cite_note-2"
-    if begin == end + 1 {
-        return Some("");
+    match code {
+        "ratelimited" | "maxlag" => Err(PCGWError::RateLimited),
+        // The page was deleted or renamed since the cargo tables were updated
+        "missingtitle" | "missingpage" => Err(PCGWError::PageNotFound(game)),
+        code => Err(PCGWError::Api {
+            code: code.to_string(),
+            info: res["error"]["info"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        }),
     }
-    Some(&id[begin..end])
 }
 
-// Environment variable placeholders
-static ENV_VARS: LazyLock<HashMap<&str, &[&str]>> = LazyLock::new(|| {
-    HashMap::from([
-        (
-            "windows",
-            [
-                "%USERPROFILE%",
-                "%APPDATA%",
-                "%LOCALAPPDATA%",
-                "%TEMP%",
-                "%PUBLIC%",
-                "%PROGRAMDATA%",
-                "%PROGRAMFILES%", // FIXME: check for byteness
-                "%WINDIR%",
-            ]
-            .as_ref(),
-        ),
-        ("macos", ["$HOME"].as_ref()),
-        (
-            "linux",
-            ["$HOME", "$XDG_DATA_HOME", "$XDG_CONFIG_HOME"].as_ref(),
-        ),
-    ])
-});
-
-/// Warning: a value must not contain any of the map's keys, otherwise `replace_path_abbrs` will panic.
-static ENV_VAR_DEFAULTS: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
-    HashMap::from([
-        ("$XDG_DATA_HOME", "$HOME/.local/share"),
-        ("$XDG_CONFIG_HOME", "$HOME/.config"),
-    ])
-});
+/// Windows environment variables used on the PCGamingWiki, with the value used if the variable
+/// is undefined. Names are upper case, matching is case-insensitive.
+const WINDOWS_VARS: &[(&str, Option<&str>)] = &[
+    ("USERPROFILE", None),
+    ("USERNAME", None),
+    ("APPDATA", Some(r"%USERPROFILE%\AppData\Roaming")),
+    ("LOCALAPPDATA", Some(r"%USERPROFILE%\AppData\Local")),
+    ("TEMP", Some(r"%LOCALAPPDATA%\Temp")),
+    ("TMP", Some(r"%LOCALAPPDATA%\Temp")),
+    ("PUBLIC", Some(r"%SYSTEMDRIVE%\Users\Public")),
+    ("PROGRAMDATA", Some(r"%SYSTEMDRIVE%\ProgramData")),
+    ("ALLUSERSPROFILE", Some(r"%SYSTEMDRIVE%\ProgramData")),
+    ("PROGRAMFILES", Some(r"%SYSTEMDRIVE%\Program Files")),
+    (
+        "PROGRAMFILES(X86)",
+        Some(r"%SYSTEMDRIVE%\Program Files (x86)"),
+    ),
+    ("PROGRAMW6432", Some(r"%SYSTEMDRIVE%\Program Files")),
+    ("COMMONPROGRAMFILES", Some(r"%PROGRAMFILES%\Common Files")),
+    ("WINDIR", Some(r"%SYSTEMDRIVE%\Windows")),
+    ("SYSTEMROOT", Some(r"%SYSTEMDRIVE%\Windows")),
+    ("SYSTEMDRIVE", Some("C:")),
+];
+
+/// Environment variables used in Linux and macOS paths, with the value used if the variable is
+/// undefined or empty.
+const UNIX_VARS: &[(&str, Option<&str>)] = &[
+    ("HOME", None),
+    ("USER", None),
+    ("XDG_DATA_HOME", Some("$HOME/.local/share")),
+    ("XDG_CONFIG_HOME", Some("$HOME/.config")),
+    ("XDG_STATE_HOME", Some("$HOME/.local/state")),
+    ("XDG_CACHE_HOME", Some("$HOME/.cache")),
+];
 
 // Other Placeholders
+const PATH_TO_GAME: &str = "<path-to-game>";
 const STEAM_FOLDER: &str = "<Steam-folder>";
-const USER_ID: &str = "<user-id>";
-
-#[derive(Debug, Clone)]
+pub(super) const USER_ID: &str = "<user-id>";
+const UBISOFT_FOLDER: &str = "<Ubisoft-folder>";
+const UPLAY_FOLDER: &str = "<Uplay-folder>";
+const UBISOFT_FOLDER_DEFAULT: &str = r"%PROGRAMFILES(X86)%\Ubisoft\Ubisoft Game Launcher";
+
+const REGISTRY_HIVES: &[(&str, &str)] = &[
+    ("HKEY_CURRENT_USER", "HKEY_CURRENT_USER"),
+    ("HKCU", "HKEY_CURRENT_USER"),
+    ("HKEY_LOCAL_MACHINE", "HKEY_LOCAL_MACHINE"),
+    ("HKLM", "HKEY_LOCAL_MACHINE"),
+    ("HKEY_USERS", "HKEY_USERS"),
+    ("HKEY_CLASSES_ROOT", "HKEY_CLASSES_ROOT"),
+];
+
+/// Values for the placeholders that are not environment variables. A placeholder without a value
+/// is reported as unresolved.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct ExpansionParams<'a> {
-    pub install_dir: &'a Path,
-    pub user_id: u64,
+    /// Replaces `<Steam-folder>`
+    pub steam_dir: Option<&'a Path>,
+    /// Replaces `<user-id>`
+    pub user_id: Option<u64>,
+    /// Replaces `<path-to-game>`
+    pub game_dir: Option<&'a Path>,
 }
-/// Should be updated from <https://www.pcgamingwiki.com/wiki/Glossary:Game_data>
-/// Returns None if an undefined abbreviation in the path
-/// Supports non-unicode encoded env variable values
-/// Finds first match only
-pub fn replace_path_abbrs(
-    path: &str,
-    os: Option<&str>,
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    /// `%NAME%` on Windows, `$NAME` or `${NAME}` elsewhere
+    Var(&'a str),
+    /// `~` at the start of a path
+    Home,
+    /// `<name>`
+    Placeholder(&'a str),
+}
+
+/// Returns the token at the start of `rest` and its length in bytes.
+fn next_token(rest: &str, windows: bool) -> Option<(Token<'_>, usize)> {
+    let is_var_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    if windows && let Some(after) = rest.strip_prefix('%') {
+        let end = after.find('%')?;
+        let name = &after[..end];
+        (!name.is_empty() && name.chars().all(|c| is_var_char(c) || c == '(' || c == ')'))
+            .then_some((Token::Var(name), end + 2))
+    } else if !windows && let Some(after) = rest.strip_prefix("${") {
+        let end = after.find('}')?;
+        let name = &after[..end];
+        (!name.is_empty() && name.chars().all(is_var_char)).then_some((Token::Var(name), end + 3))
+    } else if !windows && let Some(after) = rest.strip_prefix('$') {
+        let end = after.find(|c| !is_var_char(c)).unwrap_or(after.len());
+        let name = &after[..end];
+        (!name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()))
+            .then_some((Token::Var(name), end + 1))
+    } else if let Some(after) = rest.strip_prefix('<') {
+        let end = after.find(['>', '<', '/', '\\'])?;
+        (after[end..].starts_with('>') && end > 0)
+            .then_some((Token::Placeholder(&after[..end]), end + 2))
+    } else {
+        None
+    }
+}
+
+fn env_var(name: &str) -> Option<OsString> {
+    env::var_os(name).filter(|value| !value.is_empty())
+}
+
+/// Resolves a token to its value, or `None` if it is unknown or undefined.
+fn resolve_token(
+    token: &Token,
+    windows: bool,
     params: ExpansionParams,
-) -> Option<PathBuf> {
-    let os = os.unwrap_or(std::env::consts::OS);
-
-    let mut replacement_locations: HashMap<usize, &str> = HashMap::new();
-    let mut replacement_data: HashMap<&str, OsString> = HashMap::new();
-
-    // FIXME: Handle invalid OS without just returning None
-    for var_key in *ENV_VARS.get(os)? {
-        let var_key_lower = var_key.to_lowercase();
-        let var_key_upper = var_key.to_uppercase();
-        let find_result = match os {
-            "windows" => path
-                .find(&var_key_lower)
-                .or_else(|| path.find(&var_key_upper)),
-            "linux" | "macos" => path.find(var_key),
-            _ => return None, // FIXME
-        };
-        if let Some(i) = find_result {
-            let var_key_env_name = {
-                match os {
-                    "windows" => &var_key_lower,
-                    "linux" | "macos" => &var_key[1..], // Removes the "$"
-                    _ => return None,                   // FIXME
-                }
-            };
-            let Some(var_val) = env::var_os(var_key_env_name).or_else(|| {
-                let default_val = ENV_VAR_DEFAULTS.get(var_key)?;
-                assert!(
-                    !default_val.contains(var_key),
-                    "default value would cause an infinite loop"
-                );
-                replace_path_abbrs(default_val, Some(os), params.clone())
-                    .map(|path_buf| path_buf.into_os_string())
-            }) else {
-                warn!("{var_key_env_name} does not have a default and is undefined");
-                return None;
-            };
-            info!("`{var_key}` env var is defined or has a default");
-            replacement_locations.insert(i, var_key);
-            replacement_data.insert(var_key, var_val);
+    unresolved: &mut Vec<String>,
+) -> Option<OsString> {
+    let var = |name: &str, vars: &[(&str, Option<&str>)], unresolved: &mut Vec<String>| {
+        let &(name, default) = vars
+            .iter()
+            .find(|(var, _)| var.eq_ignore_ascii_case(name))?;
+        env_var(name).or_else(|| Some(expand(default?, windows, params, unresolved)))
+    };
+    match token {
+        Token::Var(name) if windows => var(name, WINDOWS_VARS, unresolved),
+        Token::Var(name) => var(name, UNIX_VARS, unresolved),
+        Token::Home if windows => var("USERPROFILE", WINDOWS_VARS, unresolved),
+        Token::Home => var("HOME", UNIX_VARS, unresolved),
+        Token::Placeholder(name) => {
+            let placeholder = format!("<{name}>");
+            let is = |other: &str| placeholder.eq_ignore_ascii_case(other);
+            if is(PATH_TO_GAME) {
+                params.game_dir.map(|dir| dir.as_os_str().to_os_string())
+            } else if is(STEAM_FOLDER) {
+                params.steam_dir.map(|dir| dir.as_os_str().to_os_string())
+            } else if is(USER_ID) {
+                params.user_id.map(|id| OsString::from(id.to_string()))
+            } else if windows && (is(UBISOFT_FOLDER) || is(UPLAY_FOLDER)) {
+                Some(expand(UBISOFT_FOLDER_DEFAULT, windows, params, unresolved))
+            } else {
+                None
+            }
         }
     }
+}
 
-    if let Some(i) = path.find(STEAM_FOLDER) {
-        replacement_locations.insert(i, STEAM_FOLDER);
-        replacement_data.insert(STEAM_FOLDER, params.install_dir.as_os_str().to_os_string());
+/// Replaces every token in `path`, adding the ones that could not be resolved to `unresolved`.
+fn expand(
+    path: &str,
+    windows: bool,
+    params: ExpansionParams,
+    unresolved: &mut Vec<String>,
+) -> OsString {
+    let mut buf = OsString::new();
+    let mut rest = path;
+    if !windows
+        && let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty() || after.starts_with('/'))
+    {
+        resolve_into(&Token::Home, "~", windows, params, unresolved, &mut buf);
+        rest = after;
     }
-
-    if let Some(i) = path.find(USER_ID) {
-        replacement_locations.insert(i, USER_ID);
-        replacement_data.insert(USER_ID, OsString::from(params.user_id.to_string()));
+    while let Some(c) = rest.chars().next() {
+        if let Some((token, len)) = next_token(rest, windows) {
+            resolve_into(&token, &rest[..len], windows, params, unresolved, &mut buf);
+            rest = &rest[len..];
+        } else {
+            buf.push(&rest[..c.len_utf8()]);
+            rest = &rest[c.len_utf8()..];
+        }
     }
+    buf
+}
 
-    let mut buf = OsString::new();
-    let mut i = 0;
-    while i < path.len() {
-        if let Some(var_key) = replacement_locations.get(&i)
-            && let Some(var_val) = replacement_data.get(var_key)
-        {
-            buf.push(var_val);
-            i += var_key.len();
-            continue;
+fn resolve_into(
+    token: &Token,
+    text: &str,
+    windows: bool,
+    params: ExpansionParams,
+    unresolved: &mut Vec<String>,
+    buf: &mut OsString,
+) {
+    match resolve_token(token, windows, params, unresolved) {
+        Some(value) => buf.push(value),
+        None => {
+            // Windows placeholders differing only in case are reported once
+            if !unresolved
+                .iter()
+                .any(|token| token == text || windows && token.eq_ignore_ascii_case(text))
+            {
+                unresolved.push(text.to_string());
+            }
+            buf.push(text);
         }
-        buf.push(&path[i..=i]);
-        i += 1;
     }
+}
+
+/// Replaces every ASCII case-insensitive occurrence of `from`.
+pub(super) fn replace_ignore_case(text: &str, from: &str, to: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let from = from.to_ascii_lowercase();
+    let mut replaced = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, _) in lower.match_indices(&from) {
+        replaced.push_str(&text[last..i]);
+        replaced.push_str(to);
+        last = i + from.len();
+    }
+    replaced.push_str(&text[last..]);
+    replaced
+}
 
-    Some(PathBuf::from(buf))
+/// Normalizes a Windows registry key, e.g. `HKCU/Software/Game` to
+/// `HKEY_CURRENT_USER\Software\Game`. Returns `None` if `path` is not a registry key.
+fn registry_key(path: &str) -> Option<String> {
+    let (hive, key) = path.split_once(['\\', '/']).unwrap_or((path, ""));
+    let &(_, hive) = REGISTRY_HIVES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(hive))?;
+    let key = key.replace('/', "\\");
+    let key = key.trim_end_matches('\\');
+    Some(if key.is_empty() {
+        hive.to_string()
+    } else {
+        format!("{hive}\\{key}")
+    })
 }
 
-fn fetch_section_id(api: &ApiSync, page: &str, section_line: &str) -> Result<String, PCGWError> {
-    let params = api.params_into(&[("action", "parse"), ("page", page), ("prop", "sections")]);
+/// Expands a path from <https://www.pcgamingwiki.com/wiki/Glossary:Game_data> for `os`, which
+/// defaults to the current one and takes values of `std::env::consts::OS`. Every occurrence of a
+/// placeholder is replaced, Windows variables are matched case-insensitively and environment
+/// variables are read as `OsString`s, so non-Unicode values are kept.
+///
+/// Registry keys are not expanded and returned as `ExpandedPath::Registry`. Paths with `*` or `?`
+/// wildcards are returned as `ExpandedPath::Pattern`.
+pub fn replace_path_abbrs(
+    path: &str,
+    os: Option<&str>,
+    params: ExpansionParams,
+) -> Result<ExpandedPath, ExpansionError> {
+    let windows = os.unwrap_or(env::consts::OS) == "windows";
+    let path = path.trim();
 
-    let res = api.get_query_api_json_all(&params)?;
+    if windows && let Some(key) = registry_key(path) {
+        return Ok(ExpandedPath::Registry(key));
+    }
 
-    res["parse"]["sections"]
-        .as_array()
-        .ok_or(PCGWError::NotFound)?
-        .iter()
-        .find_map(|section_val| {
-            section_val["line"]
-                .as_str()
-                .filter(|line| section_line == *line)
-                .and(section_val["index"].as_str())
-        })
-        .map(str::to_string)
-        .ok_or(PCGWError::ParseError)
+    let mut unresolved = Vec::new();
+    let expanded = PathBuf::from(expand(path, windows, params, &mut unresolved));
+    if !unresolved.is_empty() {
+        return Err(ExpansionError {
+            path: path.to_string(),
+            tokens: unresolved,
+        });
+    }
+    debug!("expanded `{path}` to {}", expanded.display());
+
+    // `%PROGRAMFILES%` is `Program Files (x86)` for 32-bit games on 64-bit Windows
+    const PROGRAM_FILES: &str = "%PROGRAMFILES%";
+    if windows
+        && let Some(i) = path.to_ascii_uppercase().find(PROGRAM_FILES)
+        && !matches!(fs::exists(&expanded), Ok(true))
+    {
+        let x86 = format!(
+            "{}%PROGRAMFILES(X86)%{}",
+            &path[..i],
+            &path[i + PROGRAM_FILES.len()..]
+        );
+        let x86_expanded = PathBuf::from(expand(&x86, windows, params, &mut unresolved));
+        if matches!(fs::exists(&x86_expanded), Ok(true)) {
+            info!("using 32-bit program files for `{path}`");
+            return Ok(ExpandedPath::Path(x86_expanded));
+        }
+    }
+
+    if path.contains(['*', '?']) {
+        Ok(ExpandedPath::Pattern(expanded))
+    } else {
+        Ok(ExpandedPath::Path(expanded))
+    }
 }
-fn section_html(api: &ApiSync, page: &str, section_line: &str) -> Result<Html, PCGWError> {
-    let section_id = fetch_section_id(api, page, section_line)?;
 
-    let params = api.params_into(&[
-        ("action", "parse"),
-        ("page", page),
-        ("prop", "text"),
-        ("section", &section_id),
-    ]);
+fn page_html(api: &impl WikiClient, page: &str, game: GameId) -> Result<Html, PCGWError> {
+    let params = [("action", "parse"), ("page", page), ("prop", "text")];
 
-    let res = api.get_query_api_json_all(&params).unwrap();
+    let res = query(api, &params, game)?;
 
     res["parse"]["text"]["*"]
         .as_str()
         .map(Html::parse_fragment)
-        .ok_or(PCGWError::NotFound)
+        .ok_or(PCGWError::MalformedResponse("parsed page has no text"))
 }
 
-fn page_html(api: &ApiSync, page: &str) -> Result<Html, PCGWError> {
-    let params = api.params_into(&[("action", "parse"), ("page", page), ("prop", "text")]);
+/// Looks for a thumbs-up or thumbs-down icon inside of `el`. Thumbs-down wins if both are present.
+fn extract_reliability(el: ElementRef) -> Option<Reliability> {
+    let mut reliability = None;
+    for descendant in el.descendants().filter_map(ElementRef::wrap) {
+        let value = descendant.value();
+        let markers = value
+            .classes()
+            .chain(
+                ["src", "alt", "title"]
+                    .iter()
+                    .filter_map(|attr| value.attr(attr)),
+            )
+            .map(|marker| marker.to_lowercase().replace(['_', ' '], "-"));
+        for marker in markers {
+            if marker.contains("thumbs-down") {
+                return Some(Reliability::Unreliable);
+            } else if marker.contains("thumbs-up") {
+                reliability = Some(Reliability::Reliable);
+            }
+        }
+    }
+    reliability
+}
 
-    let res = api.get_query_api_json_all(&params).unwrap();
+/// Renders the contents of `el` as plain text, keeping link text but dropping link targets.
+fn html_to_text(el: ElementRef) -> Result<String, PCGWError> {
+    let html = el.inner_html();
+    let text = html2text::config::with_decorator(TrivialDecorator::new())
+        .string_from_read(html.as_bytes(), html.len().max(1))
+        .inspect_err(|err| warn!("failed to render HTML as text: {err}"))?;
+    Ok(text.trim().to_string())
+}
 
-    res["parse"]["text"]["*"]
-        .as_str()
-        .map(Html::parse_fragment)
-        .ok_or(PCGWError::NotFound)
+/// Renders `el` to plain text and checks it for a reliability icon.
+fn extract_note(el: ElementRef) -> Result<Note, PCGWError> {
+    Ok(Note {
+        text: html_to_text(el)?,
+        reliability: extract_reliability(el),
+    })
 }
 
-fn extract_raw_location(el: ElementRef, notes: &HashMap<&str, String>) -> Location {
-    let mut note = None;
+fn extract_raw_location(el: ElementRef, notes: &HashMap<&str, Note>) -> Location {
+    let anchor_selector = Selector::parse("a[href]").expect("str should be a valid selector");
+    let mut location_notes = Vec::new();
     let path_str = el
         .children()
         .fold(String::new(), |mut path_str, child| match child.value() {
@@ -272,14 +433,15 @@ fn extract_raw_location(el: ElementRef, notes: &HashMap<&str, String>) -> Locati
                 path_str
             }
             Node::Element(child_el) if child_el.name() == "sup" => {
-                let note_id = child_el
-                    .attr("id")
-                    .and_then(|id| format_id(id, HtmlIdTy::CiteRef))
-                    .map(|id| id.to_string());
+                // The link points at the note's element ID, e.g. `#cite_note-Steam-2`
+                let note_id = ElementRef::wrap(child)
+                    .and_then(|sup| sup.select(&anchor_selector).next())
+                    .and_then(|a| a.value().attr("href"))
+                    .and_then(|href| href.strip_prefix('#'));
                 debug!("note #id: `{note_id:?}`");
-                note = note_id
-                    .and_then(|note_id| notes.get(note_id.as_str()))
-                    .map(|note| note.to_string());
+                if let Some(note) = note_id.and_then(|note_id| notes.get(note_id)) {
+                    location_notes.push(note.clone());
+                }
                 path_str
             }
             Node::Text(child_text) => {
@@ -291,53 +453,86 @@ fn extract_raw_location(el: ElementRef, notes: &HashMap<&str, String>) -> Locati
         });
     debug!("finished building location");
     debug!("completed path: `{path_str:?}`");
-    debug!("note: `{note:?}`");
-    Location::new(path_str, note)
+    debug!("notes: `{location_notes:?}`");
+    Location::new(path_str, location_notes)
 }
 
-fn parse_data_table(
-    section_html: &Html,
-    notes: HashMap<&str, String>,
-) -> Result<HashMap<LocationKind, Vec<Location>>, PCGWError> {
+const GAME_DATA_HEADER: &str = "Game data";
+// Headlines of the tables in the "Game data" section
+const CONFIG_TABLE_HEADER: &str = "Configuration file(s) location";
+const SAVE_TABLE_HEADER: &str = "Save game data location";
+const CLOUD_SYNC_TABLE_HEADER: &str = "Save game cloud syncing";
+/// Name used for the footnote list in errors.
+const NOTES_TABLE: &str = "Notes";
+
+/// Returns the text of a heading's `.mw-headline`, if `el` is a heading of the given level.
+fn headline<'a>(el: ElementRef<'a>, level: &str) -> Option<&'a str> {
+    let header_selector = Selector::parse(".mw-headline").expect("str should be a valid selector");
+    (el.value().name() == level)
+        .then(|| el.select(&header_selector).next()?.text().next())
+        .flatten()
+}
+
+/// Collects the elements between the `h2` headline `header` and the next `h2`.
+fn section_elements<'a>(
+    page: &str,
+    page_html: &'a Html,
+    header: &'static str,
+) -> Result<Vec<ElementRef<'a>>, PCGWError> {
     let parser_output_selector =
         Selector::parse(".mw-parser-output").expect("str should be a valid selector");
-    let header_selector = Selector::parse(".mw-headline").expect("str should be a valid selector");
-    let mut parser_output_iter = section_html
+    let mut elements = page_html
         .select(&parser_output_selector)
         .exactly_one()
-        .map_err(|_| PCGWError::NotFound)?
-        .child_elements();
-    let mut save_table: Result<ElementRef, PCGWError> = Err(PCGWError::NotFound);
-    while let Some(el) = parser_output_iter.next() {
-        if el.value().name() == "h3"
-            && el
-                .select(&header_selector)
-                .exactly_one()
-                .map_err(|_| PCGWError::ParseError)?
-                .text()
-                .next()
-                .ok_or(PCGWError::ParseError)?
-                == "Save game data location"
-        {
-            save_table = Ok(parser_output_iter.next().ok_or(PCGWError::ParseError)?);
-            break;
-        }
+        .map_err(|_| PCGWError::MalformedResponse("page has no single parser output"))?
+        .child_elements()
+        .skip_while(|el| headline(*el, "h2") != Some(header));
+    if elements.next().is_none() {
+        return Err(PCGWError::SectionNotFound {
+            page: page.to_string(),
+            section: header,
+        });
     }
-    // FIXME: extra notes are not avaliable in section thing
-    // TODO: thumbs down/Thumbs up display, convert HTML with library
-    // let extra_notes = parser_output_iter
-    //     .map_while(|el| {
-    //         (el.value().name() == "dl").then_some(
-    //             el.child_elements()
-    //                 .exactly_one()
-    //                 .map_err(|_| PCGWError::ParseError),
-    //         ).inspect(|val| println!("it was {val:?}"))
-    //     })
-    //     .fold_ok(Vec::new(), |mut acc, el| {
-    //         acc.push(el.html());
-    //         acc
-    //     })?;
+    Ok(elements
+        .take_while(|el| el.value().name() != "h2")
+        .collect())
+}
+
+/// Finds the table that follows the `h3` headline `header`, along with the notes listed right
+/// below it. Returns `Ok(None)` if the section has no such headline.
+fn find_table<'a>(
+    page: &str,
+    section: &[ElementRef<'a>],
+    header: &'static str,
+) -> Result<Option<(ElementRef<'a>, Vec<ElementRef<'a>>)>, PCGWError> {
+    let Some(i) = section
+        .iter()
+        .position(|el| headline(*el, "h3") == Some(header))
+    else {
+        return Ok(None);
+    };
+    let table = *section
+        .get(i + 1)
+        .filter(|el| el.value().name() != "h3")
+        .ok_or_else(|| PCGWError::TableNotFound {
+            page: page.to_string(),
+            table: header,
+        })?;
+    let notes = section[i + 2..]
+        .iter()
+        .take_while(|el| el.value().name() == "dl")
+        .flat_map(|dl| dl.child_elements().filter(|el| el.value().name() == "dd"))
+        .collect();
+    Ok(Some((table, notes)))
+}
 
+/// Parses a "Configuration file(s) location" or "Save game data location" table.
+fn parse_location_table(
+    page: &str,
+    table: ElementRef,
+    header: &'static str,
+    notes: &HashMap<&str, Note>,
+) -> Result<HashMap<LocationKind, Vec<Location>>, PCGWError> {
     let row_selector =
         Selector::parse(".table-gamedata-body-row").expect("str should be a valid selector");
     let os_selector =
@@ -347,53 +542,171 @@ fn parse_data_table(
     let infotable_path_selector =
         Selector::parse(".template-infotable-monospace").expect("str should be valid selector");
 
-    let locations = save_table?
+    table
         .select(&row_selector)
-        .filter_map(|row| {
-            let location_el = row.select(&location_selector).next()?;
-            let os = row.select(&os_selector).exactly_one().ok()?;
-            let os_first_child = os.children().next()?;
-            // FIXME: Steam Play path is generated later
-            let os_name = if let Some(child) = os_first_child.value().as_element()
-                && child.name() == "abbr"
-            {
-                Some(os_first_child.children().next()?.value().as_text()?.trim())
-            } else {
-                os_first_child
-                    .value()
-                    .as_text()
-                    .map(|os_name| os_name.trim())
-            }?;
-            let location_kind = match os_name {
-                "Steam" => LocationKind::Steam,
-                other => LocationKind::OS(other.to_string()),
+        .enumerate()
+        .map(|(i, row)| {
+            let malformed = |reason| PCGWError::MalformedRow {
+                page: page.to_string(),
+                table: header,
+                row: i + 1,
+                reason,
             };
+            let location_el = row
+                .select(&location_selector)
+                .next()
+                .ok_or_else(|| malformed("no location cell"))?;
+            let os = row
+                .select(&os_selector)
+                .exactly_one()
+                .map_err(|_| malformed("expected exactly one system cell"))?;
+            // FIXME: Steam Play path is generated later
+            let os_name = os
+                .children()
+                .next()
+                .and_then(|os_first_child| {
+                    if let Some(child) = os_first_child.value().as_element()
+                        && child.name() == "abbr"
+                    {
+                        Some(os_first_child.children().next()?.value().as_text()?.trim())
+                    } else {
+                        os_first_child
+                            .value()
+                            .as_text()
+                            .map(|os_name| os_name.trim())
+                    }
+                })
+                .ok_or_else(|| malformed("system cell has no name"))?;
+            let location_kind = LocationKind::from(os_name);
             let locations = location_el
                 .select(&infotable_path_selector)
-                .map(|location_el| extract_raw_location(location_el, &notes))
+                .map(|location_el| extract_raw_location(location_el, notes))
                 .collect();
 
-            Some((location_kind, locations))
+            Ok((location_kind, locations))
         })
-        .collect();
+        .collect()
+}
+
+/// Maps the rating icon of a cloud sync row, e.g. `<div class="svg-icon tickcross-true">`.
+fn parse_cloud_sync_support(rating: ElementRef) -> CloudSyncSupport {
+    let icon_selector = Selector::parse(".svg-icon").expect("str should be a valid selector");
+    let Some(icon) = rating.select(&icon_selector).next() else {
+        return CloudSyncSupport::Unknown;
+    };
+    let rating = icon
+        .value()
+        .classes()
+        .find_map(|class| class.strip_prefix("tickcross-"))
+        .or_else(|| icon.value().attr("title"));
+    match rating {
+        Some("true") => CloudSyncSupport::Supported,
+        Some("false") => CloudSyncSupport::Unsupported,
+        Some("limited") => CloudSyncSupport::Limited,
+        Some("hackable") => CloudSyncSupport::Hackable,
+        _ => CloudSyncSupport::Unknown,
+    }
+}
+
+/// Parses the "Save game cloud syncing" table.
+fn parse_cloud_sync_table(page: &str, table: ElementRef) -> Result<Vec<CloudSync>, PCGWError> {
+    let row_selector =
+        Selector::parse(".table-cloudsync-body-row").expect("str should be a valid selector");
+    let system_selector =
+        Selector::parse(".table-cloudsync-body-system").expect("str should be a valid selector");
+    let rating_selector =
+        Selector::parse(".table-cloudsync-body-rating").expect("str should be a valid selector");
+    let notes_selector =
+        Selector::parse(".table-cloudsync-body-notes").expect("str should be a valid selector");
+
+    table
+        .select(&row_selector)
+        .enumerate()
+        .map(|(i, row)| {
+            let system = row
+                .select(&system_selector)
+                .exactly_one()
+                .map_err(|_| PCGWError::MalformedRow {
+                    page: page.to_string(),
+                    table: CLOUD_SYNC_TABLE_HEADER,
+                    row: i + 1,
+                    reason: "expected exactly one system cell",
+                })?
+                .text()
+                .collect::<String>();
+            let support = row
+                .select(&rating_selector)
+                .next()
+                .map_or(CloudSyncSupport::Unknown, parse_cloud_sync_support);
+            let note = match row.select(&notes_selector).next() {
+                Some(notes_el) if notes_el.text().any(|text| !text.trim().is_empty()) => {
+                    Some(html_to_text(notes_el)?)
+                }
+                _ => None,
+            };
+            Ok(CloudSync {
+                system: CloudSyncSystem::from(system.trim()),
+                support,
+                note,
+            })
+        })
+        .collect()
+}
+
+fn parse_game_data(
+    page: &str,
+    page_html: &Html,
+    notes: HashMap<&str, Note>,
+) -> Result<PCGWSaveMeta, PCGWError> {
+    let section = section_elements(page, page_html, GAME_DATA_HEADER)?;
+    let (config_locations, mut extra_notes) = match find_table(page, &section, CONFIG_TABLE_HEADER)?
+    {
+        Some((table, config_notes)) => (
+            parse_location_table(page, table, CONFIG_TABLE_HEADER, &notes)?,
+            config_notes,
+        ),
+        None => (HashMap::new(), Vec::new()),
+    };
+    let (save_table, save_notes) =
+        find_table(page, &section, SAVE_TABLE_HEADER)?.ok_or_else(|| PCGWError::TableNotFound {
+            page: page.to_string(),
+            table: SAVE_TABLE_HEADER,
+        })?;
+    extra_notes.extend(save_notes);
+    let cloud_sync = match find_table(page, &section, CLOUD_SYNC_TABLE_HEADER)? {
+        Some((table, cloud_sync_notes)) => {
+            extra_notes.extend(cloud_sync_notes);
+            parse_cloud_sync_table(page, table)?
+        }
+        None => Vec::new(),
+    };
 
-    Ok(locations)
+    Ok(PCGWSaveMeta {
+        save_locations: parse_location_table(page, save_table, SAVE_TABLE_HEADER, &notes)?,
+        config_locations,
+        cloud_sync,
+        extra_notes: extra_notes
+            .into_iter()
+            .map(extract_note)
+            .collect::<Result<_, _>>()?,
+    })
 }
 
-pub(super) fn get_location_data(
-    api: &ApiSync,
+pub(super) fn get_game_data(
+    api: &impl WikiClient,
     steam_id: GameId,
-) -> Result<HashMap<LocationKind, Vec<Location>>, PCGWError> {
-    // TODO: different errors for different steps
+) -> Result<PCGWSaveMeta, PCGWError> {
     let page = fetch_page_by_id(api, steam_id)?;
-    let section_html = section_html(api, &page, "Game data")?;
-    let page_html = page_html(api, &page)?;
-    let notes = extract_notes(&page_html)?;
+    // Element IDs differ when requesting a single section, so notes can only be matched against
+    // the full page
+    let page_html = page_html(api, &page, steam_id)?;
+    let notes = extract_notes(&page, &page_html)?;
 
-    parse_data_table(&section_html, notes)
+    parse_game_data(&page, &page_html, notes)
 }
 
-fn extract_notes(page_html: &Html) -> Result<HashMap<&str, String>, PCGWError> {
+/// Maps every footnote's element ID (e.g. `cite_note-Steam-2`) to its contents.
+fn extract_notes<'a>(page: &str, page_html: &'a Html) -> Result<HashMap<&'a str, Note>, PCGWError> {
     let reference_notes_selector =
         Selector::parse("#pcgw-references-notes").expect("str should be a valid selector");
     let reference_list_selector =
@@ -406,27 +719,25 @@ fn extract_notes(page_html: &Html) -> Result<HashMap<&str, String>, PCGWError> {
         .exactly_one()
         .ok()
         .and_then(|el| el.select(&reference_list_selector).exactly_one().ok());
-    // TODO: also save extra note below the table, detect thumbs down or thumbs up
-    // NOTE: if there is more than one note that have no names, they will get overwritten and only the last one will save.
-    // This is a limitation caused by sections have different element IDs versus the full parsed page.
     let notes = match notes_list {
         Some(notes_list) => notes_list
             .children()
             .filter_map(ElementRef::wrap)
-            .map(|el| -> Result<(&str, String), PCGWError> {
+            .enumerate()
+            .map(|(i, el)| -> Result<(&str, Note), PCGWError> {
+                let malformed = |reason| PCGWError::MalformedRow {
+                    page: page.to_string(),
+                    table: NOTES_TABLE,
+                    row: i + 1,
+                    reason,
+                };
                 let reference_text_el = el
                     .select(&reference_text_selector)
                     .exactly_one()
-                    .map_err(|_| PCGWError::ParseError)?;
-                let reference_text_html = reference_text_el.html();
+                    .map_err(|_| malformed("expected exactly one reference text"))?;
                 Ok((
-                    format_id(
-                        el.value().id().ok_or(PCGWError::ParseError)?,
-                        HtmlIdTy::CiteNote,
-                    )
-                    .ok_or(PCGWError::ParseError)?,
-                    html2text::from_read(reference_text_html.as_bytes(), reference_text_html.len())
-                        .inspect_err(|err| println!("error is {err}"))?,
+                    el.value().id().ok_or_else(|| malformed("note has no ID"))?,
+                    extract_note(reference_text_el)?,
                 ))
             })
             .collect::<Result<HashMap<_, _>, _>>()?,
@@ -436,163 +747,5 @@ fn extract_notes(page_html: &Html) -> Result<HashMap<&str, String>, PCGWError> {
     Ok(notes)
 }
 
-// TODO: Separate tests
 #[cfg(test)]
-mod tests {
-    use serial_test::serial;
-
-    use super::*;
-
-    #[test]
-    fn test_format_id() {
-        // 1    - Template-generated Proton path from website (8/29/25)
-        // 2-3  - Undertale Linux notes (8/29/25)
-        // 4    - My Summer Car Unity note (8/29/25)
-        let (sample_ref_1, sample_note_1) = (
-            "cite_ref-Proton_path_note_4-0",
-            "cite_note-Proton_path_note-4",
-        );
-        let (sample_ref_2, sample_note_2) = ("cite_ref-Steam_old_3-0", "cite_note-Steam_old-3");
-        let (sample_ref_3, sample_note_3) = ("cite_ref-Steam_2-0", "cite_note-Steam-2");
-        let (sample_ref_4, sample_note_4) = ("cite_ref-2", "cite_note-2");
-        let correct1 = "Proton_path_note";
-        let correct2 = "Steam_old";
-        let correct3 = "Steam";
-        let correct4 = "";
-
-        assert_eq!(format_id(sample_ref_1, HtmlIdTy::CiteRef), Some(correct1));
-        assert_eq!(format_id(sample_ref_2, HtmlIdTy::CiteRef), Some(correct2));
-        assert_eq!(format_id(sample_ref_3, HtmlIdTy::CiteRef), Some(correct3));
-        assert_eq!(format_id(sample_ref_4, HtmlIdTy::CiteRef), Some(correct4));
-        assert_eq!(format_id(sample_note_1, HtmlIdTy::CiteNote), Some(correct1));
-        assert_eq!(format_id(sample_note_2, HtmlIdTy::CiteNote), Some(correct2));
-        assert_eq!(format_id(sample_note_3, HtmlIdTy::CiteNote), Some(correct3));
-        assert_eq!(format_id(sample_note_4, HtmlIdTy::CiteNote), Some(correct4));
-    }
-
-    const LOCALAPPDATA: &str = "C:\\Users\\matheus\\AppData\\Local";
-    const HOME_MAC: &str = "/Users/matheus";
-    const HOME_LINUX: &str = "/home/matheus";
-    const STEAM_FOLDER: &str = "/home/matheus/.local/share/Steam";
-    const XDG_CONFIG_HOME: &str = "/home/matheus/special/.config";
-    const USER_ID: u64 = 69;
-
-    #[test]
-    #[serial]
-    fn test_replace_path_abbrs() {
-        // Safety: #[serial] attribute should ensure this does not run concurrently
-        unsafe {
-            env::set_var("%localappdata%", LOCALAPPDATA);
-            env::set_var("HOME", HOME_MAC);
-        }
-
-        let windows_sample = r#"%LOCALAPPDATA%\UNDERTALE\"#;
-        let mac_sample = "$HOME/Library/Application Support/com.tobyfox.undertale/";
-        let linux_sample = "$HOME/.config/UNDERTALE/";
-
-        let sample4 = "<Steam-folder>/userdata/<user-id>/391540/remote/";
-        let sample5 = "$XDG_CONFIG_HOME/sample5";
-
-        let windows_correct = LOCALAPPDATA.to_string() + r#"\UNDERTALE\"#;
-        let mac_correct =
-            HOME_MAC.to_string() + "/Library/Application Support/com.tobyfox.undertale/";
-        let linux_correct = HOME_LINUX.to_string() + "/.config/UNDERTALE/";
-
-        let correct4 =
-            STEAM_FOLDER.to_string() + "/userdata/" + &USER_ID.to_string() + "/391540/remote/";
-        let correct5_undefined = HOME_LINUX.to_string() + "/.config/sample5";
-        let correct5_defined = XDG_CONFIG_HOME.to_string() + "/sample5";
-
-        assert_eq!(
-            replace_path_abbrs(
-                windows_sample,
-                Some("windows"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                },
-            ),
-            Some(windows_correct.into())
-        );
-        assert_eq!(
-            replace_path_abbrs(
-                mac_sample,
-                Some("macos"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                },
-            ),
-            Some(mac_correct.into())
-        );
-        unsafe {
-            env::set_var("HOME", HOME_LINUX);
-        }
-        assert_eq!(
-            replace_path_abbrs(
-                linux_sample,
-                Some("linux"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                },
-            ),
-            Some(linux_correct.into())
-        );
-        assert_eq!(
-            replace_path_abbrs(
-                sample4,
-                Some("linux"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                }
-            ),
-            Some(correct4.as_str().into())
-        );
-
-        unsafe {
-            env::set_var("HOME", HOME_LINUX);
-            env::set_var("XDG_CONFIG_HOME", XDG_CONFIG_HOME);
-        }
-        assert_eq!(
-            replace_path_abbrs(
-                sample5,
-                Some("linux"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                }
-            ),
-            Some(correct5_defined.into())
-        );
-
-        unsafe { env::remove_var("XDG_CONFIG_HOME") }
-        assert_eq!(
-            replace_path_abbrs(
-                sample5,
-                Some("linux"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                }
-            ),
-            Some(correct5_undefined.into())
-        );
-
-        unsafe {
-            env::remove_var("HOME");
-        }
-        assert_eq!(
-            replace_path_abbrs(
-                linux_sample,
-                Some("linux"),
-                ExpansionParams {
-                    install_dir: Path::new(STEAM_FOLDER),
-                    user_id: USER_ID
-                }
-            ),
-            None
-        );
-    }
-}
+mod tests;
@@ -0,0 +1,627 @@
+use std::{
+    fs, io,
+    time::{Duration, SystemTime},
+};
+
+use serial_test::serial;
+
+use super::*;
+
+use crate::pcgw::{api::Candidate, client::fixture::FixtureClient};
+
+fn fetch_fixture(steam_id: u32) -> Result<PCGWSaveMeta, PCGWError> {
+    get_game_data(&FixtureClient::new(), GameId::Steam(steam_id))
+}
+
+fn os(name: &str) -> LocationKind {
+    LocationKind::OS(name.to_string())
+}
+
+fn path_strs(locations: &[Location]) -> Vec<&str> {
+    locations.iter().map(Location::path_str).collect()
+}
+
+fn note_texts(notes: &[Note]) -> Vec<&str> {
+    notes.iter().map(|note| note.text.as_str()).collect()
+}
+
+#[test]
+fn test_every_fixture_parses() {
+    let client = FixtureClient::new();
+    let games: HashMap<String, String> = serde_json::from_slice(
+        &std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/pcgw/games.json"
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(games.len(), client.pages().count());
+
+    for (steam_id, page) in games {
+        let result = get_game_data(&client, GameId::Steam(steam_id.parse().unwrap()));
+        match page.as_str() {
+            "Source SDK Base 2013 Multiplayer" => assert!(
+                matches!(result, Err(PCGWError::SectionNotFound { .. })),
+                "{page}: {result:?}"
+            ),
+            "Counter-Strike 2" | "Dark Souls III" => assert!(
+                matches!(
+                    result,
+                    Err(PCGWError::TableNotFound {
+                        table: SAVE_TABLE_HEADER,
+                        ..
+                    })
+                ),
+                "{page}: {result:?}"
+            ),
+            "Red Dead Redemption 2" => assert!(
+                matches!(result, Err(PCGWError::MalformedRow { row: 1, .. })),
+                "{page}: {result:?}"
+            ),
+            "Warframe" => assert!(
+                matches!(result, Err(PCGWError::RateLimited)),
+                "{page}: {result:?}"
+            ),
+            "STALKER Shadow of Chernobyl" => assert!(
+                matches!(result, Err(PCGWError::PageNotFound(GameId::Steam(4500)))),
+                "{page}: {result:?}"
+            ),
+            _ => {
+                let meta = result.unwrap_or_else(|err| panic!("{page}: {err}"));
+                assert!(!meta.save_locations.is_empty(), "{page} has no saves");
+                assert!(!meta.cloud_sync().is_empty(), "{page} has no cloud sync");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_unknown_game_is_not_found() {
+    assert!(matches!(
+        fetch_fixture(1),
+        Err(PCGWError::PageNotFound(GameId::Steam(1)))
+    ));
+}
+
+#[test]
+fn test_parse_game_data() {
+    let mut meta = fetch_fixture(391540).unwrap();
+
+    assert_eq!(
+        path_strs(meta.get_config_locations(os("Windows"))),
+        [r#"%LOCALAPPDATA%\UNDERTALE\config.ini"#]
+    );
+    assert_eq!(
+        path_strs(meta.get_locations(os("Windows"))),
+        [r#"%LOCALAPPDATA%\UNDERTALE\"#]
+    );
+    assert_eq!(
+        path_strs(meta.get_locations(os("OS X"))),
+        ["~/Library/Application Support/com.tobyfox.undertale/"]
+    );
+    assert_eq!(
+        path_strs(meta.get_locations(os("Linux"))),
+        ["$XDG_CONFIG_HOME/UNDERTALE/"]
+    );
+
+    let cloud_sync = meta.cloud_sync();
+    assert_eq!(cloud_sync.len(), 2);
+    assert_eq!(cloud_sync[0].system, CloudSyncSystem::GogGalaxy);
+    assert_eq!(cloud_sync[0].support, CloudSyncSupport::Unsupported);
+    assert_eq!(cloud_sync[0].note, None);
+    assert_eq!(cloud_sync[1].system, CloudSyncSystem::Steam);
+    assert_eq!(
+        meta.active_cloud_sync()
+            .map(|cloud_sync| &cloud_sync.system)
+            .collect::<Vec<_>>(),
+        [&CloudSyncSystem::Steam]
+    );
+}
+
+#[test]
+fn test_notes_and_reliability() {
+    let mut meta = fetch_fixture(516750).unwrap();
+    let locations = meta.get_locations(os("Windows"));
+    assert_eq!(locations.len(), 2);
+
+    let user_profile = &locations[0];
+    assert_eq!(
+        note_texts(user_profile.notes()),
+        ["Unity games store data in LocalLow."]
+    );
+    assert_eq!(user_profile.reliability(), None);
+
+    let registry = &locations[1];
+    assert_eq!(
+        registry.path_str(),
+        r#"HKEY_CURRENT_USER\Software\Amistech\My Summer Car\"#
+    );
+    assert_eq!(
+        note_texts(registry.notes()),
+        ["Player settings only.", "Outdated since the 2016 update."]
+    );
+    assert_eq!(registry.reliability(), Some(Reliability::Unreliable));
+
+    assert_eq!(
+        meta.extra_notes(),
+        [Note {
+            text: "Saves are kept between updates.".to_string(),
+            reliability: Some(Reliability::Reliable),
+        }]
+    );
+}
+
+#[test]
+fn test_reliability_per_location() {
+    let mut meta = fetch_fixture(264710).unwrap();
+    let locations = meta.get_locations(os("Windows"));
+    assert_eq!(locations[0].reliability(), Some(Reliability::Reliable));
+    assert_eq!(locations[1].reliability(), Some(Reliability::Unreliable));
+    assert_eq!(meta.get_locations(os("OS X"))[0].reliability(), None);
+}
+
+#[test]
+fn test_shared_named_note() {
+    let mut meta = fetch_fixture(8930).unwrap();
+    let note = "Aspyr ports use a different folder than the Windows release.";
+    assert_eq!(
+        note_texts(meta.get_locations(os("OS X"))[0].notes()),
+        [note]
+    );
+    assert_eq!(
+        note_texts(meta.get_locations(os("Linux"))[0].notes()),
+        [note]
+    );
+    assert!(meta.get_locations(os("Windows"))[0].notes().is_empty());
+}
+
+#[test]
+fn test_several_paths_per_cell() {
+    let mut meta = fetch_fixture(105600).unwrap();
+    assert_eq!(
+        path_strs(meta.get_locations(os("Windows"))),
+        [
+            r#"%USERPROFILE%\Documents\My Games\Terraria\Players\"#,
+            r#"%USERPROFILE%\Documents\My Games\Terraria\Worlds\"#,
+        ]
+    );
+    assert_eq!(
+        path_strs(meta.get_locations(LocationKind::Steam)),
+        ["<Steam-folder>/userdata/<user-id>/105600/remote/"]
+    );
+    assert_eq!(
+        meta.cloud_sync()[1].note.as_deref(),
+        Some("Must be enabled per character and world, see Steam Cloud.")
+    );
+}
+
+#[test]
+fn test_system_rows() {
+    let mut meta = fetch_fixture(220).unwrap();
+    assert_eq!(
+        path_strs(meta.get_locations(os("Steam Play (Linux)"))),
+        ["<Steam-folder>/steamapps/compatdata/220/pfx/"]
+    );
+    assert_eq!(
+        path_strs(meta.get_locations(os("Windows"))),
+        [r#"<path-to-game>\hl2\save\"#]
+    );
+
+    let mut meta = fetch_fixture(367520).unwrap();
+    assert_eq!(meta.get_locations(os("Microsoft Store")).len(), 1);
+    assert_eq!(
+        path_strs(meta.get_config_locations(os("Windows"))),
+        [r#"HKEY_CURRENT_USER\Software\Team Cherry\Hollow Knight\"#]
+    );
+
+    let mut meta = fetch_fixture(620).unwrap();
+    assert_eq!(meta.save_locations.len(), 1);
+    assert_eq!(meta.get_locations(LocationKind::Steam).len(), 1);
+}
+
+#[test]
+fn test_cloud_sync_ratings() {
+    let support_of = |steam_id, system: CloudSyncSystem| {
+        fetch_fixture(steam_id)
+            .unwrap()
+            .cloud_sync()
+            .iter()
+            .find(|cloud_sync| cloud_sync.system == system)
+            .map(|cloud_sync| cloud_sync.support)
+    };
+    assert_eq!(
+        support_of(250900, CloudSyncSystem::Steam),
+        Some(CloudSyncSupport::Limited)
+    );
+    assert_eq!(
+        support_of(1145360, CloudSyncSystem::Xbox),
+        Some(CloudSyncSupport::Hackable)
+    );
+    assert_eq!(
+        support_of(1091500, CloudSyncSystem::Xbox),
+        Some(CloudSyncSupport::Unknown)
+    );
+    assert_eq!(
+        support_of(812140, CloudSyncSystem::UbisoftConnect),
+        Some(CloudSyncSupport::Supported)
+    );
+    assert_eq!(
+        support_of(292030, CloudSyncSystem::Epic),
+        Some(CloudSyncSupport::Supported)
+    );
+
+    let meta = fetch_fixture(271590).unwrap();
+    assert_eq!(
+        meta.active_cloud_sync()
+            .map(|cloud_sync| &cloud_sync.system)
+            .collect::<Vec<_>>(),
+        [&CloudSyncSystem::Other(
+            "Rockstar Games Launcher".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_notes_below_tables() {
+    let meta = fetch_fixture(4000).unwrap();
+    assert_eq!(
+        note_texts(meta.extra_notes()),
+        [
+            "Addon settings are stored with the addons.",
+            r#"Dupes are stored in garrysmod\dupes."#,
+            "Saves are portable between platforms.",
+        ]
+    );
+    assert_eq!(
+        meta.extra_notes()
+            .iter()
+            .map(|note| note.reliability)
+            .collect::<Vec<_>>(),
+        [None, None, Some(Reliability::Reliable)]
+    );
+
+    let meta = fetch_fixture(250900).unwrap();
+    assert_eq!(
+        meta.extra_notes()[0].reliability,
+        Some(Reliability::Unreliable)
+    );
+
+    let cloud_sync_notes = Html::parse_fragment(
+        r#"<div class="mw-parser-output"><h2><span class="mw-headline">Game data</span></h2><h3><span class="mw-headline">Save game data location</span></h3><table></table><dl><dd>Saves are shared.</dd></dl><h3><span class="mw-headline">Save game cloud syncing</span></h3><table></table><dl><dd>Cloud saves are off by default.</dd></dl></div>"#,
+    );
+    let meta = parse_game_data("Page", &cloud_sync_notes, HashMap::new()).unwrap();
+    assert_eq!(
+        note_texts(meta.extra_notes()),
+        ["Saves are shared.", "Cloud saves are off by default."]
+    );
+}
+
+#[test]
+fn test_last_section_before_references() {
+    let mut meta = fetch_fixture(504230).unwrap();
+    assert_eq!(meta.get_locations(os("Linux")).len(), 1);
+    assert_eq!(meta.cloud_sync().len(), 2);
+}
+
+#[test]
+fn test_parse_errors() {
+    let no_section = Html::parse_fragment(
+        r#"<div class="mw-parser-output"><h2><span class="mw-headline">Video</span></h2></div>"#,
+    );
+    assert!(matches!(
+        parse_game_data("Page", &no_section, HashMap::new()),
+        Err(PCGWError::SectionNotFound {
+            section: GAME_DATA_HEADER,
+            ..
+        })
+    ));
+
+    let no_table = Html::parse_fragment(
+        r#"<div class="mw-parser-output"><h2><span class="mw-headline">Game data</span></h2><h3><span class="mw-headline">Save game data location</span></h3></div>"#,
+    );
+    assert!(matches!(
+        parse_game_data("Page", &no_table, HashMap::new()),
+        Err(PCGWError::TableNotFound {
+            table: SAVE_TABLE_HEADER,
+            ..
+        })
+    ));
+
+    let malformed_row = Html::parse_fragment(
+        r#"<div class="mw-parser-output"><h2><span class="mw-headline">Game data</span></h2><h3><span class="mw-headline">Save game data location</span></h3><table><tr class="table-gamedata-body-row"><th class="table-gamedata-body-system">Windows</th></tr></table></div>"#,
+    );
+    assert!(matches!(
+        parse_game_data("Page", &malformed_row, HashMap::new()),
+        Err(PCGWError::MalformedRow {
+            row: 1,
+            table: SAVE_TABLE_HEADER,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_check_response() {
+    let ratelimited = serde_json::json!({ "error": { "code": "ratelimited", "info": "" } });
+    let game = GameId::Steam(1);
+    assert!(matches!(
+        check_response(ratelimited, game),
+        Err(PCGWError::RateLimited)
+    ));
+    let missing = serde_json::json!({
+        "error": { "code": "missingtitle", "info": "The page you specified doesn't exist." }
+    });
+    assert!(matches!(
+        check_response(missing, game),
+        Err(PCGWError::PageNotFound(GameId::Steam(1)))
+    ));
+    let invalid = serde_json::json!({
+        "error": { "code": "invalidtitle", "info": "Bad title \"\"." }
+    });
+    assert!(matches!(
+        check_response(invalid, game),
+        Err(PCGWError::Api { code, .. }) if code == "invalidtitle"
+    ));
+    assert!(check_response(serde_json::json!({ "parse": {} }), game).is_ok());
+    assert!(matches!(
+        PCGWError::from(mediawiki::MediaWikiError::String(
+            "Max attempts reached [MAXLAG] after 5 attempts, cumulative maxlag 25".to_string()
+        )),
+        PCGWError::RateLimited
+    ));
+}
+
+const USERPROFILE: &str = "C:\\Users\\matheus";
+const LOCALAPPDATA: &str = "C:\\Users\\matheus\\AppData\\Local";
+const HOME_MAC: &str = "/Users/matheus";
+const HOME_LINUX: &str = "/home/matheus";
+const STEAM_FOLDER: &str = "/home/matheus/.local/share/Steam";
+const GAME_FOLDER: &str = "/home/matheus/.local/share/Steam/steamapps/common/Undertale";
+const XDG_CONFIG_HOME: &str = "/home/matheus/special/.config";
+const USER_ID: u64 = 69;
+
+fn params() -> ExpansionParams<'static> {
+    ExpansionParams {
+        steam_dir: Some(Path::new(STEAM_FOLDER)),
+        user_id: Some(USER_ID),
+        game_dir: Some(Path::new(GAME_FOLDER)),
+    }
+}
+
+fn expand_for(path: &str, os: &str) -> Result<ExpandedPath, ExpansionError> {
+    replace_path_abbrs(path, Some(os), params())
+}
+
+fn expanded(path: impl Into<PathBuf>) -> Result<ExpandedPath, ExpansionError> {
+    Ok(ExpandedPath::Path(path.into()))
+}
+
+#[test]
+#[serial]
+fn test_replace_path_abbrs() {
+    // Safety: #[serial] attribute should ensure this does not run concurrently
+    unsafe {
+        env::set_var("LOCALAPPDATA", LOCALAPPDATA);
+        env::set_var("HOME", HOME_MAC);
+    }
+
+    assert_eq!(
+        expand_for(r#"%LOCALAPPDATA%\UNDERTALE\"#, "windows"),
+        expanded(LOCALAPPDATA.to_string() + r#"\UNDERTALE\"#)
+    );
+    assert_eq!(
+        expand_for(
+            "$HOME/Library/Application Support/com.tobyfox.undertale/",
+            "macos"
+        ),
+        expanded(HOME_MAC.to_string() + "/Library/Application Support/com.tobyfox.undertale/")
+    );
+    assert_eq!(
+        expand_for(
+            "~/Library/Application Support/com.tobyfox.undertale/",
+            "macos"
+        ),
+        expanded(HOME_MAC.to_string() + "/Library/Application Support/com.tobyfox.undertale/")
+    );
+    unsafe {
+        env::set_var("HOME", HOME_LINUX);
+    }
+    assert_eq!(
+        expand_for("$HOME/.config/UNDERTALE/", "linux"),
+        expanded(HOME_LINUX.to_string() + "/.config/UNDERTALE/")
+    );
+    assert_eq!(
+        expand_for("<Steam-folder>/userdata/<user-id>/391540/remote/", "linux"),
+        expanded(format!("{STEAM_FOLDER}/userdata/{USER_ID}/391540/remote/"))
+    );
+    assert_eq!(
+        expand_for("<path-to-game>/saves", "linux"),
+        expanded(GAME_FOLDER.to_string() + "/saves")
+    );
+
+    unsafe {
+        env::set_var("XDG_CONFIG_HOME", XDG_CONFIG_HOME);
+    }
+    assert_eq!(
+        expand_for("$XDG_CONFIG_HOME/sample5", "linux"),
+        expanded(XDG_CONFIG_HOME.to_string() + "/sample5")
+    );
+    unsafe { env::remove_var("XDG_CONFIG_HOME") }
+    assert_eq!(
+        expand_for("$XDG_CONFIG_HOME/sample5", "linux"),
+        expanded(HOME_LINUX.to_string() + "/.config/sample5")
+    );
+    unsafe { env::remove_var("XDG_STATE_HOME") }
+    assert_eq!(
+        expand_for("${XDG_STATE_HOME}/game", "linux"),
+        expanded(HOME_LINUX.to_string() + "/.local/state/game")
+    );
+
+    unsafe {
+        env::remove_var("HOME");
+    }
+    assert_eq!(
+        expand_for("$HOME/.config/UNDERTALE/", "linux"),
+        Err(ExpansionError {
+            path: "$HOME/.config/UNDERTALE/".to_string(),
+            tokens: vec!["$HOME".to_string()],
+        })
+    );
+}
+
+#[test]
+#[serial]
+fn test_replace_path_abbrs_windows() {
+    // Safety: #[serial] attribute should ensure this does not run concurrently
+    unsafe {
+        env::set_var("USERPROFILE", USERPROFILE);
+        env::remove_var("LOCALAPPDATA");
+        env::remove_var("APPDATA");
+    }
+
+    // Case-insensitive matching and defaults built from other variables
+    assert_eq!(
+        expand_for(r"%LocalAppData%\Game", "windows"),
+        expanded(LOCALAPPDATA.to_string() + r"\Game")
+    );
+    // Every occurrence is replaced
+    assert_eq!(
+        expand_for(r"%APPDATA%\Game;%appdata%\Game\Backup", "windows"),
+        expanded(format!(
+            r"{USERPROFILE}\AppData\Roaming\Game;{USERPROFILE}\AppData\Roaming\Game\Backup"
+        ))
+    );
+    assert_eq!(
+        expand_for(r"%USERPROFILE%\Documents\My Games\Game", "windows"),
+        expanded(USERPROFILE.to_string() + r"\Documents\My Games\Game")
+    );
+    assert_eq!(
+        expand_for(r"%USERPROFILE%\Documents\Game\*.sav", "windows"),
+        Ok(ExpandedPath::Pattern(
+            (USERPROFILE.to_string() + r"\Documents\Game\*.sav").into()
+        ))
+    );
+    assert_eq!(
+        expand_for(r"HKCU/Software/Amistech/My Summer Car/", "windows"),
+        Ok(ExpandedPath::Registry(
+            r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car".to_string()
+        ))
+    );
+    // `$` and `~` are not placeholders on Windows
+    assert_eq!(
+        expand_for(r"<path-to-game>\~$save", "windows"),
+        expanded(GAME_FOLDER.to_string() + r"\~$save")
+    );
+
+    let err = replace_path_abbrs(
+        r"%UNKNOWN%\<Epic-folder>\<user-id>\%unknown%",
+        Some("windows"),
+        ExpansionParams::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.tokens, ["%UNKNOWN%", "<Epic-folder>", "<user-id>"]);
+}
+
+#[test]
+fn test_defaults_terminate() {
+    for (vars, windows) in [(WINDOWS_VARS, true), (UNIX_VARS, false)] {
+        for (name, default) in vars {
+            if let Some(default) = default {
+                let own_token = if windows {
+                    format!("%{name}%")
+                } else {
+                    format!("${name}/")
+                };
+                assert!(
+                    !default.to_uppercase().contains(&own_token),
+                    "default of {name} would recurse"
+                );
+                let _ = expand(
+                    default,
+                    windows,
+                    ExpansionParams::default(),
+                    &mut Vec::new(),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resolve_candidates() {
+    let steam = tempfile::tempdir().unwrap();
+    let write_save = |user_id: &str, contents: &str, modified: SystemTime| {
+        let dir = steam
+            .path()
+            .join("userdata")
+            .join(user_id)
+            .join("391540/remote");
+        fs::create_dir_all(&dir).unwrap();
+        let file = fs::File::create(dir.join("file0")).unwrap();
+        io::Write::write_all(&mut &file, contents.as_bytes()).unwrap();
+        file.set_modified(modified).unwrap();
+        fs::File::open(&dir)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        dir
+    };
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let new = SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000);
+    let old_dir = write_save("111", "old", old);
+    let new_dir = write_save("222", "newer", new);
+    fs::create_dir_all(steam.path().join("userdata/333/730")).unwrap();
+
+    let location = Location::new(
+        "<Steam-folder>/userdata/<user-id>/391540/remote/".to_string(),
+        Vec::new(),
+    );
+    let params = ExpansionParams {
+        steam_dir: Some(steam.path()),
+        ..Default::default()
+    };
+    let candidates = location.resolve_for(Some("linux"), params).unwrap();
+    assert_eq!(
+        candidates,
+        [
+            Candidate {
+                path: new_dir,
+                modified: Some(new),
+                size: 5,
+            },
+            Candidate {
+                path: old_dir.clone(),
+                modified: Some(old),
+                size: 3,
+            },
+        ]
+    );
+
+    // A known id only matches its own folder
+    let params = ExpansionParams {
+        user_id: Some(111),
+        ..params
+    };
+    let candidates = location.resolve_for(Some("linux"), params).unwrap();
+    assert_eq!(
+        candidates.iter().map(|c| &c.path).collect::<Vec<_>>(),
+        [&old_dir]
+    );
+
+    let wildcard = Location::new(
+        "<Steam-folder>/userdata/*/391540/remote/fi?e0".to_string(),
+        Vec::new(),
+    );
+    assert_eq!(
+        wildcard.resolve_for(Some("linux"), params).unwrap().len(),
+        2
+    );
+    let registry = Location::new(r"HKEY_CURRENT_USER\Software\Game".to_string(), Vec::new());
+    assert!(
+        registry
+            .resolve_for(Some("windows"), params)
+            .unwrap()
+            .is_empty()
+    );
+}
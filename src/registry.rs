@@ -0,0 +1,410 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+use crate::crypt;
+
+/// Header of the `.reg` files written for every version.
+const REG_FILE_HEADER: &str = "Windows Registry Editor Version 5.00";
+
+/// Registry hives that Wine stores in a prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hive {
+    CurrentUser,
+    LocalMachine,
+}
+
+impl Hive {
+    fn name(self) -> &'static str {
+        match self {
+            Hive::CurrentUser => "HKEY_CURRENT_USER",
+            Hive::LocalMachine => "HKEY_LOCAL_MACHINE",
+        }
+    }
+    /// File of the hive in a Wine prefix.
+    fn wine_file(self) -> &'static str {
+        match self {
+            Hive::CurrentUser => "user.reg",
+            Hive::LocalMachine => "system.reg",
+        }
+    }
+}
+
+/// A Windows registry key, e.g. `HKEY_CURRENT_USER\Software\Game`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryKey {
+    hive: Hive,
+    /// Path below the hive, separated by single backslashes
+    path: String,
+}
+
+impl RegistryKey {
+    #[inline]
+    pub fn hive(&self) -> Hive {
+        self.hive
+    }
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Returns `true` if `path` is this key or one of its subkeys. Registry keys are
+    /// case-insensitive.
+    fn contains(&self, hive: Hive, path: &str) -> bool {
+        let own = self.path.to_lowercase();
+        let path = path.to_lowercase();
+        hive == self.hive
+            && (path == own || path.starts_with(&own) && path[own.len()..].starts_with('\\'))
+    }
+}
+
+impl FromStr for RegistryKey {
+    type Err = anyhow::Error;
+    fn from_str(key: &str) -> Result<Self> {
+        let key = key.trim().replace('/', "\\");
+        let (hive, path) = key.split_once('\\').unwrap_or((&key, ""));
+        let hive = match hive.to_uppercase().as_str() {
+            "HKEY_CURRENT_USER" | "HKCU" => Hive::CurrentUser,
+            "HKEY_LOCAL_MACHINE" | "HKLM" => Hive::LocalMachine,
+            _ => bail!("unsupported registry hive `{hive}`"),
+        };
+        let path = path.trim_matches('\\');
+        if path.is_empty() {
+            bail!("refusing to use the whole `{}` hive", hive.name());
+        }
+        Ok(Self {
+            hive,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for RegistryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\\{}", self.hive.name(), self.path)
+    }
+}
+
+/// A key with its values, in the syntax of Wine's registry files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Section {
+    /// Path below the hive, separated by single backslashes
+    path: String,
+    /// Value lines, without Wine's metadata lines
+    values: Vec<String>,
+}
+
+/// Contents of a `user.reg` or `system.reg` file of a Wine prefix. Keys are relative to the hive
+/// and backslashes in their names are escaped.
+#[derive(Debug)]
+struct WineRegistry {
+    header: String,
+    /// Raw text of every section
+    sections: Vec<(String, String)>,
+}
+
+impl WineRegistry {
+    fn parse(text: &str) -> Self {
+        let mut header = String::new();
+        let mut sections: Vec<(String, String)> = Vec::new();
+        for line in text.split_inclusive('\n') {
+            if line.starts_with('[')
+                && let Some(end) = line.rfind(']')
+            {
+                sections.push((line[1..end].replace("\\\\", "\\"), line.to_string()));
+            } else if let Some((_, raw)) = sections.last_mut() {
+                raw.push_str(line);
+            } else {
+                header.push_str(line);
+            }
+        }
+        Self { header, sections }
+    }
+    /// Sections of `key` and its subkeys.
+    fn extract(&self, hive: Hive, key: &RegistryKey) -> Vec<Section> {
+        self.sections
+            .iter()
+            .filter(|(path, _)| key.contains(hive, path))
+            .map(|(path, raw)| Section {
+                path: path.clone(),
+                values: raw
+                    .lines()
+                    .skip(1)
+                    .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+            .collect()
+    }
+    /// Replaces the sections of `key` and its subkeys with `sections`.
+    fn replace(&mut self, hive: Hive, key: &RegistryKey, sections: &[Section]) {
+        self.sections.retain(|(path, _)| !key.contains(hive, path));
+        let modified = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        for section in sections {
+            let mut raw = format!("[{}] {modified}\n", section.path.replace('\\', "\\\\"));
+            for line in &section.values {
+                raw.push_str(line);
+                raw.push('\n');
+            }
+            raw.push('\n');
+            self.sections.push((section.path.clone(), raw));
+        }
+    }
+}
+
+impl fmt::Display for WineRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.header)?;
+        for (_, raw) in &self.sections {
+            f.write_str(raw)?;
+            if !raw.ends_with("\n\n") {
+                f.write_str("\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `.reg` file written by [`WineRegistryKeys::export`].
+fn parse_reg_file(text: &str) -> Result<Vec<(Hive, Section)>> {
+    let mut lines = text.lines();
+    if lines
+        .next()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        != Some(REG_FILE_HEADER)
+    {
+        bail!("missing `{REG_FILE_HEADER}` header");
+    }
+    let mut sections: Vec<(Hive, Section)> = Vec::new();
+    for line in lines {
+        if let Some(key) = line.strip_prefix('[') {
+            let key: RegistryKey = key.trim_end().trim_end_matches(']').parse()?;
+            sections.push((
+                key.hive,
+                Section {
+                    path: key.path,
+                    values: Vec::new(),
+                },
+            ));
+        } else if !line.trim().is_empty() {
+            let Some((_, section)) = sections.last_mut() else {
+                bail!("value outside of a key: {line}");
+            };
+            section.values.push(line.to_string());
+        }
+    }
+    Ok(sections)
+}
+
+/// Registry keys of a game that live in a Wine prefix, e.g. a Proton prefix in
+/// `steamapps/compatdata/<app id>/pfx`. Wine must not be running while keys are imported, since
+/// it overwrites the registry files when it exits.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WineRegistryKeys {
+    prefix: PathBuf,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    keys: Vec<RegistryKey>,
+}
+
+impl WineRegistryKeys {
+    pub fn new(prefix: PathBuf, keys: Vec<RegistryKey>) -> Self {
+        Self { prefix, keys }
+    }
+    #[inline]
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+    #[inline]
+    pub fn keys(&self) -> &[RegistryKey] {
+        &self.keys
+    }
+    fn hive_path(&self, hive: Hive) -> PathBuf {
+        self.prefix.join(hive.wine_file())
+    }
+    fn read_hive(&self, hive: Hive) -> Result<WineRegistry> {
+        let path = self.hive_path(hive);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read registry {}", path.display()))?;
+        Ok(WineRegistry::parse(&text))
+    }
+    /// Returns the keys and their subkeys from the prefix as the contents of a `.reg` file. Values
+    /// keep Wine's syntax.
+    pub fn export(&self) -> Result<String> {
+        let mut reg = format!("{REG_FILE_HEADER}\n\n");
+        for key in &self.keys {
+            for section in self.read_hive(key.hive)?.extract(key.hive, key) {
+                reg.push_str(&format!("[{}\\{}]\n", key.hive.name(), section.path));
+                for line in section.values {
+                    reg.push_str(&line);
+                    reg.push('\n');
+                }
+                reg.push('\n');
+            }
+        }
+        Ok(reg)
+    }
+    /// Replaces the keys in the prefix with the ones in `reg`, the contents of a `.reg` file. Keys
+    /// that are missing in `reg` are deleted.
+    pub fn import(&self, reg: &str) -> Result<()> {
+        let sections = parse_reg_file(reg)?;
+        for hive in [Hive::CurrentUser, Hive::LocalMachine] {
+            let keys: Vec<_> = self.keys.iter().filter(|key| key.hive == hive).collect();
+            if keys.is_empty() {
+                continue;
+            }
+            let mut registry = self.read_hive(hive)?;
+            for key in keys {
+                let key_sections: Vec<_> = sections
+                    .iter()
+                    .filter(|(section_hive, section)| key.contains(*section_hive, &section.path))
+                    .map(|(_, section)| section.clone())
+                    .collect();
+                registry.replace(hive, key, &key_sections);
+            }
+            // Written next to the original first, so that a failure cannot truncate it
+            let path = self.hive_path(hive);
+            let tmp_path = path.with_extension("reg.vittusave");
+            fs::write(&tmp_path, registry.to_string())?;
+            fs::rename(&tmp_path, &path)?;
+        }
+        Ok(())
+    }
+    /// Exports the keys to a `.reg` file at `path`.
+    pub fn export_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.export()?)?;
+        Ok(())
+    }
+    /// Imports the keys from a `.reg` file at `path`, or deletes them if it does not exist.
+    /// Encrypted files are decrypted with the key of the session.
+    pub fn import_from(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let reg = if fs::exists(path)? {
+            String::from_utf8(crypt::read_file(path)?)?
+        } else {
+            format!("{REG_FILE_HEADER}\n")
+        };
+        self.import(&reg)
+            .with_context(|| format!("failed to import registry from {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/registry");
+
+    /// Copies the fixture prefix to a temporary directory.
+    fn prefix() -> TempDir {
+        let prefix = tempfile::tempdir().unwrap();
+        for file in ["user.reg", "system.reg"] {
+            fs::copy(Path::new(FIXTURES).join(file), prefix.path().join(file)).unwrap();
+        }
+        prefix
+    }
+
+    fn keys(prefix: &TempDir, keys: &[&str]) -> WineRegistryKeys {
+        WineRegistryKeys::new(
+            prefix.path().to_path_buf(),
+            keys.iter().map(|key| key.parse().unwrap()).collect(),
+        )
+    }
+
+    #[test]
+    fn parse_key() {
+        let key: RegistryKey = "HKCU/Software/Amistech/My Summer Car/".parse().unwrap();
+        assert_eq!(key.hive(), Hive::CurrentUser);
+        assert_eq!(
+            key.to_string(),
+            r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"
+        );
+        assert!("HKEY_CLASSES_ROOT\\.sav".parse::<RegistryKey>().is_err());
+        assert!("HKEY_CURRENT_USER".parse::<RegistryKey>().is_err());
+    }
+
+    #[test]
+    fn export_includes_subkeys_only() {
+        let prefix = prefix();
+        let reg = keys(
+            &prefix,
+            &[r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"],
+        )
+        .export()
+        .unwrap();
+        assert_eq!(
+            reg,
+            r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\Amistech\My Summer Car]
+"Money_h2081340548"=hex:fa,44,e3,40
+"PlayerHasHayosiko_h3271854003"=hex:01
+"MailboxKeys"=hex:00,01,02,03,04,05,06,07,08,09,0a,0b,0c,0d,0e,0f,10,11,12,13,\
+  14,15,16,17,18,19,1a,1b,1c,1d,1e,1f
+"screenmanager Resolution Width_h182942802"=dword:00000780
+
+[HKEY_CURRENT_USER\Software\Amistech\My Summer Car\Options]
+"Language"="English"
+
+"#
+        );
+    }
+
+    #[test]
+    fn import_replaces_keys_and_keeps_the_rest() {
+        let prefix = prefix();
+        let keys = keys(
+            &prefix,
+            &[r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"],
+        );
+        keys.import_from(Path::new(FIXTURES).join("my_summer_car.reg"))
+            .unwrap();
+
+        assert_eq!(
+            keys.export().unwrap(),
+            fs::read_to_string(Path::new(FIXTURES).join("my_summer_car.reg")).unwrap()
+        );
+        let user_reg = fs::read_to_string(prefix.path().join("user.reg")).unwrap();
+        assert!(user_reg.starts_with("WINE REGISTRY Version 2\n"));
+        assert!(!user_reg.contains("\"Language\"=\"English\""));
+        for unrelated in [
+            "[Control Panel\\\\Desktop] 1700000000\n",
+            "[Software\\\\Amistech\\\\My Summer Car Launcher] 1712345678\n",
+            "\"Codepages\"=\"1252,437\"\n",
+        ] {
+            assert!(user_reg.contains(unrelated), "{unrelated} was removed");
+        }
+        assert!(user_reg.contains("[Software\\\\Amistech\\\\My Summer Car] "));
+    }
+
+    #[test]
+    fn round_trip_across_hives() {
+        let prefix = prefix();
+        let keys = keys(
+            &prefix,
+            &[
+                r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car",
+                r"HKEY_LOCAL_MACHINE\Software\Wow6432Node\Zeal Game Studio\Lobotomy Corporation",
+            ],
+        );
+        let exported = keys.export().unwrap();
+
+        keys.import_from(prefix.path().join("missing.reg")).unwrap();
+        assert_eq!(keys.export().unwrap(), format!("{REG_FILE_HEADER}\n\n"));
+        let system_reg = fs::read_to_string(prefix.path().join("system.reg")).unwrap();
+        assert!(system_reg.contains("Valve\\\\Steam"));
+
+        keys.import(&exported).unwrap();
+        assert_eq!(keys.export().unwrap(), exported);
+    }
+}
@@ -1,31 +1,43 @@
 use std::{
-    cell::Ref,
-    cmp::Ordering,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
     num::ParseIntError,
     path::{Path, PathBuf},
     str::FromStr,
-    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use log::{info, warn};
 use mediawiki::ApiSync;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use steamlocate::SteamDir;
 use strum::Display;
+use uuid::Uuid;
 
 use crate::{
-    consts::{DATA_FILENAME, PCGW_API},
-    dir_swapper::DirSwapper,
+    archive::{self, OnConflict, SlotSource},
+    consts::{CACHE_DIR, DATA_FILENAME, PCGW_API, SAVE_SLOT_PATH},
+    crypt,
+    diff::FileDiff,
+    dir_swapper::{DiffSide, DirSwapper, PendingCompression, SwapMode, TrashedVersion},
+    journal::{Journal, Operation},
     pcgw::{self, PCGWError},
+    registry::WineRegistryKeys,
+    slot_name::SlotName,
+    sources::{SaveLocations, SourceSet},
+    trash::{TRASHED_SLOTS, Trash, TrashConfig, Trashed},
+    usage::{self, GameUsage, QuotaConfig, UsageReport},
     utils::{self, Cached, states},
 };
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Display)]
+#[derive(
+    Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy, Display,
+)]
 #[non_exhaustive]
 pub enum GameId {
-    // #[strum(to_string = "{}")]
+    #[strum(to_string = "{0}")]
     Steam(u32),
 }
 impl GameId {
@@ -33,6 +45,25 @@ impl GameId {
     pub fn fetch_name(self) -> Result<String, anyhow::Error> {
         Ok(pcgw::fetch_page_by_id(&ApiSync::new(PCGW_API)?, self)?)
     }
+    /// Looks up save locations in every configured source, see `SourceSet::build`.
+    pub fn fetch_locations(self) -> Result<Option<SaveLocations>> {
+        SourceSet::build()?.locations(self)
+    }
+    /// Finds the Wine prefix that Proton runs the game in, `steamapps/compatdata/<app id>/pfx`
+    /// in the library the game is installed to. Returns `Ok(None)` if the game is not installed
+    /// or never ran through Proton.
+    pub fn proton_prefix(self) -> Result<Option<PathBuf>> {
+        let GameId::Steam(app_id) = self;
+        let Some((_, library)) = SteamDir::locate()?.find_app(app_id)? else {
+            return Ok(None);
+        };
+        let prefix = library
+            .path()
+            .join("steamapps/compatdata")
+            .join(app_id.to_string())
+            .join("pfx");
+        Ok(fs::exists(&prefix)?.then_some(prefix))
+    }
 }
 impl FromStr for GameId {
     type Err = ParseIntError;
@@ -41,7 +72,7 @@ impl FromStr for GameId {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct SlotMeta {
     // TODO: date created, last loaded, etc.
 }
@@ -49,8 +80,40 @@ pub struct SlotMeta {
 // TODO: Use getters and setters for relevant data maybe to not expose irrelevant
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct GameSaves {
-    pub slot_metadata: HashMap<String, SlotMeta>,
+    pub slot_metadata: HashMap<SlotName, SlotMeta>,
     pub slot_swapper: DirSwapper,
+    /// Started by `launch` instead of Steam
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executable: Option<PathBuf>,
+}
+
+impl GameSaves {
+    /// Moves slot `name` of game `id` into item `item` of the trash.
+    fn trash_slot(
+        &mut self,
+        id: GameId,
+        name: &SlotName,
+        trash: &Trash,
+        item: &str,
+    ) -> Result<Option<(SlotMeta, TrashedVersion)>> {
+        let Some(version) = self
+            .slot_swapper
+            .trash_version(name, &trash.item_dir(item))?
+        else {
+            return Ok(None);
+        };
+        let meta = self.slot_metadata.remove(name).unwrap_or_default();
+        trash.add(
+            item,
+            Trashed::Slot {
+                game: id,
+                name: name.clone(),
+                meta: meta.clone(),
+                version: version.clone(),
+            },
+        )?;
+        Ok(Some((meta, version)))
+    }
 }
 
 #[serde_as]
@@ -61,6 +124,11 @@ pub struct SaveManager {
     game_data: HashMap<GameId, GameSaves>,
     name_cache: Cached<states::Resolved<BTreeMap<String, GameId>>>,
     steam_loaded: bool,
+    /// Deletes, renames, loads and path changes that can be undone
+    #[serde(default)]
+    journal: Journal,
+    #[serde(skip)]
+    trash: Trash,
 }
 
 impl Drop for SaveManager {
@@ -75,18 +143,22 @@ impl SaveManager {
     pub fn build() -> Result<Self> {
         let save_swapper_data = utils::read_data(DATA_FILENAME)?;
 
-        if let Some(save_swapper) = save_swapper_data {
+        let save_swapper = if let Some(save_swapper) = save_swapper_data {
             println!("Found swapper");
-            Ok(save_swapper)
+            save_swapper
         } else {
             let save_swapper = save_swapper_data.unwrap_or(Self {
                 game_data: Default::default(),
                 name_cache: Cached::default().read()?,
                 steam_loaded: Default::default(),
+                journal: Default::default(),
+                trash: Default::default(),
             });
             save_swapper.save()?;
-            Ok(save_swapper)
-        }
+            save_swapper
+        };
+        save_swapper.expire_trash();
+        Ok(save_swapper)
     }
     /// Finds the steam directory and loads games from all libraries. Returns `Ok(false)` if no
     /// games or libraries are found but Steam is installed.
@@ -112,7 +184,7 @@ impl SaveManager {
                             cache.insert(id.fetch_name()?, id);
                         }
                         // Not a game
-                        Err(PCGWError::NotFound) => {}
+                        Err(PCGWError::PageNotFound(_)) => {}
                         Err(err) => return Err(err.into()),
                     }
                 }
@@ -140,12 +212,125 @@ impl SaveManager {
             .get(&id)
             .map(|game| game.slot_swapper.get_version_dir())
     }
-    #[inline]
     pub fn set_path(&mut self, id: GameId, new_path: PathBuf) -> Result<(), ()> {
+        let game = self.game_data.get_mut(&id).ok_or(())?;
+        let previous = game.slot_swapper.primary_dir().to_path_buf();
+        game.slot_swapper.set_primary_dir(new_path.clone());
+        self.record(Operation::SetPath {
+            game: id,
+            previous,
+            path: new_path,
+        });
+        Ok(())
+    }
+    #[inline]
+    pub fn set_executable(&mut self, id: GameId, executable: Option<PathBuf>) -> Result<(), ()> {
         self.game_data.get_mut(&id).map_or(Err(()), |game| {
-            Ok(game.slot_swapper.set_primary_dir(new_path))
+            game.executable = executable;
+            Ok(())
         })
     }
+    /// Swaps `registry` along with the slots of a game from now on, or stops swapping registry
+    /// keys. The current keys are stored with the loaded slot, the other slots have none until
+    /// they are loaded and saved.
+    pub fn set_registry(
+        &mut self,
+        id: GameId,
+        registry: Option<WineRegistryKeys>,
+    ) -> Result<Option<()>> {
+        let Some(game) = self.game_data.get_mut(&id) else {
+            return Ok(None);
+        };
+        game.slot_swapper.set_registry(registry);
+        game.slot_swapper.snapshot()?;
+        Ok(Some(()))
+    }
+    /// Changes how the slots of a game are loaded, see [`DirSwapper::set_mode`].
+    pub fn set_mode(&mut self, id: GameId, mode: SwapMode) -> Result<Option<()>> {
+        self.game_data
+            .get_mut(&id)
+            .map(|game| game.slot_swapper.set_mode(mode))
+            .transpose()
+    }
+    /// Stores the inactive slots of a game compressed, see [`DirSwapper::set_compression`].
+    /// Returns the number of slots compressed right away.
+    pub fn set_compression(&mut self, id: GameId, enabled: bool) -> Result<Option<usize>> {
+        let Some(game) = self.game_data.get_mut(&id) else {
+            return Ok(None);
+        };
+        game.slot_swapper.set_compression(enabled)?;
+        Ok(Some(if enabled {
+            Self::compress_all(&game.slot_swapper)?
+        } else {
+            0
+        }))
+    }
+    /// Encrypts the inactive slots of a game with the key of the session, see
+    /// [`DirSwapper::set_encryption`]. Returns the number of slots encrypted right away.
+    pub fn set_encryption(&mut self, id: GameId, enabled: bool) -> Result<Option<usize>> {
+        let Some(game) = self.game_data.get_mut(&id) else {
+            return Ok(None);
+        };
+        game.slot_swapper.set_encryption(enabled)?;
+        Ok(Some(if enabled {
+            Self::compress_all(&game.slot_swapper)?
+        } else {
+            0
+        }))
+    }
+    fn compress_all(swapper: &DirSwapper) -> Result<usize> {
+        let mut compressed = 0;
+        if fs::exists(swapper.get_version_dir())? {
+            for name in swapper.versions()? {
+                compressed += usize::from(swapper.compress_version(&name)?);
+            }
+        }
+        Ok(compressed)
+    }
+    /// Inactive slots of all games that are ready to be compressed, see
+    /// [`DirSwapper::prepare_compression`]. Games with encryption are skipped until the session
+    /// is unlocked.
+    pub fn pending_compressions(
+        &self,
+        min_idle: Duration,
+    ) -> Result<Vec<(GameId, PendingCompression)>> {
+        let mut pending = Vec::new();
+        for (&id, saves) in &self.game_data {
+            let swapper = &saves.slot_swapper;
+            if !swapper.compression()
+                || swapper.encryption() && !crypt::is_unlocked()
+                || !fs::exists(swapper.get_version_dir())?
+            {
+                continue;
+            }
+            for name in swapper.versions()? {
+                if let Some(compression) = swapper.prepare_compression(&name, min_idle)? {
+                    pending.push((id, compression));
+                }
+            }
+        }
+        Ok(pending)
+    }
+    /// Replaces a slot of game `id` with its archive, see [`DirSwapper::finish_compression`].
+    pub fn finish_compression(&self, id: GameId, pending: &PendingCompression) -> Result<bool> {
+        let Some(game) = self.game_data.get(&id) else {
+            pending.discard();
+            return Ok(false);
+        };
+        game.slot_swapper.finish_compression(pending)
+    }
+    /// Finds a game by its ID or by its name, ignoring case.
+    pub fn find(&self, query: &str) -> Option<GameId> {
+        if let Ok(id) = query.parse()
+            && self.contains(id)
+        {
+            return Some(id);
+        }
+        self.games()
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(query))
+            .map(|(_, &id)| id)
+    }
     #[inline]
     pub fn contains(&self, id: GameId) -> bool {
         self.game_data.contains_key(&id)
@@ -156,42 +341,613 @@ impl SaveManager {
         self.game_data.get(&id)
     }
     // TODO: do not expose indexes, use HashMap with keys or expose references somehow
-    pub fn create(&mut self, id: GameId, name: &str) -> Result<Option<()>> {
+    pub fn create(&mut self, id: GameId, name: &SlotName) -> Result<Option<()>> {
         self.game_data
             .get_mut(&id)
             .map(|game| game.slot_swapper.add_version(name))
             .transpose()
             .map(|game| game.flatten())
     }
-    pub fn rename(&mut self, game: GameId, name: &str, new_name: &str) -> Result<Option<()>> {
+    /// Adds slot `new_name` as a copy of slot `name` without loading anything, see
+    /// [`DirSwapper::duplicate_version`].
+    pub fn duplicate(
+        &mut self,
+        game: GameId,
+        name: &SlotName,
+        new_name: &SlotName,
+    ) -> Result<Option<()>> {
+        let Some(game) = self.game_data.get_mut(&game) else {
+            return Ok(None);
+        };
+        if game
+            .slot_swapper
+            .duplicate_version(name, new_name)?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        if let Some(meta) = game.slot_metadata.get(name).cloned() {
+            game.slot_metadata.insert(new_name.clone(), meta);
+        }
+        Ok(Some(()))
+    }
+    /// Adds a slot with a copy of the live save directory, see
+    /// [`DirSwapper::add_version_from_primary`].
+    pub fn create_from_live(&mut self, game: GameId, name: &SlotName) -> Result<Option<()>> {
         self.game_data
             .get_mut(&game)
-            .map(|game| game.slot_swapper.rename_version(name, new_name))
+            .map(|game| game.slot_swapper.add_version_from_primary(name))
             .transpose()
             .map(|game| game.flatten())
     }
-    pub fn delete(&mut self, game: GameId, name: &str) -> Result<Option<()>> {
+    /// Renames a slot together with its metadata, see [`DirSwapper::rename_version`].
+    pub fn rename(
+        &mut self,
+        game: GameId,
+        name: &SlotName,
+        new_name: &SlotName,
+    ) -> Result<Option<()>> {
+        let renamed = self.rename_slot(game, name, new_name)?;
+        if renamed.is_some() {
+            self.record(Operation::Rename {
+                game,
+                name: name.clone(),
+                new_name: new_name.clone(),
+            });
+        }
+        Ok(renamed)
+    }
+    fn rename_slot(
+        &mut self,
+        game: GameId,
+        name: &SlotName,
+        new_name: &SlotName,
+    ) -> Result<Option<()>> {
+        let Some(game) = self.game_data.get_mut(&game) else {
+            return Ok(None);
+        };
+        if game.slot_swapper.rename_version(name, new_name)?.is_none() {
+            return Ok(None);
+        }
+        if let Some(meta) = game.slot_metadata.remove(name) {
+            game.slot_metadata.insert(new_name.clone(), meta);
+        }
+        Ok(Some(()))
+    }
+    /// Moves a slot to the trash, from where [`SaveManager::undo`] can restore it.
+    pub fn delete(&mut self, game: GameId, name: &SlotName) -> Result<Option<()>> {
+        let trash = Uuid::new_v4().to_string();
+        let Some((meta, version)) = self.trash_slot(game, name, &trash)? else {
+            return Ok(None);
+        };
+        self.record(Operation::Delete {
+            game,
+            name: name.clone(),
+            meta,
+            trash,
+            version,
+        });
+        Ok(Some(()))
+    }
+    fn trash_slot(
+        &mut self,
+        game: GameId,
+        name: &SlotName,
+        trash: &str,
+    ) -> Result<Option<(SlotMeta, TrashedVersion)>> {
+        let Some(saves) = self.game_data.get_mut(&game) else {
+            return Ok(None);
+        };
+        let trashed = saves.trash_slot(game, name, &self.trash, trash)?;
+        self.expire_trash();
+        Ok(trashed)
+    }
+    /// Moves a game with all of its slots to the trash, leaving its save directory as is. Slots
+    /// are encrypted first if the game encrypts them. Returns `Ok(None)` if the game does not
+    /// exist.
+    pub fn remove_game(&mut self, id: GameId) -> Result<Option<()>> {
+        let Some(saves) = self.game_data.get(&id) else {
+            return Ok(None);
+        };
+        saves.slot_swapper.encrypt_versions()?;
+        let item = Uuid::new_v4().to_string();
+        let item_dir = self.trash.item_dir(&item);
+        fs::create_dir_all(&item_dir)?;
+        let version_dir = saves.slot_swapper.get_version_dir();
+        if !version_dir.as_os_str().is_empty() && fs::exists(version_dir)? {
+            utils::move_path(version_dir, item_dir.join(TRASHED_SLOTS))?;
+        }
+        let saves = self.game_data.remove(&id).expect("game should exist");
+        let cache = self.name_cache.get_mut();
+        let name = cache
+            .iter()
+            .find(|(_, game)| **game == id)
+            .map(|(name, _)| name.clone());
+        if let Some(name) = &name {
+            cache.remove(name);
+        }
+        self.trash.add(
+            &item,
+            Trashed::Game {
+                game: id,
+                name,
+                saves,
+            },
+        )?;
+        self.expire_trash();
+        Ok(Some(()))
+    }
+    /// Restores item `id` of the trash. A slot whose name was taken in the meantime is restored
+    /// with a number appended. Returns `Ok(None)` if the item or the game of a slot does not
+    /// exist, or if a game was added again.
+    pub fn restore_trashed(&mut self, id: &str) -> Result<Option<()>> {
+        let Some(item) = self.trash.get(id)? else {
+            return Ok(None);
+        };
+        let item_dir = self.trash.item_dir(id);
+        match item.trashed {
+            Trashed::Slot {
+                game,
+                name,
+                meta,
+                version,
+            } => {
+                let Some(saves) = self.game_data.get_mut(&game) else {
+                    return Ok(None);
+                };
+                let existing = saves.slot_swapper.versions()?;
+                let Some(name) = OnConflict::Rename.resolve(&name, &existing) else {
+                    return Ok(None);
+                };
+                if saves
+                    .slot_swapper
+                    .restore_version(&name, &version, &item_dir)?
+                    .is_none()
+                {
+                    return Ok(None);
+                }
+                saves.slot_metadata.insert(name, meta);
+            }
+            Trashed::Game { game, name, saves } => {
+                if self.game_data.contains_key(&game) {
+                    return Ok(None);
+                }
+                let slots = item_dir.join(TRASHED_SLOTS);
+                let version_dir = saves.slot_swapper.get_version_dir();
+                if fs::exists(&slots)? {
+                    if fs::exists(version_dir)? {
+                        return Ok(None);
+                    }
+                    if let Some(parent) = version_dir.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    utils::move_path(slots, version_dir)?;
+                }
+                if let Some(name) = name {
+                    self.name_cache.get_mut().insert(name, game);
+                }
+                self.game_data.insert(game, saves);
+            }
+        }
+        self.trash.purge(id)?;
+        Ok(Some(()))
+    }
+    /// Deleted slots and games.
+    #[inline]
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+    /// Purges the items of the trash that exceed the limits of [`TrashConfig`].
+    fn expire_trash(&self) {
+        // The trash only has to be emptied eventually, which should not stop anything else
+        match TrashConfig::read().and_then(|config| self.trash.expire(&config, SystemTime::now())) {
+            Ok(expired) => {
+                for item in expired {
+                    info!("{} expired from the trash", item.trashed);
+                }
+            }
+            Err(err) => warn!("failed to expire the trash: {err:#}"),
+        }
+    }
+    pub fn load(&mut self, game: GameId, name: SlotName) -> Result<Option<()>> {
+        let previous = self
+            .game_data
+            .get(&game)
+            .and_then(|game| game.slot_swapper.active_version().cloned());
+        let loaded = self.load_slot(game, name.clone())?;
+        if loaded.is_some()
+            && let Some(previous) = previous
+            && previous != name
+        {
+            self.record(Operation::Load {
+                game,
+                previous,
+                name,
+            });
+        }
+        Ok(loaded)
+    }
+    fn load_slot(&mut self, game: GameId, name: SlotName) -> Result<Option<()>> {
         self.game_data
             .get_mut(&game)
-            .map(|game| game.slot_swapper.delete_version(name))
+            .map(|game| game.slot_swapper.set_active(name))
             .transpose()
             .map(|game| game.flatten())
     }
-    pub fn load(&mut self, game: GameId, name: String) -> Result<Option<()>> {
+    /// Reverts the most recent operation, see [`Journal`]. Returns the operation, or `Ok(None)`
+    /// if there is nothing to undo. Operations that cannot be reverted any more, e.g. because
+    /// the slot was deleted by other means, are forgotten with an error.
+    pub fn undo(&mut self) -> Result<Option<Operation>> {
+        let Some(operation) = self.journal.undo() else {
+            return Ok(None);
+        };
+        let reverted = match &operation {
+            Operation::Delete {
+                game,
+                name,
+                meta,
+                trash,
+                version,
+            } => {
+                // The item is gone once it is restored or purged from the trash
+                let trash_dir = self.trash.item_dir(trash);
+                let restored = match self.game_data.get_mut(game) {
+                    Some(saves) if fs::exists(&trash_dir)? => saves
+                        .slot_swapper
+                        .restore_version(name, version, &trash_dir)?
+                        .inspect(|_| {
+                            saves.slot_metadata.insert(name.clone(), meta.clone());
+                        }),
+                    _ => None,
+                };
+                if restored.is_some() {
+                    self.trash.purge(trash)?;
+                }
+                restored
+            }
+            Operation::Rename {
+                game,
+                name,
+                new_name,
+            } => self.rename_slot(*game, new_name, name)?,
+            Operation::Overwrite {
+                game,
+                name,
+                trash,
+                imported,
+            } => self.swap_trashed(*game, name, imported, trash)?,
+            Operation::Load { game, previous, .. } => self.load_slot(*game, previous.clone())?,
+            Operation::SetPath { game, previous, .. } => {
+                self.game_data.get_mut(game).map(|saves| {
+                    saves.slot_swapper.set_primary_dir(previous.clone());
+                })
+            }
+        };
+        if reverted.is_none() {
+            bail!("cannot undo \"{operation}\", the slot or game was changed since");
+        }
+        self.journal.undone(operation.clone());
+        Ok(Some(operation))
+    }
+    /// Does the most recently undone operation again. Returns the operation, or `Ok(None)` if
+    /// there is nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<Operation>> {
+        let Some(mut operation) = self.journal.redo() else {
+            return Ok(None);
+        };
+        let redone = match &mut operation {
+            Operation::Delete {
+                game,
+                name,
+                meta,
+                trash,
+                version,
+            } => self
+                .trash_slot(*game, name, trash)?
+                .map(|trashed| (*meta, *version) = trashed),
+            Operation::Rename {
+                game,
+                name,
+                new_name,
+            } => self.rename_slot(*game, name, new_name)?,
+            Operation::Overwrite {
+                game,
+                name,
+                trash,
+                imported,
+            } => self.swap_trashed(*game, name, trash, imported)?,
+            Operation::Load { game, name, .. } => self.load_slot(*game, name.clone())?,
+            Operation::SetPath { game, path, .. } => self.game_data.get_mut(game).map(|saves| {
+                saves.slot_swapper.set_primary_dir(path.clone());
+            }),
+        };
+        if redone.is_none() {
+            bail!("cannot redo \"{operation}\", the slot or game was changed since");
+        }
+        self.journal.redone(operation.clone());
+        Ok(Some(operation))
+    }
+    /// Moves slot `name` to trash item `out` and restores the slot of trash item `into` in its
+    /// place. Returns `Ok(None)` if the slot or the item does not exist.
+    fn swap_trashed(
+        &mut self,
+        game: GameId,
+        name: &SlotName,
+        out: &str,
+        into: &str,
+    ) -> Result<Option<()>> {
+        let Some(Trashed::Slot { meta, version, .. }) =
+            self.trash.get(into)?.map(|item| item.trashed)
+        else {
+            return Ok(None);
+        };
+        if self.trash_slot(game, name, out)?.is_none() {
+            return Ok(None);
+        }
+        let Some(saves) = self.game_data.get_mut(&game) else {
+            return Ok(None);
+        };
+        if saves
+            .slot_swapper
+            .restore_version(name, &version, &self.trash.item_dir(into))?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        saves.slot_metadata.insert(name.clone(), meta);
+        self.trash.purge(into)?;
+        Ok(Some(()))
+    }
+    #[inline]
+    fn record(&mut self, operation: Operation) {
+        self.journal.record(operation);
+    }
+    /// Copies the primary directory back into the loaded slot, see [`DirSwapper::snapshot`].
+    pub fn snapshot(&mut self, game: GameId) -> Result<Option<()>> {
         self.game_data
-            .get_mut(&game)
-            .map(|game| game.slot_swapper.set_active(name))
+            .get(&game)
+            .map(|game| game.slot_swapper.snapshot())
+            .transpose()
+            .map(|game| game.flatten())
+    }
+    /// Copies the primary directory of every game back into its loaded slot and writes the names
+    /// of the slots next to their directories, so that the slot store is up to date before it is
+    /// synced. Slots loaded by moving their directory would be synced as empty otherwise.
+    pub fn prepare_sync(&self) -> Result<()> {
+        for saves in self.game_data.values() {
+            let swapper = &saves.slot_swapper;
+            if !swapper.get_version_dir().as_os_str().is_empty()
+                && fs::exists(swapper.get_version_dir())?
+            {
+                swapper.snapshot()?;
+                swapper.write_name_files()?;
+            }
+        }
+        Ok(())
+    }
+    /// Slots loaded into the save directory of their game as `<game>/<slot>` relative to
+    /// `crate::consts::SAVE_SLOT_PATH`, which a sync must not replace, see
+    /// `crate::sync::Syncer::set_loaded`.
+    pub fn loaded_slots(&self) -> Result<BTreeSet<String>> {
+        let mut loaded = BTreeSet::new();
+        for saves in self.game_data.values() {
+            let swapper = &saves.slot_swapper;
+            let Some(name) = swapper.active_version() else {
+                continue;
+            };
+            if swapper.get_version_dir().as_os_str().is_empty() {
+                continue;
+            }
+            if let Some(dir) = swapper.version_dir_of(name)?
+                && let Ok(slot) = dir.strip_prefix(SAVE_SLOT_PATH.as_path())
+            {
+                loaded.insert(slot.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Ok(loaded)
+    }
+    /// Adds the slots pulled by a sync under their names, see [`DirSwapper::adopt_versions`].
+    pub fn adopt_synced(&mut self) -> Result<()> {
+        for saves in self.game_data.values_mut() {
+            let swapper = &mut saves.slot_swapper;
+            if !swapper.get_version_dir().as_os_str().is_empty()
+                && fs::exists(swapper.get_version_dir())?
+            {
+                swapper.adopt_versions()?;
+            }
+        }
+        Ok(())
+    }
+    /// Compares two slots or a slot and the live save directory, see [`DirSwapper::diff`].
+    pub fn diff(
+        &self,
+        game: GameId,
+        old: DiffSide,
+        new: DiffSide,
+    ) -> Result<Option<Vec<FileDiff>>> {
+        self.game_data
+            .get(&game)
+            .map(|game| game.slot_swapper.diff(old, new))
+            .transpose()
+            .map(|game| game.flatten())
+    }
+    /// Returns the automatic snapshots of a slot, oldest first, see [`DirSwapper::history`].
+    pub fn history(&self, game: GameId, name: &SlotName) -> Result<Option<Vec<String>>> {
+        self.game_data
+            .get(&game)
+            .map(|game| game.slot_swapper.history(name))
+            .transpose()
+    }
+    /// Rolls a slot back to an automatic snapshot, see [`DirSwapper::restore_history`].
+    pub fn restore(&mut self, game: GameId, name: &SlotName, snapshot: &str) -> Result<Option<()>> {
+        self.game_data
+            .get(&game)
+            .map(|game| game.slot_swapper.restore_history(name, snapshot))
             .transpose()
             .map(|game| game.flatten())
     }
     #[inline]
-    pub fn is_loaded(&self, game: GameId, name: &str) -> Option<bool> {
+    pub fn is_loaded(&self, game: GameId, name: &SlotName) -> Option<bool> {
         self.game_data.get(&game).map(|game| {
             game.slot_swapper
                 .active_version()
                 .is_some_and(|version| version == name)
         })
     }
+    /// Exports slots `names` of a game, or all of its slots if `names` is empty, to an archive at
+    /// `path`, encrypted with the key of the session if `encrypt` is set. Returns `Ok(None)` if
+    /// the game or a slot does not exist.
+    pub fn export_slots(
+        &self,
+        id: GameId,
+        names: &[SlotName],
+        path: &Path,
+        encrypt: bool,
+    ) -> Result<Option<()>> {
+        let Some(game) = self.game_data.get(&id) else {
+            return Ok(None);
+        };
+        let key = encrypt.then(crypt::session_key).transpose()?;
+        let swapper = &game.slot_swapper;
+        let names = if names.is_empty() {
+            swapper.versions()?
+        } else {
+            names.to_vec()
+        };
+        let mut contents = Vec::new();
+        for name in &names {
+            let Some(dir) = swapper.contents_dir_of(name)? else {
+                return Ok(None);
+            };
+            contents.push(dir);
+        }
+        let default_meta = SlotMeta::default();
+        let mut slots = Vec::new();
+        for (name, dir) in names.iter().zip(&contents) {
+            slots.push(SlotSource {
+                name,
+                meta: game.slot_metadata.get(name).unwrap_or(&default_meta),
+                dir: dir.to_path_buf(),
+                registry: swapper.registry_of(name)?,
+            });
+        }
+        archive::export(id, &slots, path, key.as_ref())?;
+        Ok(Some(()))
+    }
+    /// Imports the slots of an archive created by [`SaveManager::export_slots`] after verifying
+    /// it. Encrypted archives need the key of the session. Returns the names the slots were
+    /// imported as, or `Ok(None)` if the game of the archive was not added.
+    pub fn import_slots(
+        &mut self,
+        path: &Path,
+        on_conflict: OnConflict,
+    ) -> Result<Option<Vec<SlotName>>> {
+        let archive = archive::extract(path, CACHE_DIR.join(Uuid::new_v4().to_string()))?;
+        let id = archive.manifest.game;
+        let Some(game) = self.game_data.get(&id) else {
+            return Ok(None);
+        };
+        // Every name is resolved before anything is changed, so that a conflict does not leave
+        // the archive half imported
+        let mut existing = game.slot_swapper.versions()?;
+        let mut planned = Vec::new();
+        for slot in &archive.manifest.slots {
+            let Some(name) = on_conflict.resolve(&slot.name, &existing) else {
+                continue;
+            };
+            let overwrite = existing.contains(&name);
+            if overwrite && game.slot_swapper.active_version() == Some(&name) {
+                bail!("cannot overwrite slot \"{name}\" while it is loaded");
+            }
+            if !overwrite {
+                existing.push(name.clone());
+            }
+            planned.push((slot, name, overwrite));
+        }
+
+        let mut imported = Vec::new();
+        for (slot, name, overwrite) in planned {
+            let game = self.game_data.get_mut(&id).expect("game should exist");
+            let trash = Uuid::new_v4().to_string();
+            if overwrite {
+                game.trash_slot(id, &name, &self.trash, &trash)?;
+            }
+            game.slot_swapper.import_version(
+                &name,
+                &archive.slot_dir(&slot.name),
+                archive.registry_file(&slot.name).as_deref(),
+            )?;
+            game.slot_metadata.insert(name.clone(), slot.meta.clone());
+            if overwrite {
+                self.record(Operation::Overwrite {
+                    game: id,
+                    name: name.clone(),
+                    trash,
+                    imported: Uuid::new_v4().to_string(),
+                });
+                self.expire_trash();
+            }
+            imported.push(name);
+        }
+        Ok(Some(imported))
+    }
+    /// Reports the disk space used by the slots and snapshots of every game and by the trash.
+    pub fn usage(&self) -> Result<UsageReport> {
+        let names: HashMap<GameId, &String> =
+            self.games().iter().map(|(name, &id)| (id, name)).collect();
+        let mut trashed = HashMap::new();
+        let mut trash = 0;
+        for item in self.trash.items()? {
+            if let Trashed::Slot { game, .. } = item.trashed {
+                *trashed.entry(game).or_insert(0) += item.size;
+            }
+            trash += item.size;
+        }
+        let mut games = Vec::new();
+        for (&id, saves) in &self.game_data {
+            let swapper = &saves.slot_swapper;
+            let mut slots = Vec::new();
+            // Games that were never set up have no versions directory
+            if !swapper.get_version_dir().as_os_str().is_empty()
+                && fs::exists(swapper.get_version_dir())?
+            {
+                for name in swapper.versions()? {
+                    slots.push(swapper.usage(&name)?);
+                }
+            }
+            games.push(GameUsage {
+                id,
+                name: names.get(&id).map(|name| name.to_string()),
+                slots,
+                trash: trashed.get(&id).copied().unwrap_or_default(),
+            });
+        }
+        games.sort_by_key(|game| std::cmp::Reverse(game.total()));
+        Ok(UsageReport {
+            games,
+            trash,
+            available: usage::available_space(&SAVE_SLOT_PATH),
+        })
+    }
+    /// Prunes the oldest snapshots until the slot store fits into the quotas of `config`, see
+    /// [`QuotaConfig::snapshots_to_prune`]. Returns the number of pruned snapshots and the
+    /// warnings about what still exceeds the quotas.
+    pub fn enforce_quota(&self, config: &QuotaConfig) -> Result<(usize, Vec<String>)> {
+        let report = self.usage()?;
+        let pruned = config.snapshots_to_prune(&report);
+        for snapshot in &pruned {
+            if let Some(game) = self.game_data.get(&snapshot.game) {
+                game.slot_swapper
+                    .remove_snapshot(snapshot.slot, snapshot.name)?;
+            }
+        }
+        let warnings = if pruned.is_empty() {
+            config.warnings(&report)
+        } else {
+            config.warnings(&self.usage()?)
+        };
+        Ok((pruned.len(), warnings))
+    }
     #[inline]
     // TODO: Make a `Vec<&str>` or something to improve performance
     pub fn games(&self) -> &BTreeMap<String, GameId> {
@@ -0,0 +1,191 @@
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Longest allowed name in characters.
+const MAX_LENGTH: usize = 64;
+/// Characters that are not allowed in file names on Windows.
+const INVALID_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+/// Device names that cannot be used as file names on Windows, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SlotNameError {
+    #[error("slot name is empty")]
+    Empty,
+    #[error("slot name is longer than {MAX_LENGTH} characters")]
+    TooLong,
+    #[error("slot name contains invalid character {0:?}")]
+    InvalidChar(char),
+    #[error("slot name starts or ends with whitespace or a dot")]
+    Padded,
+    #[error("slot name \"{0}\" is reserved")]
+    Reserved(String),
+}
+
+/// Display name of a save slot. Slots are stored under generated ids, but names are still valid
+/// file names on every platform so that they can be used in archives and synced stores.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SlotName(String);
+
+impl SlotName {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    /// Appends ` (<suffix>)`, e.g. to name a copy of the slot, cutting the name short so that the
+    /// result is not too long. Returns `None` if the suffix leaves no room for the name.
+    pub fn with_suffix(&self, suffix: &str) -> Option<Self> {
+        let suffix = format!(" ({suffix})");
+        let room = MAX_LENGTH.checked_sub(suffix.chars().count())?;
+        let name: String = self.0.chars().take(room).collect();
+        // Cutting can leave whitespace or a dot at the end, which is not allowed
+        let name = name.trim_end_matches(|c: char| c.is_whitespace() || c == '.');
+        if name.is_empty() {
+            return None;
+        }
+        format!("{name}{suffix}").parse().ok()
+    }
+}
+
+impl TryFrom<String> for SlotName {
+    type Error = SlotNameError;
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if name.is_empty() {
+            return Err(SlotNameError::Empty);
+        }
+        if name.chars().count() > MAX_LENGTH {
+            return Err(SlotNameError::TooLong);
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| c.is_control() || INVALID_CHARS.contains(c))
+        {
+            return Err(SlotNameError::InvalidChar(c));
+        }
+        // Also rules out `.`, `..` and hidden directories
+        let padding = |c: char| c.is_whitespace() || c == '.';
+        if name.starts_with(padding) || name.ends_with(padding) {
+            return Err(SlotNameError::Padded);
+        }
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        if RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+        {
+            return Err(SlotNameError::Reserved(name));
+        }
+        Ok(Self(name))
+    }
+}
+
+impl FromStr for SlotName {
+    type Err = SlotNameError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.to_string().try_into()
+    }
+}
+
+impl From<SlotName> for String {
+    fn from(name: SlotName) -> Self {
+        name.0
+    }
+}
+
+impl Deref for SlotName {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for SlotName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for SlotName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Display for SlotName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in [
+            "Main",
+            "Before the boss (2)",
+            "Genocide run v1.2",
+            "ÄÖ 日本",
+        ] {
+            assert_eq!(name.parse::<SlotName>().unwrap().as_str(), name);
+        }
+    }
+
+    #[test]
+    fn suffixes_fit_into_names() {
+        let name: SlotName = "Main".parse().unwrap();
+        assert_eq!(name.with_suffix("2").unwrap().as_str(), "Main (2)");
+
+        // The name is cut right after a space, which is dropped as well
+        let long: SlotName = format!("{} bbbbbbbb", "a".repeat(MAX_LENGTH - 11))
+            .parse()
+            .unwrap();
+        let copy = long.with_suffix("desktop").unwrap();
+        assert_eq!(
+            copy.as_str(),
+            format!("{} (desktop)", "a".repeat(MAX_LENGTH - 11))
+        );
+        assert_eq!(name.with_suffix(&"h".repeat(MAX_LENGTH)), None);
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert_eq!("".parse::<SlotName>(), Err(SlotNameError::Empty));
+        assert_eq!(
+            "a".repeat(MAX_LENGTH + 1).parse::<SlotName>(),
+            Err(SlotNameError::TooLong)
+        );
+        assert_eq!(
+            "../escape".parse::<SlotName>(),
+            Err(SlotNameError::InvalidChar('/'))
+        );
+        assert_eq!(
+            r"a\b".parse::<SlotName>(),
+            Err(SlotNameError::InvalidChar('\\'))
+        );
+        assert_eq!(
+            "tab\there".parse::<SlotName>(),
+            Err(SlotNameError::InvalidChar('\t'))
+        );
+        for name in ["..", ".history", " padded", "trailing."] {
+            assert_eq!(
+                name.parse::<SlotName>(),
+                Err(SlotNameError::Padded),
+                "{name}"
+            );
+        }
+        for name in ["con", "NUL.txt", "com1"] {
+            assert!(matches!(
+                name.parse::<SlotName>(),
+                Err(SlotNameError::Reserved(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,340 @@
+mod ludusavi;
+mod overrides;
+
+pub use ludusavi::LudusaviManifest;
+pub use overrides::{GameOverride, LocalOverrides};
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use log::warn;
+use mediawiki::ApiSync;
+
+use crate::{
+    consts::{DATA_DIR, LUDUSAVI_MANIFEST_FILENAME, PCGW_API},
+    pcgw::{
+        Candidate, ExpansionParams, Location, LocationKind, PCGWError, PCGWSaveMeta, WikiClient,
+    },
+    registry::RegistryKey,
+    save_manager::GameId,
+};
+
+/// Save and configuration locations of a game, grouped by the system they apply to.
+#[derive(Debug, Default)]
+pub struct SaveLocations {
+    pub saves: HashMap<LocationKind, Vec<Location>>,
+    pub configs: HashMap<LocationKind, Vec<Location>>,
+}
+
+impl SaveLocations {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.saves.values().all(Vec::is_empty) && self.configs.values().all(Vec::is_empty)
+    }
+    /// Every path on disk matching a save location of `kind`, most recently modified first, so
+    /// that the user can pick the real save folder. Locations that fail to resolve are skipped.
+    pub fn save_candidates(&self, kind: &LocationKind, params: ExpansionParams) -> Vec<Candidate> {
+        let mut candidates: Vec<_> = self
+            .saves
+            .get(kind)
+            .into_iter()
+            .flatten()
+            .filter_map(|location| {
+                location
+                    .resolve(params)
+                    .inspect_err(|err| warn!("{}: {err}", location.path_str()))
+                    .ok()
+            })
+            .flatten()
+            .collect();
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.modified));
+        candidates.dedup_by(|a, b| a.path == b.path);
+        candidates
+    }
+    /// Registry keys among the save and config locations of every system, without duplicates.
+    pub fn registry_keys(&self) -> Vec<RegistryKey> {
+        let mut keys = Vec::new();
+        for location in self.saves.values().chain(self.configs.values()).flatten() {
+            if let Some(key) = location.registry_key()
+                && !keys.contains(&key)
+            {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+    /// Merges results ordered from highest to lowest precedence.
+    fn merge(results: Vec<(SaveLocations, Precedence)>) -> SaveLocations {
+        let mut merged = SaveLocations::default();
+        let mut closed_saves = HashSet::new();
+        let mut closed_configs = HashSet::new();
+        for (locations, precedence) in results {
+            merge_table(
+                &mut merged.saves,
+                &mut closed_saves,
+                locations.saves,
+                precedence,
+            );
+            merge_table(
+                &mut merged.configs,
+                &mut closed_configs,
+                locations.configs,
+                precedence,
+            );
+        }
+        merged
+    }
+}
+
+/// Appends `lower` to `table`, skipping duplicates and systems that a higher source overrode.
+fn merge_table(
+    table: &mut HashMap<LocationKind, Vec<Location>>,
+    closed: &mut HashSet<LocationKind>,
+    lower: HashMap<LocationKind, Vec<Location>>,
+    precedence: Precedence,
+) {
+    for (kind, locations) in lower {
+        if locations.is_empty() || closed.contains(&kind) {
+            continue;
+        }
+        let own_locations = table.entry(kind.clone()).or_default();
+        for location in locations {
+            if !own_locations
+                .iter()
+                .any(|own| own.path_str() == location.path_str())
+            {
+                own_locations.push(location);
+            }
+        }
+        if precedence == Precedence::Override {
+            closed.insert(kind);
+        }
+    }
+}
+
+/// A database that knows where games keep their saves.
+pub trait SaveLocationSource {
+    /// Shown to the user when a lookup fails.
+    fn name(&self) -> &str;
+    /// Returns `Ok(None)` if the source does not know about the game.
+    fn locations(&self, id: GameId) -> Result<Option<SaveLocations>>;
+}
+
+/// How a source's locations combine with those of sources below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precedence {
+    /// Locations of lower sources are added after this source's, skipping duplicates.
+    #[default]
+    Merge,
+    /// For every system this source lists, locations of lower sources are ignored.
+    Override,
+}
+
+/// Save locations from the PCGamingWiki.
+pub struct PCGWSource<C: WikiClient> {
+    client: C,
+}
+
+impl<C: WikiClient> PCGWSource<C> {
+    pub fn new(client: C) -> Self {
+        Self { client }
+    }
+}
+
+impl<C: WikiClient> SaveLocationSource for PCGWSource<C> {
+    fn name(&self) -> &str {
+        "PCGamingWiki"
+    }
+    fn locations(&self, id: GameId) -> Result<Option<SaveLocations>> {
+        match PCGWSaveMeta::build(&self.client, id) {
+            Ok(meta) => Ok(Some(meta.into())),
+            Err(
+                PCGWError::PageNotFound(_)
+                | PCGWError::SectionNotFound { .. }
+                | PCGWError::TableNotFound { .. },
+            ) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Sources ordered from highest to lowest precedence.
+#[derive(Default)]
+pub struct SourceSet {
+    sources: Vec<(Box<dyn SaveLocationSource>, Precedence)>,
+}
+
+impl SourceSet {
+    /// Local overrides, then the Ludusavi manifest in `crate::consts::DATA_DIR` if there is one,
+    /// both overriding the PCGamingWiki.
+    pub fn build() -> Result<Self> {
+        let mut sources = Self::default().with(LocalOverrides::read()?, Precedence::Override);
+        let manifest_path = DATA_DIR.join(LUDUSAVI_MANIFEST_FILENAME);
+        if manifest_path.exists() {
+            sources = sources.with(
+                LudusaviManifest::load(&manifest_path)?,
+                Precedence::Override,
+            );
+        }
+        Ok(sources.with(PCGWSource::new(ApiSync::new(PCGW_API)?), Precedence::Merge))
+    }
+    /// Adds a source below all current ones.
+    pub fn with(
+        mut self,
+        source: impl SaveLocationSource + 'static,
+        precedence: Precedence,
+    ) -> Self {
+        self.sources.push((Box::new(source), precedence));
+        self
+    }
+    /// Looks up the game in every source and merges the results. A failing source is skipped so
+    /// that the others can still be used offline; its error is only returned if no source knows
+    /// the game.
+    pub fn locations(&self, id: GameId) -> Result<Option<SaveLocations>> {
+        let mut results = Vec::new();
+        let mut first_err = None;
+        for (source, precedence) in &self.sources {
+            match source.locations(id) {
+                Ok(Some(locations)) => results.push((locations, *precedence)),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("{} lookup failed for {id:?}: {err:#}", source.name());
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        match first_err {
+            Some(err) if results.is_empty() => Err(err),
+            _ if results.is_empty() => Ok(None),
+            _ => Ok(Some(SaveLocations::merge(results))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::pcgw::client::fixture::FixtureClient;
+
+    fn manifest() -> LudusaviManifest {
+        LudusaviManifest::load(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ludusavi/manifest.yaml"),
+        )
+        .unwrap()
+    }
+
+    fn windows() -> LocationKind {
+        LocationKind::OS("Windows".to_string())
+    }
+
+    fn path_strs(locations: &SaveLocations, kind: LocationKind) -> Vec<&str> {
+        locations.saves[&kind]
+            .iter()
+            .map(Location::path_str)
+            .collect()
+    }
+
+    #[test]
+    fn override_replaces_lower_locations_per_system() {
+        let sources = SourceSet::default()
+            .with(manifest(), Precedence::Override)
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge);
+        let locations = sources.locations(GameId::Steam(391540)).unwrap().unwrap();
+
+        assert_eq!(
+            path_strs(&locations, windows()),
+            [r#"%LOCALAPPDATA%\UNDERTALE"#]
+        );
+        // Only the wiki lists a macOS location
+        assert_eq!(
+            path_strs(&locations, LocationKind::OS("OS X".to_string())),
+            ["~/Library/Application Support/com.tobyfox.undertale/"]
+        );
+    }
+
+    #[test]
+    fn registry_keys_are_collected_from_all_systems() {
+        let sources = SourceSet::default()
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge)
+            .with(manifest(), Precedence::Merge);
+        let locations = sources.locations(GameId::Steam(516750)).unwrap().unwrap();
+
+        assert_eq!(
+            locations.registry_keys(),
+            [r"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"
+                .parse::<RegistryKey>()
+                .unwrap()]
+        );
+        let locations = sources.locations(GameId::Steam(391540)).unwrap().unwrap();
+        assert!(locations.registry_keys().is_empty());
+    }
+
+    #[test]
+    fn merge_appends_lower_locations_without_duplicates() {
+        let sources = SourceSet::default()
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge)
+            .with(manifest(), Precedence::Merge);
+        let locations = sources.locations(GameId::Steam(391540)).unwrap().unwrap();
+
+        assert_eq!(
+            path_strs(&locations, windows()),
+            [
+                r#"%LOCALAPPDATA%\UNDERTALE\"#,
+                r#"%LOCALAPPDATA%\UNDERTALE"#
+            ]
+        );
+        assert_eq!(
+            path_strs(&locations, LocationKind::OS("Linux".to_string())),
+            ["$XDG_CONFIG_HOME/UNDERTALE/"]
+        );
+    }
+
+    #[test]
+    fn failing_source_is_skipped() {
+        let sources = SourceSet::default()
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge)
+            .with(manifest(), Precedence::Merge);
+        // The wiki fixture for this game is a rate limit response
+        let locations = sources.locations(GameId::Steam(230410)).unwrap().unwrap();
+        assert_eq!(
+            path_strs(&locations, windows()),
+            [r#"%LOCALAPPDATA%\Warframe"#]
+        );
+
+        let sources =
+            SourceSet::default().with(PCGWSource::new(FixtureClient::new()), Precedence::Merge);
+        assert!(sources.locations(GameId::Steam(230410)).is_err());
+    }
+
+    #[test]
+    fn local_overrides_take_precedence() {
+        let mut overrides = LocalOverrides::default();
+        overrides.add(
+            GameId::Steam(391540),
+            &windows(),
+            r"D:\Saves\UNDERTALE",
+            false,
+        );
+        let sources = SourceSet::default()
+            .with(overrides, Precedence::Override)
+            .with(manifest(), Precedence::Override)
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge);
+        let locations = sources.locations(GameId::Steam(391540)).unwrap().unwrap();
+
+        assert_eq!(path_strs(&locations, windows()), [r"D:\Saves\UNDERTALE"]);
+        assert_eq!(
+            path_strs(&locations, LocationKind::Steam),
+            ["<Steam-folder>/userdata/<user-id>/391540/remote"]
+        );
+    }
+
+    #[test]
+    fn unknown_game_has_no_locations() {
+        let sources = SourceSet::default()
+            .with(manifest(), Precedence::Override)
+            .with(PCGWSource::new(FixtureClient::new()), Precedence::Merge);
+        assert!(sources.locations(GameId::Steam(1)).unwrap().is_none());
+    }
+}
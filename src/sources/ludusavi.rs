@@ -0,0 +1,347 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{SaveLocationSource, SaveLocations};
+use crate::{
+    pcgw::{Location, LocationKind},
+    save_manager::GameId,
+};
+
+/// A game data database in the format of <https://github.com/mtkennerly/ludusavi-manifest>.
+#[derive(Debug)]
+pub struct LudusaviManifest {
+    games: HashMap<String, ManifestEntry>,
+    steam_ids: HashMap<u32, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    #[serde(default)]
+    files: BTreeMap<String, Option<Rule>>,
+    #[serde(default)]
+    registry: BTreeMap<String, Option<Rule>>,
+    #[serde(default)]
+    steam: Option<SteamInfo>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Rule {
+    #[serde(default)]
+    tags: Vec<Tag>,
+    #[serde(default)]
+    when: Vec<Constraint>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Tag {
+    Save,
+    Config,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Constraint {
+    #[serde(default)]
+    os: Option<Os>,
+    #[serde(default)]
+    store: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Os {
+    Windows,
+    Linux,
+    Mac,
+    #[serde(other)]
+    Other,
+}
+
+impl Os {
+    /// Name of the system in PCGamingWiki tables.
+    fn location_kind(self) -> Option<LocationKind> {
+        let name = match self {
+            Os::Windows => "Windows",
+            Os::Linux => "Linux",
+            Os::Mac => "OS X",
+            Os::Other => return None,
+        };
+        Some(LocationKind::OS(name.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SteamInfo {
+    id: Option<u32>,
+}
+
+impl LudusaviManifest {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let manifest = fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest {}", path.display()))?;
+        Self::parse(&manifest).with_context(|| format!("invalid manifest {}", path.display()))
+    }
+    fn parse(manifest: &str) -> Result<Self> {
+        let games: HashMap<String, ManifestEntry> = serde_yaml::from_str(manifest)?;
+        let steam_ids = games
+            .iter()
+            .filter_map(|(name, entry)| Some((entry.steam.as_ref()?.id?, name.clone())))
+            .collect();
+        Ok(Self { games, steam_ids })
+    }
+    fn entry(&self, id: GameId) -> Option<&ManifestEntry> {
+        #[allow(unreachable_patterns)]
+        let steam_id = match id {
+            GameId::Steam(id) => id,
+            _ => return None,
+        };
+        self.games.get(self.steam_ids.get(&steam_id)?)
+    }
+}
+
+impl SaveLocationSource for LudusaviManifest {
+    fn name(&self) -> &str {
+        "Ludusavi manifest"
+    }
+    fn locations(&self, id: GameId) -> Result<Option<SaveLocations>> {
+        let Some(entry) = self.entry(id) else {
+            return Ok(None);
+        };
+        let mut locations = SaveLocations::default();
+        let rules = entry
+            .files
+            .iter()
+            .map(|(path, rule)| (path, rule, false))
+            .chain(entry.registry.iter().map(|(key, rule)| (key, rule, true)));
+        for (path, rule, is_registry) in rules {
+            let default_rule = Rule::default();
+            let rule = rule.as_ref().unwrap_or(&default_rule);
+            for kind in location_kinds(path, rule, is_registry) {
+                let path_str = translate_path(path, &kind, id);
+                let has_tag = |tag| rule.tags.contains(&tag);
+                if has_tag(Tag::Config) {
+                    locations
+                        .configs
+                        .entry(kind.clone())
+                        .or_default()
+                        .push(Location::new(path_str.clone(), Vec::new()));
+                }
+                if has_tag(Tag::Save) || !has_tag(Tag::Config) {
+                    locations
+                        .saves
+                        .entry(kind)
+                        .or_default()
+                        .push(Location::new(path_str, Vec::new()));
+                }
+            }
+        }
+        Ok(Some(locations))
+    }
+}
+
+/// Systems a manifest path applies to. Explicit `os` constraints win, then Steam's userdata folder,
+/// then whatever the placeholders imply. Paths with only generic placeholders apply everywhere.
+fn location_kinds(path: &str, rule: &Rule, is_registry: bool) -> Vec<LocationKind> {
+    let oses: Vec<_> = rule
+        .when
+        .iter()
+        .filter_map(|constraint| constraint.os?.location_kind())
+        .collect();
+    if !oses.is_empty() {
+        return oses;
+    }
+    if is_registry || path.starts_with("<win") {
+        return vec![LocationKind::OS("Windows".to_string())];
+    }
+    if path.starts_with("<root>/userdata")
+        && rule.when.iter().all(|constraint| {
+            constraint
+                .store
+                .as_deref()
+                .is_none_or(|store| store == "steam")
+        })
+    {
+        return vec![LocationKind::Steam];
+    }
+    if path.starts_with("<xdg") {
+        return vec![LocationKind::OS("Linux".to_string())];
+    }
+    [Os::Windows, Os::Mac, Os::Linux]
+        .into_iter()
+        .filter_map(Os::location_kind)
+        .collect()
+}
+
+/// Rewrites Ludusavi placeholders into the PCGamingWiki abbreviations that
+/// `pcgw::utils::replace_path_abbrs` understands.
+fn translate_path(path: &str, kind: &LocationKind, id: GameId) -> String {
+    let is_windows = matches!(kind, LocationKind::OS(os) if os == "Windows");
+    let home = match kind {
+        _ if is_windows => "%USERPROFILE%",
+        LocationKind::OS(os) if os == "OS X" => "~",
+        _ => "$HOME",
+    };
+    #[allow(unreachable_patterns)]
+    let store_game_id = match id {
+        GameId::Steam(id) => id.to_string(),
+        _ => String::new(),
+    };
+    let replacements = [
+        ("<winLocalAppDataLow>", "%USERPROFILE%/AppData/LocalLow"),
+        ("<winLocalAppData>", "%LOCALAPPDATA%"),
+        ("<winAppData>", "%APPDATA%"),
+        ("<winDocuments>", "%USERPROFILE%/Documents"),
+        ("<winPublic>", "%PUBLIC%"),
+        ("<winProgramData>", "%PROGRAMDATA%"),
+        ("<winDir>", "%WINDIR%"),
+        ("<xdgData>", "$XDG_DATA_HOME"),
+        ("<xdgConfig>", "$XDG_CONFIG_HOME"),
+        ("<regHkcu>", "HKEY_CURRENT_USER"),
+        ("<regHklm>", "HKEY_LOCAL_MACHINE"),
+        ("<home>", home),
+        ("<base>", "<path-to-game>"),
+        ("<root>", "<Steam-folder>"),
+        ("<storeUserId>", "<user-id>"),
+        ("<storeGameId>", &store_game_id),
+    ];
+    let path = replacements
+        .iter()
+        .fold(path.to_string(), |path, (placeholder, abbr)| {
+            path.replace(placeholder, abbr)
+        });
+    if is_windows {
+        path.replace('/', "\\")
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+Undertale:
+  files:
+    <home>/Library/Application Support/com.tobyfox.undertale:
+      tags:
+        - save
+      when:
+        - os: mac
+    <winLocalAppData>/UNDERTALE:
+      tags:
+        - save
+      when:
+        - os: windows
+    <root>/userdata/<storeUserId>/<storeGameId>/remote:
+      tags:
+        - save
+      when:
+        - store: steam
+  steam:
+    id: 391540
+My Summer Car:
+  files:
+    <winLocalAppDataLow>/Amistech/My Summer Car:
+      tags:
+        - save
+        - config
+  registry:
+    HKEY_CURRENT_USER/Software/Amistech/My Summer Car:
+      tags:
+        - config
+  steam:
+    id: 516750
+Half-Life 2:
+  files:
+    <base>/hl2/save: {}
+  installDir:
+    Half-Life 2: {}
+  steam:
+    id: 220
+No Steam:
+  files:
+    <home>/.nosteam: {}
+"#;
+
+    fn locations(steam_id: u32) -> SaveLocations {
+        LudusaviManifest::parse(MANIFEST)
+            .unwrap()
+            .locations(GameId::Steam(steam_id))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn os(name: &str) -> LocationKind {
+        LocationKind::OS(name.to_string())
+    }
+
+    fn path_strs(table: &HashMap<LocationKind, Vec<Location>>, kind: LocationKind) -> Vec<&str> {
+        table
+            .get(&kind)
+            .map(|locations| locations.iter().map(Location::path_str).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn os_constraints_and_placeholders() {
+        let undertale = locations(391540);
+        assert_eq!(
+            path_strs(&undertale.saves, os("Windows")),
+            [r#"%LOCALAPPDATA%\UNDERTALE"#]
+        );
+        assert_eq!(
+            path_strs(&undertale.saves, os("OS X")),
+            ["~/Library/Application Support/com.tobyfox.undertale"]
+        );
+        assert_eq!(
+            path_strs(&undertale.saves, LocationKind::Steam),
+            ["<Steam-folder>/userdata/<user-id>/391540/remote"]
+        );
+        assert!(undertale.configs.is_empty());
+    }
+
+    #[test]
+    fn tags_and_registry() {
+        let my_summer_car = locations(516750);
+        let path = r#"%USERPROFILE%\AppData\LocalLow\Amistech\My Summer Car"#;
+        assert_eq!(path_strs(&my_summer_car.saves, os("Windows")), [path]);
+        assert_eq!(
+            path_strs(&my_summer_car.configs, os("Windows")),
+            [path, r#"HKEY_CURRENT_USER\Software\Amistech\My Summer Car"#]
+        );
+    }
+
+    #[test]
+    fn generic_paths_apply_to_every_os() {
+        let half_life = locations(220);
+        assert_eq!(
+            path_strs(&half_life.saves, os("Windows")),
+            [r#"<path-to-game>\hl2\save"#]
+        );
+        assert_eq!(
+            path_strs(&half_life.saves, os("Linux")),
+            ["<path-to-game>/hl2/save"]
+        );
+        assert_eq!(
+            path_strs(&half_life.saves, os("OS X")),
+            ["<path-to-game>/hl2/save"]
+        );
+    }
+
+    #[test]
+    fn unknown_game_is_none() {
+        let manifest = LudusaviManifest::parse(MANIFEST).unwrap();
+        assert!(manifest.locations(GameId::Steam(1)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+use super::{SaveLocationSource, SaveLocations};
+use crate::{
+    consts::OVERRIDES_FILENAME,
+    pcgw::{Location, LocationKind},
+    save_manager::GameId,
+    utils,
+};
+
+/// Location templates of a game keyed by system name (`Windows`, `Linux`, `OS X` or `Steam`). The
+/// templates use the same abbreviations as the PCGamingWiki, e.g. `%APPDATA%` or
+/// `<Steam-folder>`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameOverride {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub saves: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub configs: BTreeMap<String, Vec<String>>,
+}
+
+impl GameOverride {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.saves.values().all(Vec::is_empty) && self.configs.values().all(Vec::is_empty)
+    }
+    /// Adds templates of `other` that are not listed yet.
+    fn merge(&mut self, other: GameOverride) {
+        for (own, other) in [
+            (&mut self.saves, other.saves),
+            (&mut self.configs, other.configs),
+        ] {
+            for (system, templates) in other {
+                let own_templates = own.entry(system).or_default();
+                for template in templates {
+                    if !own_templates.contains(&template) {
+                        own_templates.push(template);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// User-maintained save locations stored in `crate::consts::OVERRIDES_FILENAME`. They take
+/// precedence over every other source.
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalOverrides {
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    games: BTreeMap<GameId, GameOverride>,
+}
+
+impl LocalOverrides {
+    /// Reads overrides from `crate::consts::DATA_DIR`, returning empty overrides if the file does
+    /// not exist yet.
+    pub fn read() -> Result<Self> {
+        Ok(utils::read_data(OVERRIDES_FILENAME)?.unwrap_or_default())
+    }
+    #[inline]
+    pub fn save(&self) -> Result<()> {
+        utils::write_data(OVERRIDES_FILENAME, self)
+    }
+    #[inline]
+    pub fn get(&self, id: GameId) -> Option<&GameOverride> {
+        self.games.get(&id)
+    }
+    /// Adds a location template. Returns `false` if it was already listed.
+    pub fn add(&mut self, id: GameId, kind: &LocationKind, template: &str, config: bool) -> bool {
+        let game = self.games.entry(id).or_default();
+        let table = if config {
+            &mut game.configs
+        } else {
+            &mut game.saves
+        };
+        let templates = table.entry(kind.to_string()).or_default();
+        if templates.iter().any(|own| own == template) {
+            return false;
+        }
+        templates.push(template.to_string());
+        true
+    }
+    /// Removes a location template. Returns `false` if it was not listed.
+    pub fn remove(
+        &mut self,
+        id: GameId,
+        kind: &LocationKind,
+        template: &str,
+        config: bool,
+    ) -> bool {
+        let Some(game) = self.games.get_mut(&id) else {
+            return false;
+        };
+        let table = if config {
+            &mut game.configs
+        } else {
+            &mut game.saves
+        };
+        let system = kind.to_string();
+        let Some(templates) = table.get_mut(&system) else {
+            return false;
+        };
+        let len = templates.len();
+        templates.retain(|own| own != template);
+        let removed = templates.len() != len;
+        if templates.is_empty() {
+            table.remove(&system);
+        }
+        if game.is_empty() {
+            self.games.remove(&id);
+        }
+        removed
+    }
+    /// Writes overrides of `ids`, or of every game if `ids` is empty, to `path` so that they can
+    /// be shared and loaded with [`LocalOverrides::import`].
+    pub fn export(&self, ids: &[GameId], path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let exported = LocalOverrides {
+            games: self
+                .games
+                .iter()
+                .filter(|(id, _)| ids.is_empty() || ids.contains(id))
+                .map(|(&id, game)| (id, game.clone()))
+                .collect(),
+        };
+        fs::write(path, toml::to_string(&exported)?)
+            .with_context(|| format!("failed to export overrides to {}", path.display()))
+    }
+    /// Adds the templates of an exported file to the current ones. Returns the number of games in
+    /// the file.
+    pub fn import(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let imported: LocalOverrides = toml::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("failed to read overrides {}", path.display()))?,
+        )
+        .with_context(|| format!("invalid overrides {}", path.display()))?;
+        let count = imported.games.len();
+        for (id, game) in imported.games {
+            self.games.entry(id).or_default().merge(game);
+        }
+        Ok(count)
+    }
+}
+
+fn to_locations(table: &BTreeMap<String, Vec<String>>) -> HashMap<LocationKind, Vec<Location>> {
+    table
+        .iter()
+        .map(|(system, templates)| {
+            let locations = templates
+                .iter()
+                .map(|template| Location::new(template.clone(), Vec::new()))
+                .collect();
+            (LocationKind::from(system.as_str()), locations)
+        })
+        .collect()
+}
+
+impl SaveLocationSource for LocalOverrides {
+    fn name(&self) -> &str {
+        "Local overrides"
+    }
+    fn locations(&self, id: GameId) -> Result<Option<SaveLocations>> {
+        Ok(self.get(id).map(|game| SaveLocations {
+            saves: to_locations(&game.saves),
+            configs: to_locations(&game.configs),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn windows() -> LocationKind {
+        LocationKind::OS("Windows".to_string())
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut overrides = LocalOverrides::default();
+        let id = GameId::Steam(391540);
+        assert!(overrides.add(id, &windows(), r"%LOCALAPPDATA%\UNDERTALE", false));
+        assert!(!overrides.add(id, &windows(), r"%LOCALAPPDATA%\UNDERTALE", false));
+        assert!(overrides.add(id, &LocationKind::Steam, "<Steam-folder>/userdata", true));
+        assert_eq!(
+            overrides.get(id).unwrap().saves["Windows"],
+            [r"%LOCALAPPDATA%\UNDERTALE"]
+        );
+
+        assert!(!overrides.remove(id, &windows(), r"%LOCALAPPDATA%\UNDERTALE", true));
+        assert!(overrides.remove(id, &windows(), r"%LOCALAPPDATA%\UNDERTALE", false));
+        assert!(overrides.remove(id, &LocationKind::Steam, "<Steam-folder>/userdata", true));
+        assert!(overrides.get(id).is_none());
+    }
+
+    #[test]
+    fn export_and_import() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("overrides.toml");
+        let mut overrides = LocalOverrides::default();
+        overrides.add(GameId::Steam(391540), &windows(), "%APPDATA%", false);
+        overrides.add(GameId::Steam(220), &windows(), "<path-to-game>", false);
+        overrides.export(&[GameId::Steam(391540)], &path).unwrap();
+
+        let mut teammate = LocalOverrides::default();
+        teammate.add(GameId::Steam(391540), &windows(), "%USERPROFILE%", false);
+        assert_eq!(teammate.import(&path).unwrap(), 1);
+        assert_eq!(
+            teammate.get(GameId::Steam(391540)).unwrap().saves["Windows"],
+            ["%USERPROFILE%", "%APPDATA%"]
+        );
+        assert!(teammate.get(GameId::Steam(220)).is_none());
+        // Importing again does not duplicate templates
+        teammate.import(&path).unwrap();
+        assert_eq!(
+            teammate.get(GameId::Steam(391540)).unwrap().saves["Windows"].len(),
+            2
+        );
+    }
+}
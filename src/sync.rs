@@ -0,0 +1,829 @@
+mod folder;
+mod git;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+
+pub use folder::FolderRemote;
+pub use git::GitRemote;
+
+use crate::{
+    consts::{CACHE_DIR, SAVE_SLOT_PATH, SYNC_FILENAME, SYNC_STATE_FILENAME},
+    slot_name::SlotName,
+    utils::{self, copy_dir_all, walk_dir},
+};
+
+/// Lists the state of every slot of a remote store, kept at its root.
+const INDEX_FILENAME: &str = "vittusave-index.toml";
+
+/// A copy of the slot store that [`Syncer`] pushes to and pulls from. Both stores use the layout
+/// of `crate::consts::SAVE_SLOT_PATH`: `<game>/<slot>/` directories, each with an optional
+/// `<game>/<slot>.reg` registry file and `<game>/<slot>.name` file with the name of the slot next
+/// to it.
+pub trait SyncRemote: Debug {
+    /// Identifies the remote, the last synced state is discarded when it changes.
+    fn name(&self) -> String;
+    /// Directory holding the remote store after [`SyncRemote::pull`].
+    fn root(&self) -> &Path;
+    /// Brings [`SyncRemote::root`] up to date, discarding changes that were not pushed.
+    fn pull(&mut self) -> Result<()>;
+    /// Publishes the changes made to [`SyncRemote::root`].
+    fn push(&mut self, message: &str) -> Result<()>;
+}
+
+impl<R: SyncRemote + ?Sized> SyncRemote for Box<R> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+    fn root(&self) -> &Path {
+        (**self).root()
+    }
+    fn pull(&mut self) -> Result<()> {
+        (**self).pull()
+    }
+    fn push(&mut self, message: &str) -> Result<()> {
+        (**self).push(message)
+    }
+}
+
+/// Remote configured in `crate::consts::SYNC_FILENAME`, e.g.
+///
+/// ```toml
+/// kind = "git"
+/// url = "git@example.com:me/saves.git"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SyncConfig {
+    /// A directory such as a NAS mount or a Syncthing folder
+    Folder { path: PathBuf },
+    Git {
+        url: String,
+        #[serde(default = "default_branch")]
+        branch: String,
+    },
+}
+
+fn default_branch() -> String {
+    "main".to_string()
+}
+
+/// Contents of a slot, compared between the local and the remote store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotState {
+    /// SHA-256 over the relative paths and contents of the files of the slot and its registry
+    /// file. Empty directories are ignored since git does not track them.
+    pub hash: String,
+    /// Most recent modification time of the files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    /// Machine the slot was scanned or pushed on
+    pub host: String,
+}
+
+impl SlotState {
+    fn scan(store: &Path, slot: &str, host: &str) -> Result<Self> {
+        let dir = store.join(slot);
+        let (files, _) =
+            walk_dir(&dir).with_context(|| format!("failed to read slot {}", dir.display()))?;
+        let mut hasher = Sha256::new();
+        let mut modified = None;
+        let mut size = 0;
+        let registry = registry_path(store, slot);
+        let registry = registry
+            .exists()
+            .then_some((PathBuf::from(".reg"), registry));
+        let name_file = name_path(store, slot);
+        let name_file = name_file
+            .exists()
+            .then_some((PathBuf::from(".name"), name_file));
+        for (name, path) in files
+            .into_iter()
+            .map(|file| (file.clone(), dir.join(file)))
+            .chain(registry)
+            .chain(name_file)
+        {
+            let contents =
+                fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+            let metadata = fs::symlink_metadata(&path)?;
+            modified = modified.max(metadata.modified().ok());
+            size += contents.len() as u64;
+            hasher.update(name.to_string_lossy().replace('\\', "/"));
+            hasher.update([0]);
+            hasher.update(Sha256::digest(&contents));
+        }
+        Ok(Self {
+            hash: hex::encode(hasher.finalize()),
+            modified,
+            size,
+            host: host.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RemoteIndex {
+    #[serde(default)]
+    slots: BTreeMap<String, SlotState>,
+}
+
+/// Hashes of the slots as of the last sync, used to tell which side changed a slot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    remote: String,
+    #[serde(default)]
+    synced: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    Push,
+    Pull,
+    DeleteRemote,
+    DeleteLocal,
+}
+
+/// A slot that changed on both sides since the last sync. `None` means it was deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// `<game>/<slot>`
+    pub slot: String,
+    pub local: Option<SlotState>,
+    pub remote: Option<SlotState>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolution {
+    #[default]
+    Local,
+    Remote,
+    /// Keeps the local slot and adds the remote one under a new name
+    Both,
+}
+
+/// Changes found by [`Syncer::prepare`]. Slots are named `<game>/<slot>`.
+#[derive(Debug)]
+pub struct SyncPlan {
+    pub actions: BTreeMap<String, SyncAction>,
+    pub conflicts: Vec<Conflict>,
+    local: BTreeMap<String, SlotState>,
+    index: RemoteIndex,
+    synced: BTreeMap<String, String>,
+}
+
+impl SyncPlan {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// Synchronizes a local slot store with a [`SyncRemote`]. Syncing happens in two steps so that
+/// conflicts can be resolved by the user in between: [`Syncer::prepare`] and
+/// [`Syncer::finish`].
+#[derive(Debug)]
+pub struct Syncer<R> {
+    store: PathBuf,
+    remote: R,
+    state_path: PathBuf,
+    host: String,
+    /// Slots loaded into a save directory, see [`Syncer::set_loaded`]
+    loaded: BTreeSet<String>,
+}
+
+impl Syncer<Box<dyn SyncRemote>> {
+    /// Syncs `crate::consts::SAVE_SLOT_PATH` with the remote configured in
+    /// `crate::consts::SYNC_FILENAME`. Returns `Ok(None)` if sync is not configured.
+    pub fn build() -> Result<Option<Self>> {
+        let Some(config) = utils::read_data::<SyncConfig>(SYNC_FILENAME)? else {
+            return Ok(None);
+        };
+        let remote: Box<dyn SyncRemote> = match config {
+            SyncConfig::Folder { path } => Box::new(FolderRemote::new(path)),
+            SyncConfig::Git { url, branch } => {
+                Box::new(GitRemote::new(url, branch, CACHE_DIR.join("sync")))
+            }
+        };
+        Ok(Some(Self::new(
+            SAVE_SLOT_PATH.clone(),
+            remote,
+            utils::build_data_path(SYNC_STATE_FILENAME),
+        )))
+    }
+}
+
+impl<R: SyncRemote> Syncer<R> {
+    /// Syncs `store` with `remote`, keeping the last synced state in `state_path`.
+    pub fn new(store: PathBuf, remote: R, state_path: PathBuf) -> Self {
+        Self {
+            store,
+            remote,
+            state_path,
+            host: host_name(),
+            loaded: BTreeSet::new(),
+        }
+    }
+    /// Marks slots `<game>/<slot>` as loaded into a save directory. They are still pushed, but
+    /// pulling into or deleting them is deferred until they are no longer loaded, as the game
+    /// and the next swap use the save directory instead.
+    pub fn set_loaded(&mut self, loaded: BTreeSet<String>) {
+        self.loaded = loaded;
+    }
+    #[inline]
+    pub fn remote(&self) -> &R {
+        &self.remote
+    }
+    /// Pulls the remote and compares every slot with the last synced state: a slot that changed
+    /// on one side is copied to the other, one that changed on both is a conflict.
+    pub fn prepare(&mut self) -> Result<SyncPlan> {
+        self.remote.pull()?;
+        let index: RemoteIndex =
+            read_toml(&self.remote.root().join(INDEX_FILENAME))?.unwrap_or_default();
+        let local = self.local_slots()?;
+        let synced = read_toml::<SyncState>(&self.state_path)?
+            .filter(|state| state.remote == self.remote.name())
+            .map(|state| state.synced)
+            .unwrap_or_default();
+
+        let mut actions = BTreeMap::new();
+        let mut conflicts = Vec::new();
+        for slot in local
+            .keys()
+            .chain(index.slots.keys())
+            .collect::<BTreeSet<_>>()
+        {
+            let local = local.get(slot);
+            let remote = index.slots.get(slot);
+            let local_hash = local.map(|state| &state.hash);
+            let remote_hash = remote.map(|state| &state.hash);
+            let base = synced.get(slot);
+            let action = if local_hash == remote_hash {
+                continue;
+            } else if remote_hash == base {
+                match local {
+                    Some(_) => SyncAction::Push,
+                    None => SyncAction::DeleteRemote,
+                }
+            } else if local_hash == base {
+                match remote {
+                    Some(_) => SyncAction::Pull,
+                    None => SyncAction::DeleteLocal,
+                }
+            } else {
+                conflicts.push(Conflict {
+                    slot: slot.clone(),
+                    local: local.cloned(),
+                    remote: remote.cloned(),
+                });
+                continue;
+            };
+            actions.insert(slot.clone(), action);
+        }
+        Ok(SyncPlan {
+            actions,
+            conflicts,
+            local,
+            index,
+            synced,
+        })
+    }
+    /// Applies `plan` and publishes the result. Conflicts without a resolution are left as they
+    /// are and come up again on the next sync. Returns the applied actions.
+    pub fn finish(
+        &mut self,
+        plan: SyncPlan,
+        resolutions: &BTreeMap<String, Resolution>,
+    ) -> Result<Vec<(String, SyncAction)>> {
+        let SyncPlan {
+            mut actions,
+            conflicts,
+            mut local,
+            mut index,
+            synced,
+        } = plan;
+        let mut unresolved = BTreeSet::new();
+        for conflict in conflicts {
+            let Some(resolution) = resolutions.get(&conflict.slot) else {
+                unresolved.insert(conflict.slot);
+                continue;
+            };
+            let action = match (resolution, &conflict.local, &conflict.remote) {
+                (Resolution::Local, None, _) => SyncAction::DeleteRemote,
+                (Resolution::Local, Some(_), _) => SyncAction::Push,
+                (Resolution::Remote, _, None) => SyncAction::DeleteLocal,
+                (Resolution::Remote, _, Some(_)) => SyncAction::Pull,
+                (Resolution::Both, _, None) => SyncAction::Push,
+                (Resolution::Both, None, Some(_)) => SyncAction::Pull,
+                (Resolution::Both, Some(_), Some(remote)) => {
+                    let copy = self.free_name(&conflict.slot, &remote.host, &local, &index)?;
+                    copy_slot(self.remote.root(), &conflict.slot, &self.store, &copy)?;
+                    rename_copy(&self.store, &conflict.slot, &copy, &remote.host)?;
+                    local.insert(
+                        copy.clone(),
+                        SlotState::scan(&self.store, &copy, &self.host)?,
+                    );
+                    actions.insert(copy, SyncAction::Push);
+                    SyncAction::Push
+                }
+            };
+            actions.insert(conflict.slot, action);
+        }
+
+        // Deferred slots keep their last synced state, so they come up again on the next sync
+        actions.retain(|slot, action| {
+            let deferred = self.loaded.contains(slot)
+                && matches!(action, SyncAction::Pull | SyncAction::DeleteLocal);
+            if deferred {
+                info!("not syncing {slot} while it is loaded");
+                unresolved.insert(slot.clone());
+            }
+            !deferred
+        });
+        let root = self.remote.root().to_path_buf();
+        for (slot, action) in &actions {
+            match action {
+                SyncAction::Push => {
+                    copy_slot(&self.store, slot, &root, slot)?;
+                    let mut state = local[slot].clone();
+                    state.host.clone_from(&self.host);
+                    index.slots.insert(slot.clone(), state);
+                }
+                SyncAction::Pull => copy_slot(&root, slot, &self.store, slot)?,
+                SyncAction::DeleteRemote => {
+                    remove_slot(&root, slot)?;
+                    index.slots.remove(slot);
+                }
+                SyncAction::DeleteLocal => remove_slot(&self.store, slot)?,
+            }
+        }
+        if !actions.is_empty() {
+            fs::write(root.join(INDEX_FILENAME), toml::to_string(&index)?)?;
+            self.remote
+                .push(&format!("Sync {} slots from {}", actions.len(), self.host))?;
+        }
+
+        let state = SyncState {
+            remote: self.remote.name(),
+            synced: index
+                .slots
+                .iter()
+                .filter(|(slot, _)| !unresolved.contains(*slot))
+                .map(|(slot, state)| (slot.clone(), state.hash.clone()))
+                .chain(
+                    synced
+                        .into_iter()
+                        .filter(|(slot, _)| unresolved.contains(slot)),
+                )
+                .collect(),
+        };
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.state_path, toml::to_string(&state)?)?;
+        Ok(actions.into_iter().collect())
+    }
+    fn local_slots(&self) -> Result<BTreeMap<String, SlotState>> {
+        let mut slots = BTreeMap::new();
+        if !self.store.exists() {
+            return Ok(slots);
+        }
+        for game in visible_dirs(&self.store)? {
+            for slot in visible_dirs(&self.store.join(&game))? {
+                let slot = format!("{game}/{slot}");
+                let state = SlotState::scan(&self.store, &slot, &self.host)?;
+                slots.insert(slot, state);
+            }
+        }
+        Ok(slots)
+    }
+
+    /// Name for the remote copy of a conflicting slot, e.g. `220/main (desktop)`.
+    fn free_name(
+        &self,
+        slot: &str,
+        host: &str,
+        local: &BTreeMap<String, SlotState>,
+        index: &RemoteIndex,
+    ) -> Result<String> {
+        let taken = |name: &String| {
+            local.contains_key(name)
+                || index.slots.contains_key(name)
+                || self.store.join(name).exists()
+        };
+        (1..)
+            .map(|i| match i {
+                1 => format!("{slot} ({host})"),
+                i => format!("{slot} ({host} {i})"),
+            })
+            .find(|name| !taken(name))
+            .context("no free slot name")
+    }
+}
+
+fn registry_path(store: &Path, slot: &str) -> PathBuf {
+    store.join(format!("{slot}.reg"))
+}
+
+fn name_path(store: &Path, slot: &str) -> PathBuf {
+    store.join(format!("{slot}.name"))
+}
+
+/// Names `copy`, a copy of `slot` from `host`, after the slot with the host appended.
+fn rename_copy(store: &Path, slot: &str, copy: &str, host: &str) -> Result<()> {
+    let name_file = name_path(store, copy);
+    // Slots without a name file are named after their directory
+    let name = match fs::read_to_string(&name_file) {
+        Ok(name) => name,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            slot.rsplit('/').next().unwrap_or(slot).to_string()
+        }
+        Err(err) => return Err(err.into()),
+    };
+    // Copies that cannot be named get a number when they are adopted
+    if let Ok(name) = name.trim().parse::<SlotName>()
+        && let Some(name) = name.with_suffix(host)
+    {
+        fs::write(name_file, name.as_str())?;
+    }
+    Ok(())
+}
+
+/// Names of the directories in `dir`, skipping hidden ones such as `.git`.
+fn visible_dirs(dir: &Path) -> Result<Vec<String>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str()
+            && !name.starts_with('.')
+        {
+            dirs.push(name.to_string());
+        }
+    }
+    Ok(dirs)
+}
+
+fn remove_slot(store: &Path, slot: &str) -> Result<()> {
+    let dir = store.join(slot);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    for file in [registry_path(store, slot), name_path(store, slot)] {
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    // Removes the directory of the game once its last slot is gone
+    if let Some(game) = dir.parent() {
+        let _ = fs::remove_dir(game);
+    }
+    Ok(())
+}
+
+fn copy_slot(from: &Path, slot: &str, to: &Path, target: &str) -> Result<()> {
+    remove_slot(to, target)?;
+    let dir = from.join(slot);
+    let target_dir = to.join(target);
+    // Git drops slots without files, so a missing directory is an empty slot
+    if dir.exists() {
+        copy_dir_all(&dir, &target_dir)
+    } else {
+        fs::create_dir_all(&target_dir)
+    }
+    .with_context(|| format!("failed to copy slot {}", dir.display()))?;
+    let registry = registry_path(from, slot);
+    if registry.exists() {
+        fs::copy(registry, registry_path(to, target))?;
+    }
+    let name_file = name_path(from, slot);
+    if name_file.exists() {
+        fs::copy(name_file, name_path(to, target))?;
+    }
+    Ok(())
+}
+
+fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(
+            toml::from_str(&contents).with_context(|| format!("invalid {}", path.display()))?,
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::{TempDir, tempdir};
+
+    use super::*;
+    use crate::dir_swapper::DirSwapper;
+
+    struct Machine {
+        dir: TempDir,
+        syncer: Syncer<Box<dyn SyncRemote>>,
+    }
+
+    impl Machine {
+        fn new(remote: Box<dyn SyncRemote>) -> Self {
+            let dir = tempdir().unwrap();
+            let syncer = Syncer::new(
+                dir.path().join("store"),
+                remote,
+                dir.path().join("sync-state.toml"),
+            );
+            Self { dir, syncer }
+        }
+        fn write(&self, slot: &str, contents: &str) {
+            let dir = self.dir.path().join("store").join(slot);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("save.dat"), contents).unwrap();
+        }
+        fn read(&self, slot: &str) -> Option<String> {
+            fs::read_to_string(self.dir.path().join("store").join(slot).join("save.dat")).ok()
+        }
+        fn sync(&mut self) -> Vec<(String, SyncAction)> {
+            let plan = self.syncer.prepare().unwrap();
+            assert!(plan.conflicts.is_empty());
+            self.syncer.finish(plan, &BTreeMap::new()).unwrap()
+        }
+    }
+
+    fn folder_machines() -> (TempDir, Machine, Machine) {
+        let remote = tempdir().unwrap();
+        let machine = || Machine::new(Box::new(FolderRemote::new(remote.path().to_path_buf())));
+        let (a, b) = (machine(), machine());
+        (remote, a, b)
+    }
+
+    #[test]
+    fn push_and_pull() {
+        let (_remote, mut a, mut b) = folder_machines();
+        a.write("220/main", "a");
+        fs::write(a.dir.path().join("store/220/main.reg"), "REGEDIT4").unwrap();
+        assert_eq!(a.sync(), [("220/main".to_string(), SyncAction::Push)]);
+        assert_eq!(b.sync(), [("220/main".to_string(), SyncAction::Pull)]);
+        assert_eq!(b.read("220/main").unwrap(), "a");
+        assert!(b.dir.path().join("store/220/main.reg").exists());
+        assert!(a.sync().is_empty());
+        assert!(b.sync().is_empty());
+
+        fs::remove_dir_all(b.dir.path().join("store/220/main")).unwrap();
+        assert_eq!(
+            b.sync(),
+            [("220/main".to_string(), SyncAction::DeleteRemote)]
+        );
+        assert_eq!(
+            a.sync(),
+            [("220/main".to_string(), SyncAction::DeleteLocal)]
+        );
+        assert!(!a.dir.path().join("store/220").exists());
+    }
+
+    #[test]
+    fn conflicts_are_resolved() {
+        let (_remote, mut a, mut b) = folder_machines();
+        a.write("220/main", "base");
+        a.write("220/other", "base");
+        a.sync();
+        b.sync();
+        a.write("220/main", "a");
+        a.write("220/other", "a");
+        b.write("220/main", "b");
+        b.write("220/other", "b");
+        a.sync();
+
+        let plan = b.syncer.prepare().unwrap();
+        let slots: Vec<_> = plan.conflicts.iter().map(|c| c.slot.as_str()).collect();
+        assert_eq!(slots, ["220/main", "220/other"]);
+        assert_eq!(plan.conflicts[0].remote.as_ref().unwrap().size, 1);
+        let resolutions = BTreeMap::from([("220/main".to_string(), Resolution::Both)]);
+        b.syncer.finish(plan, &resolutions).unwrap();
+        let copy = format!("220/main ({})", host_name());
+        assert_eq!(b.read("220/main").unwrap(), "b");
+        assert_eq!(b.read(&copy).unwrap(), "a");
+
+        // The unresolved conflict stays until it is resolved
+        let plan = b.syncer.prepare().unwrap();
+        assert_eq!(plan.conflicts.len(), 1);
+        let resolutions = BTreeMap::from([("220/other".to_string(), Resolution::Remote)]);
+        b.syncer.finish(plan, &resolutions).unwrap();
+        assert_eq!(b.read("220/other").unwrap(), "a");
+
+        a.sync();
+        assert_eq!(a.read("220/main").unwrap(), "b");
+        assert_eq!(a.read(&copy).unwrap(), "a");
+    }
+
+    #[test]
+    fn slot_loaded_by_move_keeps_its_saves() {
+        let (_remote, mut a, mut b) = folder_machines();
+        let primary_dir = a.dir.path().join("saves");
+        fs::create_dir(&primary_dir).unwrap();
+        fs::write(primary_dir.join("save.dat"), "first").unwrap();
+        fs::create_dir_all(a.dir.path().join("store/220")).unwrap();
+        let (main, other): (SlotName, SlotName) =
+            ("main".parse().unwrap(), "other".parse().unwrap());
+        let mut swapper = DirSwapper::build(
+            primary_dir.clone(),
+            a.dir.path().join("store/220"),
+            main.clone(),
+        )
+        .unwrap();
+        swapper.add_version(&other).unwrap();
+        swapper.snapshot().unwrap();
+        a.sync();
+
+        // Loading by moving leaves an empty placeholder in the store
+        swapper.set_active(other).unwrap();
+        swapper.set_active(main.clone()).unwrap();
+        fs::write(primary_dir.join("save.dat"), "second").unwrap();
+        let main_dir = swapper.version_dir_of(&main).unwrap().unwrap();
+        assert_eq!(fs::read_dir(&main_dir).unwrap().count(), 0);
+
+        // What `SaveManager::prepare_sync` does before every sync
+        swapper.snapshot().unwrap();
+        let slot = format!("220/{}", main_dir.file_name().unwrap().to_str().unwrap());
+        assert!(a.sync().contains(&(slot.clone(), SyncAction::Push)));
+        b.sync();
+        assert_eq!(b.read(&slot).unwrap(), "second");
+    }
+
+    #[test]
+    fn slot_names_are_kept_on_other_machines() {
+        let (_remote, mut a, mut b) = folder_machines();
+        let swapper = |machine: &Machine| {
+            fs::create_dir_all(machine.dir.path().join("store/220")).unwrap();
+            DirSwapper::build(
+                machine.dir.path().join("saves"),
+                machine.dir.path().join("store/220"),
+                "main".parse().unwrap(),
+            )
+            .unwrap()
+        };
+        let other: SlotName = "other".parse().unwrap();
+        let mut swapper_a = swapper(&a);
+        swapper_a.add_version(&other).unwrap();
+        let other_dir = swapper_a.version_dir_of(&other).unwrap().unwrap();
+        fs::write(other_dir.join("save.dat"), "a").unwrap();
+        a.sync();
+
+        let mut swapper_b = swapper(&b);
+        b.sync();
+        assert!(swapper_b.adopt_versions().unwrap());
+        assert!(!swapper_b.adopt_versions().unwrap());
+        let versions: Vec<_> = swapper_b
+            .versions()
+            .unwrap()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(versions, ["main", "main (2)", "other"]);
+        let other_dir = swapper_b.version_dir_of(&other).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(other_dir.join("save.dat")).unwrap(), "a");
+
+        // Both machines change the slot, and the remote one is kept as a copy
+        fs::write(other_dir.join("save.dat"), "b").unwrap();
+        let other_dir = swapper_a.version_dir_of(&other).unwrap().unwrap();
+        fs::write(other_dir.join("save.dat"), "a2").unwrap();
+        a.sync();
+        let plan = b.syncer.prepare().unwrap();
+        let resolutions = plan
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.slot.clone(), Resolution::Both))
+            .collect();
+        b.syncer.finish(plan, &resolutions).unwrap();
+        assert!(swapper_b.adopt_versions().unwrap());
+        let copy: SlotName = format!("other ({})", host_name()).parse().unwrap();
+        let copy_dir = swapper_b.version_dir_of(&copy).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(copy_dir.join("save.dat")).unwrap(), "a2");
+    }
+
+    #[test]
+    fn loaded_slots_are_not_deleted_or_replaced() {
+        let (_remote, mut a, mut b) = folder_machines();
+        let primary_dir = a.dir.path().join("saves");
+        fs::create_dir(&primary_dir).unwrap();
+        fs::write(primary_dir.join("save.dat"), "a").unwrap();
+        fs::create_dir_all(a.dir.path().join("store/220")).unwrap();
+        let (main, other): (SlotName, SlotName) =
+            ("main".parse().unwrap(), "other".parse().unwrap());
+        let mut swapper = DirSwapper::build(
+            primary_dir.clone(),
+            a.dir.path().join("store/220"),
+            main.clone(),
+        )
+        .unwrap();
+        swapper.add_version(&other).unwrap();
+        swapper.snapshot().unwrap();
+        let main_dir = swapper.version_dir_of(&main).unwrap().unwrap();
+        let slot = format!("220/{}", main_dir.file_name().unwrap().to_str().unwrap());
+        a.syncer.set_loaded(BTreeSet::from([slot.clone()]));
+        a.sync();
+
+        // Another machine changes the loaded slot and then deletes it
+        b.sync();
+        b.write(&slot, "b");
+        b.sync();
+        assert!(a.sync().is_empty());
+        assert_eq!(b.read(&slot).unwrap(), "b");
+        remove_slot(&b.dir.path().join("store"), &slot).unwrap();
+        assert_eq!(b.sync(), [(slot.clone(), SyncAction::DeleteRemote)]);
+        assert!(a.sync().is_empty());
+        assert!(main_dir.exists());
+        swapper.snapshot().unwrap();
+
+        // Once another slot is loaded, the deletion goes through
+        swapper.set_active(other.clone()).unwrap();
+        let other_dir = swapper.version_dir_of(&other).unwrap().unwrap();
+        a.syncer.set_loaded(BTreeSet::from([format!(
+            "220/{}",
+            other_dir.file_name().unwrap().to_str().unwrap()
+        )]));
+        assert_eq!(a.sync(), [(slot, SyncAction::DeleteLocal)]);
+        assert!(!main_dir.exists());
+    }
+
+    #[test]
+    fn copies_of_long_names_stay_within_the_limit() {
+        let (_remote, mut a, mut b) = folder_machines();
+        let long = "a".repeat(64);
+        for machine in [&a, &b] {
+            machine.write("220/slot", "contents");
+            fs::write(machine.dir.path().join("store/220/slot.name"), &long).unwrap();
+        }
+        a.write("220/slot", "a");
+        a.sync();
+        b.write("220/slot", "b");
+        let plan = b.syncer.prepare().unwrap();
+        let resolutions = BTreeMap::from([("220/slot".to_string(), Resolution::Both)]);
+        b.syncer.finish(plan, &resolutions).unwrap();
+
+        let copy = format!("220/slot ({})", host_name());
+        let name = fs::read_to_string(name_path(&b.dir.path().join("store"), &copy)).unwrap();
+        let name: SlotName = name.parse().unwrap();
+        assert!(name.starts_with("aaaa"));
+        assert!(name.ends_with(&format!(" ({})", host_name())));
+    }
+
+    #[test]
+    fn missing_folder_is_an_error() {
+        let mut a = Machine::new(Box::new(FolderRemote::new(PathBuf::from(
+            "/nonexistent/vittusave",
+        ))));
+        a.write("220/main", "a");
+        assert!(a.syncer.prepare().is_err());
+    }
+
+    #[test]
+    fn git_remote() {
+        let remote = tempdir().unwrap();
+        let status = std::process::Command::new("git")
+            .args(["init", "--bare", "--quiet"])
+            .arg(remote.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let url = remote.path().to_string_lossy().into_owned();
+        let machine = || {
+            let clone = tempdir().unwrap();
+            let git = GitRemote::new(url.clone(), default_branch(), clone.path().join("sync"));
+            (Machine::new(Box::new(git)), clone)
+        };
+        let ((mut a, _a_clone), (mut b, _b_clone)) = (machine(), machine());
+
+        a.write("220/main", "a");
+        assert_eq!(a.sync(), [("220/main".to_string(), SyncAction::Push)]);
+        assert_eq!(b.sync(), [("220/main".to_string(), SyncAction::Pull)]);
+        assert_eq!(b.read("220/main").unwrap(), "a");
+
+        b.write("220/main", "b");
+        b.sync();
+        assert_eq!(a.sync(), [("220/main".to_string(), SyncAction::Pull)]);
+        assert_eq!(a.read("220/main").unwrap(), "b");
+    }
+}
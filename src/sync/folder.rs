@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use super::SyncRemote;
+
+/// A store in a directory that is kept in sync by other means, e.g. a NAS mount or a Syncthing
+/// folder.
+#[derive(Debug)]
+pub struct FolderRemote {
+    root: PathBuf,
+}
+
+impl FolderRemote {
+    #[inline]
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl SyncRemote for FolderRemote {
+    fn name(&self) -> String {
+        self.root.display().to_string()
+    }
+    fn root(&self) -> &Path {
+        &self.root
+    }
+    fn pull(&mut self) -> Result<()> {
+        // Not creating the directory, an unmounted share would silently become a local folder
+        if !self.root.is_dir() {
+            bail!(
+                "sync folder {} does not exist, is it mounted?",
+                self.root.display()
+            );
+        }
+        Ok(())
+    }
+    fn push(&mut self, _message: &str) -> Result<()> {
+        Ok(())
+    }
+}
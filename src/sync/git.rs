@@ -0,0 +1,116 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+
+use super::SyncRemote;
+
+/// A store in a git repository, synced through a clone in `work_dir` with the `git` executable.
+#[derive(Debug)]
+pub struct GitRemote {
+    url: String,
+    branch: String,
+    work_dir: PathBuf,
+}
+
+impl GitRemote {
+    #[inline]
+    pub fn new(url: String, branch: String, work_dir: PathBuf) -> Self {
+        Self {
+            url,
+            branch,
+            work_dir,
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.work_dir)
+            .args(args)
+            .output()
+            .context("failed to run git, is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl SyncRemote for GitRemote {
+    fn name(&self) -> String {
+        format!("{}#{}", self.url, self.branch)
+    }
+    fn root(&self) -> &Path {
+        &self.work_dir
+    }
+    fn pull(&mut self) -> Result<()> {
+        if !self.work_dir.join(".git").exists() {
+            fs::create_dir_all(&self.work_dir)?;
+            self.git(&["init", "--quiet"])?;
+            self.git(&["remote", "add", "origin", &self.url])?;
+        } else {
+            self.git(&["remote", "set-url", "origin", &self.url])?;
+        }
+        self.git(&["fetch", "--quiet", "origin"])?;
+        let upstream = format!("refs/remotes/origin/{}", self.branch);
+        if self
+            .git(&["rev-parse", "--verify", "--quiet", &upstream])
+            .is_ok()
+        {
+            self.git(&[
+                "checkout",
+                "--quiet",
+                "--force",
+                "-B",
+                &self.branch,
+                &upstream,
+            ])?;
+            self.git(&["clean", "--quiet", "-fd"])?;
+        } else if self
+            .git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .is_err()
+        {
+            // Empty repository, the first push creates the branch
+            self.git(&[
+                "symbolic-ref",
+                "HEAD",
+                &format!("refs/heads/{}", self.branch),
+            ])?;
+        }
+        Ok(())
+    }
+    fn push(&mut self, message: &str) -> Result<()> {
+        self.git(&["add", "--all"])?;
+        if self.git(&["status", "--porcelain"])?.trim().is_empty() {
+            return Ok(());
+        }
+        // Commits need an identity, use a placeholder if the user has not configured one
+        let mut commit = Vec::new();
+        if self.git(&["config", "user.email"]).is_err() {
+            commit.extend([
+                "-c",
+                "user.name=VittuSave",
+                "-c",
+                "user.email=vittusave@localhost",
+            ]);
+        }
+        commit.extend(["commit", "--quiet", "-m", message]);
+        self.git(&commit)?;
+        self.git(&[
+            "push",
+            "--quiet",
+            "origin",
+            &format!("HEAD:refs/heads/{}", self.branch),
+        ])
+        .context("failed to push, sync again if the remote changed meanwhile")?;
+        Ok(())
+    }
+}
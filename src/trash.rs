@@ -0,0 +1,309 @@
+use std::{
+    fmt, fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{TRASH_DIR, TRASH_FILENAME},
+    dir_swapper::TrashedVersion,
+    save_manager::{GameId, GameSaves, SlotMeta},
+    slot_name::SlotName,
+    utils,
+};
+
+/// File in every trash item that describes it, see [`TrashItem`].
+const ITEM_FILENAME: &str = "item.toml";
+
+/// Directory of a trashed game with all of its slots.
+pub const TRASHED_SLOTS: &str = "slots";
+
+/// Limits of the trash configured in `crate::consts::TRASH_FILENAME`, e.g.
+///
+/// ```toml
+/// max_age_days = 7
+/// max_size = 10_000_000_000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// Items deleted longer ago are removed for good
+    pub max_age_days: Option<u64>,
+    /// Total size in bytes above which the oldest items are removed for good
+    pub max_size: Option<u64>,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: Some(30),
+            max_size: None,
+        }
+    }
+}
+
+impl TrashConfig {
+    pub fn read() -> Result<Self> {
+        Ok(utils::read_data(TRASH_FILENAME)?.unwrap_or_default())
+    }
+}
+
+/// What a trash item was before it was deleted.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Trashed {
+    /// A slot moved out by [`crate::dir_swapper::DirSwapper::trash_version`]
+    Slot {
+        game: GameId,
+        name: SlotName,
+        meta: SlotMeta,
+        version: TrashedVersion,
+    },
+    /// A game whose slots are in [`TRASHED_SLOTS`]
+    Game {
+        game: GameId,
+        name: Option<String>,
+        saves: GameSaves,
+    },
+}
+
+impl fmt::Display for Trashed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trashed::Slot { game, name, .. } => write!(f, "slot \"{name}\" of {game}"),
+            Trashed::Game {
+                name: Some(name), ..
+            } => write!(f, "game {name}"),
+            Trashed::Game { game, .. } => write!(f, "game {game}"),
+        }
+    }
+}
+
+/// A deleted slot or game in the [`Trash`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashItem {
+    /// Name of the directory of the item
+    #[serde(skip)]
+    pub id: String,
+    pub deleted: SystemTime,
+    /// Total size of the files of the item
+    #[serde(skip)]
+    pub size: u64,
+    pub trashed: Trashed,
+}
+
+/// Deleted slots and games, each in a directory of its own until it is restored, purged or
+/// expires.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        Self::new(TRASH_DIR.clone())
+    }
+}
+
+impl Trash {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+    /// Directory of the item `id`, where its contents are moved before [`Trash::add`].
+    #[inline]
+    pub fn item_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+    /// Describes the contents of the item `id` as deleted now.
+    pub fn add(&self, id: &str, trashed: Trashed) -> Result<()> {
+        let item = TrashItem {
+            id: id.to_string(),
+            deleted: SystemTime::now(),
+            size: 0,
+            trashed,
+        };
+        let dir = self.item_dir(id);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(ITEM_FILENAME), toml::to_string(&item)?)?;
+        Ok(())
+    }
+    /// Returns the item `id`, or `Ok(None)` if it does not exist.
+    pub fn get(&self, id: &str) -> Result<Option<TrashItem>> {
+        let dir = self.item_dir(id);
+        let file = dir.join(ITEM_FILENAME);
+        if !fs::exists(&file)? {
+            return Ok(None);
+        }
+        let mut item: TrashItem = toml::from_str(&fs::read_to_string(file)?)?;
+        item.id = id.to_string();
+        item.size = utils::dir_stats(&dir)?.0;
+        Ok(Some(item))
+    }
+    /// Returns all items, oldest first. Directories that are not described are skipped.
+    pub fn items(&self) -> Result<Vec<TrashItem>> {
+        if !fs::exists(&self.dir)? {
+            return Ok(Vec::new());
+        }
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let Ok(id) = entry?.file_name().into_string() else {
+                continue;
+            };
+            match self.get(&id) {
+                Ok(Some(item)) => items.push(item),
+                Ok(None) => {}
+                Err(err) => warn!("skipping trash item {id}: {err:#}"),
+            }
+        }
+        items.sort_by_key(|item| item.deleted);
+        Ok(items)
+    }
+    /// Deletes the item `id` for good.
+    pub fn purge(&self, id: &str) -> io::Result<()> {
+        match fs::remove_dir_all(self.item_dir(id)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+    /// Purges the items that are older than the configured age as of `now`, and then the oldest
+    /// ones until the trash fits into the configured size. Returns the purged items.
+    pub fn expire(&self, config: &TrashConfig, now: SystemTime) -> Result<Vec<TrashItem>> {
+        let max_age = config
+            .max_age_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60));
+        let mut size: u64 = 0;
+        let mut kept = Vec::new();
+        let mut expired = Vec::new();
+        for item in self.items()? {
+            if max_age.is_some_and(|max_age| {
+                now.duration_since(item.deleted)
+                    .is_ok_and(|age| age > max_age)
+            }) {
+                expired.push(item);
+            } else {
+                size += item.size;
+                kept.push(item);
+            }
+        }
+        if let Some(max_size) = config.max_size {
+            let mut kept = kept.into_iter();
+            while size > max_size
+                && let Some(item) = kept.next()
+            {
+                size -= item.size;
+                expired.push(item);
+            }
+        }
+        for item in &expired {
+            self.purge(&item.id)?;
+        }
+        Ok(expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn add_slot(trash: &Trash, id: &str, name: &str, size: usize) {
+        let dir = trash.item_dir(id);
+        fs::create_dir_all(dir.join("version")).unwrap();
+        fs::write(dir.join("version/save.dat"), vec![0; size]).unwrap();
+        trash
+            .add(
+                id,
+                Trashed::Slot {
+                    game: GameId::Steam(1),
+                    name: name.parse().unwrap(),
+                    meta: SlotMeta::default(),
+                    version: TrashedVersion {
+                        id: id.to_string(),
+                        active: false,
+                    },
+                },
+            )
+            .unwrap();
+    }
+
+    fn ids(items: &[TrashItem]) -> Vec<&str> {
+        items.iter().map(|item| item.id.as_str()).collect()
+    }
+
+    #[test]
+    fn items_are_listed_and_purged() {
+        let dir = tempdir().unwrap();
+        let trash = Trash::new(dir.path().to_path_buf());
+        add_slot(&trash, "a", "First", 10);
+        fs::create_dir(trash.item_dir("unknown")).unwrap();
+
+        let items = trash.items().unwrap();
+        assert_eq!(ids(&items), ["a"]);
+        assert!(items[0].size >= 10);
+        assert!(matches!(
+            &items[0].trashed,
+            Trashed::Slot { name, .. } if name.as_str() == "First"
+        ));
+
+        trash.purge("a").unwrap();
+        assert!(trash.items().unwrap().is_empty());
+        assert!(trash.get("a").unwrap().is_none());
+    }
+
+    #[test]
+    fn games_are_described() {
+        let dir = tempdir().unwrap();
+        let trash = Trash::new(dir.path().to_path_buf());
+        trash
+            .add(
+                "g",
+                Trashed::Game {
+                    game: GameId::Steam(2),
+                    name: Some("Game".to_string()),
+                    saves: GameSaves::default(),
+                },
+            )
+            .unwrap();
+
+        let item = trash.get("g").unwrap().unwrap();
+        assert_eq!(item.trashed.to_string(), "game Game");
+    }
+
+    #[test]
+    fn old_items_expire() {
+        let dir = tempdir().unwrap();
+        let trash = Trash::new(dir.path().to_path_buf());
+        add_slot(&trash, "a", "First", 10);
+        let config = TrashConfig {
+            max_age_days: Some(1),
+            max_size: None,
+        };
+
+        assert!(trash.expire(&config, SystemTime::now()).unwrap().is_empty());
+        let later = SystemTime::now() + Duration::from_secs(2 * 24 * 60 * 60);
+        assert_eq!(ids(&trash.expire(&config, later).unwrap()), ["a"]);
+        assert!(trash.items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn oldest_items_expire_beyond_max_size() {
+        let dir = tempdir().unwrap();
+        let trash = Trash::new(dir.path().to_path_buf());
+        add_slot(&trash, "a", "First", 1000);
+        std::thread::sleep(Duration::from_millis(10));
+        add_slot(&trash, "b", "Second", 1000);
+        let config = TrashConfig {
+            max_age_days: None,
+            max_size: Some(1500),
+        };
+
+        let expired = trash.expire(&config, SystemTime::now()).unwrap();
+        assert_eq!(ids(&expired), ["a"]);
+        assert_eq!(ids(&trash.items().unwrap()), ["b"]);
+    }
+}
@@ -0,0 +1,318 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sysinfo::Disks;
+
+use crate::{consts::QUOTA_FILENAME, save_manager::GameId, slot_name::SlotName, utils};
+
+/// Disk space used by a slot, see `crate::dir_swapper::DirSwapper::usage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotUsage {
+    pub name: SlotName,
+    /// Stored copy and registry file of the slot
+    pub size: u64,
+    /// Snapshots in the history of the slot with the size of the files that only they hold,
+    /// which is what removing them frees, oldest first
+    pub snapshots: Vec<(String, u64)>,
+    /// Files hard linked between several snapshots, counted once
+    pub shared_history: u64,
+}
+
+impl SlotUsage {
+    pub fn history_size(&self) -> u64 {
+        self.snapshots.iter().map(|(_, size)| size).sum::<u64>() + self.shared_history
+    }
+    pub fn total(&self) -> u64 {
+        self.size + self.history_size()
+    }
+}
+
+/// Disk space used by the slots of a game and by its slots in the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameUsage {
+    pub id: GameId,
+    pub name: Option<String>,
+    pub slots: Vec<SlotUsage>,
+    pub trash: u64,
+}
+
+impl GameUsage {
+    pub fn total(&self) -> u64 {
+        self.slots.iter().map(SlotUsage::total).sum::<u64>() + self.trash
+    }
+    /// Name of the game, or its ID if the name is unknown.
+    pub fn title(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.id.to_string())
+    }
+}
+
+/// Disk space used by the slot store, see `crate::save_manager::SaveManager::usage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageReport {
+    /// Largest first
+    pub games: Vec<GameUsage>,
+    /// Everything in the trash, including removed games
+    pub trash: u64,
+    /// Free space on the disk of the slot store, if it is known
+    pub available: Option<u64>,
+}
+
+impl UsageReport {
+    pub fn total(&self) -> u64 {
+        self.games
+            .iter()
+            .flat_map(|game| &game.slots)
+            .map(SlotUsage::total)
+            .sum::<u64>()
+            + self.trash
+    }
+}
+
+/// A snapshot chosen by [`QuotaConfig::snapshots_to_prune`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot<'a> {
+    pub game: GameId,
+    pub slot: &'a SlotName,
+    pub name: &'a str,
+    pub size: u64,
+}
+
+/// Limits on the disk space of the slot store configured in `crate::consts::QUOTA_FILENAME`,
+/// e.g.
+///
+/// ```toml
+/// max_size = 20_000_000_000
+/// max_game_size = 5_000_000_000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Total size in bytes of all slots, snapshots and the trash
+    pub max_size: Option<u64>,
+    /// Size in bytes of the slots, snapshots and trashed slots of a single game
+    pub max_game_size: Option<u64>,
+    /// Free disk space in bytes below which a warning is shown
+    pub min_free_space: u64,
+    /// Newest snapshots of every slot that are never pruned
+    pub keep_snapshots: usize,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            max_game_size: None,
+            min_free_space: 1 << 30,
+            keep_snapshots: 1,
+        }
+    }
+}
+
+impl QuotaConfig {
+    pub fn read() -> Result<Self> {
+        Ok(utils::read_data(QUOTA_FILENAME)?.unwrap_or_default())
+    }
+    /// Snapshots to remove for `report` to fit into the quotas, oldest first within every game
+    /// over its quota and then across all games. The newest [`QuotaConfig::keep_snapshots`] of
+    /// every slot are kept even if the quotas cannot be met. Files hard linked between snapshots
+    /// do not count towards the space a snapshot frees.
+    pub fn snapshots_to_prune<'a>(&self, report: &'a UsageReport) -> Vec<Snapshot<'a>> {
+        let candidates = |game: &'a GameUsage| {
+            let mut snapshots: Vec<_> = game
+                .slots
+                .iter()
+                .flat_map(|slot| {
+                    let prunable = slot.snapshots.len().saturating_sub(self.keep_snapshots);
+                    slot.snapshots[..prunable]
+                        .iter()
+                        .map(move |(name, size)| Snapshot {
+                            game: game.id,
+                            slot: &slot.name,
+                            name,
+                            size: *size,
+                        })
+                })
+                .collect();
+            // Snapshot names are timestamps
+            snapshots.sort_by_key(|snapshot| snapshot.name);
+            snapshots
+        };
+        let mut pruned = Vec::new();
+        let mut freed = HashMap::new();
+        if let Some(max_game_size) = self.max_game_size {
+            for game in &report.games {
+                let mut excess = game.total().saturating_sub(max_game_size);
+                for snapshot in candidates(game) {
+                    if excess == 0 {
+                        break;
+                    }
+                    excess = excess.saturating_sub(snapshot.size);
+                    *freed.entry(game.id).or_insert(0) += snapshot.size;
+                    pruned.push(snapshot);
+                }
+            }
+        }
+        if let Some(max_size) = self.max_size {
+            let mut excess = report
+                .total()
+                .saturating_sub(freed.values().sum::<u64>())
+                .saturating_sub(max_size);
+            let mut remaining: Vec<_> = report
+                .games
+                .iter()
+                .flat_map(candidates)
+                .filter(|snapshot| !pruned.contains(snapshot))
+                .collect();
+            remaining.sort_by_key(|snapshot| snapshot.name);
+            for snapshot in remaining {
+                if excess == 0 {
+                    break;
+                }
+                excess = excess.saturating_sub(snapshot.size);
+                pruned.push(snapshot);
+            }
+        }
+        pruned
+    }
+    /// Describes the quotas that `report` exceeds and whether the disk is running out of space.
+    pub fn warnings(&self, report: &UsageReport) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(max_game_size) = self.max_game_size {
+            for game in report
+                .games
+                .iter()
+                .filter(|game| game.total() > max_game_size)
+            {
+                warnings.push(format!(
+                    "{} uses {}, more than the quota of {} per game",
+                    game.title(),
+                    utils::format_size(game.total()),
+                    utils::format_size(max_game_size)
+                ));
+            }
+        }
+        if let Some(max_size) = self.max_size
+            && report.total() > max_size
+        {
+            warnings.push(format!(
+                "Slots use {}, more than the quota of {}",
+                utils::format_size(report.total()),
+                utils::format_size(max_size)
+            ));
+        }
+        if let Some(available) = report.available
+            && available < self.min_free_space
+        {
+            warnings.push(format!(
+                "Only {} of disk space is left for slots",
+                utils::format_size(available)
+            ));
+        }
+        warnings
+    }
+}
+
+/// Free space on the disk that `path` is on, if the disk can be found.
+pub fn available_space(path: &Path) -> Option<u64> {
+    let path = path.ancestors().find_map(|path| path.canonicalize().ok())?;
+    Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(name: &str, size: u64, snapshots: &[(&str, u64)]) -> SlotUsage {
+        SlotUsage {
+            name: name.parse().unwrap(),
+            size,
+            snapshots: snapshots
+                .iter()
+                .map(|(name, size)| (name.to_string(), *size))
+                .collect(),
+            shared_history: 0,
+        }
+    }
+
+    fn report() -> UsageReport {
+        UsageReport {
+            games: vec![
+                GameUsage {
+                    id: GameId::Steam(1),
+                    name: Some("First".to_string()),
+                    slots: vec![
+                        slot("a", 100, &[("001", 50), ("004", 50), ("005", 50)]),
+                        slot("b", 100, &[("002", 50)]),
+                    ],
+                    trash: 0,
+                },
+                GameUsage {
+                    id: GameId::Steam(2),
+                    name: None,
+                    slots: vec![slot("c", 100, &[("003", 50), ("006", 50)])],
+                    trash: 100,
+                },
+            ],
+            trash: 100,
+            available: Some(1 << 20),
+        }
+    }
+
+    fn names<'a>(snapshots: &[Snapshot<'a>]) -> Vec<&'a str> {
+        snapshots.iter().map(|snapshot| snapshot.name).collect()
+    }
+
+    #[test]
+    fn game_quota_prunes_oldest_snapshots_of_the_game() {
+        let report = report();
+        assert_eq!(report.games[0].total(), 400);
+        let config = QuotaConfig {
+            max_game_size: Some(350),
+            ..Default::default()
+        };
+        assert_eq!(names(&config.snapshots_to_prune(&report)), ["001"]);
+    }
+
+    #[test]
+    fn total_quota_prunes_oldest_snapshots_and_keeps_the_newest() {
+        let report = report();
+        assert_eq!(report.total(), 700);
+        let config = QuotaConfig {
+            max_size: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(names(&config.snapshots_to_prune(&report)), ["001", "003"]);
+        // Only one snapshot of every slot is left to prune
+        let config = QuotaConfig {
+            max_size: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&config.snapshots_to_prune(&report)),
+            ["001", "003", "004"]
+        );
+    }
+
+    #[test]
+    fn warnings_name_exceeded_quotas() {
+        let config = QuotaConfig {
+            max_size: Some(1000),
+            max_game_size: Some(300),
+            ..Default::default()
+        };
+        let warnings = config.warnings(&report());
+        assert_eq!(
+            warnings,
+            [
+                "First uses 400 B, more than the quota of 300 B per game",
+                "Only 1.0 MiB of disk space is left for slots",
+            ]
+        );
+    }
+}
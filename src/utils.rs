@@ -1,16 +1,21 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::{
-    cell::{Ref, RefCell},
+    collections::HashMap,
     fs, io,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 use uuid::Uuid;
 
-use crate::consts::{CACHE_DIR, DATA_DIR, FILE_EXTENSION};
+use crate::{
+    consts::{CACHE_DIR, DATA_DIR, FILE_EXTENSION},
+    copy::DirCopier,
+};
 
 // TODO: Move methods to relevant struct or remove them
-fn build_data_path(filename: impl AsRef<Path>) -> PathBuf {
+/// Path of `filename` in `crate::consts::DATA_DIR`, as used by [`write_data`] and [`read_data`].
+pub fn build_data_path(filename: impl AsRef<Path>) -> PathBuf {
     let mut path = DATA_DIR.clone();
     path.push(filename);
     path.set_extension(FILE_EXTENSION);
@@ -46,27 +51,148 @@ pub fn read_data<T: DeserializeOwned>(filename: impl AsRef<Path>) -> Result<Opti
     }
 }
 
-pub fn remove_dir_contents(path: impl AsRef<Path>) -> io::Result<()> {
-    fs::create_dir_all(&path)?;
-    fs::remove_dir_all(&path)?;
-    fs::create_dir(&path)
+/// Returns the total size of the files at `path` and the most recent modification time of `path`
+/// or anything inside it. Symbolic links are not followed.
+pub fn dir_stats(path: impl AsRef<Path>) -> io::Result<(u64, Option<SystemTime>)> {
+    let mut size = 0;
+    let mut modified = None;
+    let mut stack = vec![path.as_ref().to_path_buf()];
+    while let Some(path) = stack.pop() {
+        let metadata = fs::symlink_metadata(&path)?;
+        modified = modified.max(metadata.modified().ok());
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                stack.push(entry?.path());
+            }
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok((size, modified))
 }
 
-// https://stackoverflow.com/a/65192210
-// TODO: iteration instead of recursion
-pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
-    fs::create_dir_all(&dst)?;
-    for entry in fs::read_dir(src)? {
+/// Identifies the data of a file, which hard links to the file share.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileId {
+    Inode {
+        dev: u64,
+        ino: u64,
+    },
+    /// Hard links cannot be told apart on this platform, so every path is its own file
+    Path(PathBuf),
+}
+
+/// Adds the files at `path` to `files` with their size, so that files hard linked to each other
+/// are counted once. Symbolic links are not followed.
+pub fn collect_files(path: impl AsRef<Path>, files: &mut HashMap<FileId, u64>) -> io::Result<()> {
+    let mut stack = vec![path.as_ref().to_path_buf()];
+    while let Some(path) = stack.pop() {
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                stack.push(entry?.path());
+            }
+            continue;
+        }
+        #[cfg(unix)]
+        let id = {
+            use std::os::unix::fs::MetadataExt;
+            FileId::Inode {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+            }
+        };
+        #[cfg(not(unix))]
+        let id = FileId::Path(path);
+        files.insert(id, metadata.len());
+    }
+    Ok(())
+}
+
+/// Formats how long ago something happened, e.g. `5 min ago`.
+pub fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        secs @ 0..60 => format!("{secs} s ago"),
+        secs @ 60..3600 => format!("{} min ago", secs / 60),
+        secs @ 3600..86400 => format!("{} h ago", secs / 3600),
+        secs => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// Formats a size in bytes with a binary unit, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Lists the files and directories inside `dir` relative to it, sorted. Symbolic links are listed
+/// as files.
+pub fn walk_dir(dir: &Path) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut stack = vec![PathBuf::new()];
+    while let Some(relative) = stack.pop() {
+        for entry in fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                dirs.push(path.clone());
+                stack.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    dirs.sort();
+    Ok((files, dirs))
+}
+
+/// Removes everything inside `path`, creating it if it does not exist. The directory itself is
+/// kept, so that symbolic links to it stay in place.
+pub fn remove_dir_contents(path: impl AsRef<Path>) -> io::Result<()> {
+    fs::create_dir_all(&path)?;
+    for entry in fs::read_dir(&path)? {
         let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
         } else {
-            fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
+            fs::remove_file(entry.path())?;
         }
     }
     Ok(())
 }
+
+/// Copies the contents of `src` into `dst` with their metadata, see [`DirCopier`].
+pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
+    DirCopier::new().copy(src, dst).map(|_| ())
+}
+
+/// Moves the file or directory `from` to `to`, copying it if they are on different filesystems.
+pub fn move_path(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            if fs::symlink_metadata(from)?.is_dir() {
+                copy_dir_all(from, to)?;
+                fs::remove_dir_all(from)
+            } else {
+                fs::copy(from, to)?;
+                fs::remove_file(from)
+            }
+        }
+        result => result,
+    }
+}
 pub trait CachedState: private::Sealed {}
 pub mod states {
     use serde::{Deserialize, Serialize, de::DeserializeOwned};
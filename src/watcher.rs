@@ -0,0 +1,243 @@
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use log::warn;
+use notify::{Config, Event, EventKind, PollWatcher, RecursiveMode, Watcher};
+
+use crate::dir_swapper::DirSwapper;
+
+/// How often [`record_while`] checks whether it should stop.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How changes are detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchBackend {
+    /// inotify, FSEvents or ReadDirectoryChangesW
+    Native,
+    /// Scanning the directory periodically, which also works on network shares
+    Polling,
+}
+
+/// Watches a directory recursively for changes.
+pub struct DirWatcher {
+    // Stops watching when dropped
+    _watcher: Box<dyn Watcher + Send>,
+    events: Receiver<notify::Result<Event>>,
+    backend: WatchBackend,
+}
+
+impl fmt::Debug for DirWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirWatcher")
+            .field("backend", &self.backend)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DirWatcher {
+    /// Watches `dir` with the native backend of the platform, falling back to polling every
+    /// `poll_interval` if it is unavailable, e.g. when inotify runs out of watches.
+    pub fn new(dir: &Path, poll_interval: Duration) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let native = notify::recommended_watcher(sender.clone()).and_then(|mut watcher| {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        match native {
+            Ok(watcher) => Ok(Self {
+                _watcher: Box::new(watcher),
+                events,
+                backend: WatchBackend::Native,
+            }),
+            Err(err) => {
+                warn!("failed to watch {} natively, polling: {err}", dir.display());
+                Self::with_polling(dir, poll_interval, sender, events)
+            }
+        }
+    }
+    /// Watches `dir` by scanning it every `poll_interval`.
+    pub fn polling(dir: &Path, poll_interval: Duration) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        Self::with_polling(dir, poll_interval, sender, events)
+    }
+    fn with_polling(
+        dir: &Path,
+        poll_interval: Duration,
+        sender: Sender<notify::Result<Event>>,
+        events: Receiver<notify::Result<Event>>,
+    ) -> Result<Self> {
+        let mut watcher =
+            PollWatcher::new(sender, Config::default().with_poll_interval(poll_interval))?;
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: Box::new(watcher),
+            events,
+            backend: WatchBackend::Polling,
+        })
+    }
+    #[inline]
+    pub fn backend(&self) -> WatchBackend {
+        self.backend
+    }
+    /// Blocks until something in the directory changed and then nothing changed for `debounce`,
+    /// so that a game is done writing its save. Returns `Ok(false)` if nothing changed before
+    /// `deadline`.
+    pub fn wait_for_changes(&self, debounce: Duration, deadline: Instant) -> Result<bool> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(timeout) {
+                Ok(event) => {
+                    if is_change(&event?) {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => bail!("file watcher stopped"),
+            }
+        }
+        loop {
+            match self.events.recv_timeout(debounce) {
+                Ok(event) => {
+                    event?;
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(true),
+                Err(RecvTimeoutError::Disconnected) => bail!("file watcher stopped"),
+            }
+        }
+    }
+}
+
+fn is_change(event: &Event) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+}
+
+/// When automatic snapshots are taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    /// Time without writes after which a snapshot is taken
+    pub debounce: Duration,
+    /// Number of snapshots kept per slot
+    pub history_limit: usize,
+    /// Scan interval if the directory has to be polled
+    pub poll_interval: Duration,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(5),
+            history_limit: 20,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Records a snapshot in the history of the active version of `swapper` whenever its primary
+/// directory settles after changes, until `stop` is set. Returns the number of snapshots taken.
+pub fn record_while(
+    swapper: &DirSwapper,
+    watcher: &DirWatcher,
+    policy: SnapshotPolicy,
+    stop: &AtomicBool,
+) -> Result<usize> {
+    let mut count = 0;
+    while !stop.load(Ordering::Relaxed) {
+        let deadline = Instant::now() + STOP_CHECK_INTERVAL;
+        if watcher.wait_for_changes(policy.debounce, deadline)?
+            && swapper.record_history(policy.history_limit)?.is_some()
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_millis(300);
+
+    fn assert_detects_writes(watcher: DirWatcher, dir: &Path) {
+        let deadline = || Instant::now() + Duration::from_secs(5);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(200));
+                fs::write(dir.join("save.dat"), "1").unwrap();
+            });
+            assert!(watcher.wait_for_changes(DEBOUNCE, deadline()).unwrap());
+        });
+        let quiet = Instant::now() + Duration::from_millis(500);
+        assert!(!watcher.wait_for_changes(DEBOUNCE, quiet).unwrap());
+    }
+
+    #[test]
+    fn native_watcher_detects_writes() {
+        let dir = tempdir().unwrap();
+        let watcher = DirWatcher::new(dir.path(), Duration::from_millis(100)).unwrap();
+        assert_detects_writes(watcher, dir.path());
+    }
+
+    #[test]
+    fn polling_watcher_detects_writes() {
+        let dir = tempdir().unwrap();
+        let watcher = DirWatcher::polling(dir.path(), Duration::from_millis(100)).unwrap();
+        assert_eq!(watcher.backend(), WatchBackend::Polling);
+        assert_detects_writes(watcher, dir.path());
+    }
+
+    #[test]
+    fn snapshots_are_recorded_after_writes() {
+        let primary_dir = tempdir().unwrap();
+        let version_dir = tempdir().unwrap();
+        let swapper = DirSwapper::build(
+            primary_dir.path().to_path_buf(),
+            version_dir.path().to_path_buf(),
+            "main".parse().unwrap(),
+        )
+        .unwrap();
+        let policy = SnapshotPolicy {
+            debounce: DEBOUNCE,
+            history_limit: 5,
+            poll_interval: Duration::from_millis(100),
+        };
+        let watcher = DirWatcher::new(primary_dir.path(), policy.poll_interval).unwrap();
+        let stop = AtomicBool::new(false);
+        let count = thread::scope(|scope| {
+            let (swapper, stop_ref) = (&swapper, &stop);
+            let recorder = scope.spawn(move || record_while(swapper, &watcher, policy, stop_ref));
+            thread::sleep(Duration::from_millis(200));
+            fs::write(primary_dir.path().join("save.dat"), "autosave").unwrap();
+            thread::sleep(DEBOUNCE * 4);
+            stop.store(true, Ordering::Relaxed);
+            recorder.join().unwrap().unwrap()
+        });
+        assert_eq!(count, 1);
+        let history = swapper.history(&"main".parse().unwrap()).unwrap();
+        assert_eq!(history.len(), 1);
+        // The history is kept under the generated directory name of the slot
+        let slot_history = fs::read_dir(version_dir.path().join(".history"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let snapshot = slot_history.join(&history[0]);
+        assert_eq!(
+            fs::read_to_string(snapshot.join("save.dat")).unwrap(),
+            "autosave"
+        );
+    }
+}